@@ -1,9 +1,7 @@
 use serde::Deserialize;
 use serde::Serialize;
-use serde::{de, ser};
-use serde_molecule::de::MoleculeDeserializer;
 use serde_molecule::from_slice;
-use serde_molecule::struct_serde::CollectData;
+use serde_molecule::molecule;
 use serde_molecule::to_vec;
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
@@ -24,70 +22,31 @@ pub struct Table1 {
 //     Table1:  4278190082,
 // }
 //
-#[derive(Clone, PartialEq, Debug)]
+// `#[molecule(union)]` must be placed before `#[derive(Serialize, Deserialize)]`.
+#[molecule(union)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub enum UnionCustomizedId {
+    #[molecule(union_id = 4278190081)]
     S1(Struct1),
+    // the id of previous variant plus one: 4278190082
     T1(Table1),
 }
 
-// example of serialization of customized union id
-impl Serialize for UnionCustomizedId {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        match self {
-            UnionCustomizedId::S1(s1) => {
-                let mut data: Vec<u8> = (4278190081u32).to_le_bytes().into();
-                data.extend(
-                    to_vec(s1, false)
-                        .map_err(|_| ser::Error::custom("failed to serialized Struct1"))?,
-                );
-                serializer.serialize_bytes(&data)
-            }
-            UnionCustomizedId::T1(t1) => {
-                let mut data: Vec<u8> = (4278190082u32).to_le_bytes().into();
-                data.extend(
-                    to_vec(t1, false)
-                        .map_err(|_| ser::Error::custom("failed to serialize Table1"))?,
-                );
-                serializer.serialize_bytes(&data)
-            }
-        }
-    }
-}
-
-// example of deserialization of customized union id
-impl<'de> Deserialize<'de> for UnionCustomizedId {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: serde::Deserializer<'de>,
-    {
-        let data = CollectData::deserialize(deserializer)?.data;
-        let id = u32::from_le_bytes(data.as_slice()[0..4].try_into().unwrap());
-        let mut de = MoleculeDeserializer::new(&data[4..]);
-
-        match id {
-            4278190081u32 => {
-                let s1 = Struct1::deserialize(&mut de).map_err(de::Error::custom)?;
-                Ok(UnionCustomizedId::S1(s1))
-            }
-            4278190082u32 => {
-                let t1 = Table1::deserialize(&mut de).map_err(de::Error::custom)?;
-                Ok(UnionCustomizedId::T1(t1))
-            }
-            _ => Err(de::Error::custom("invalid union id")),
-        }
-    }
-}
-
-fn test_once(value: &UnionCustomizedId) {
+fn test_once(value: &UnionCustomizedId, id: u32) {
     let bytes = to_vec(&value, false).unwrap();
+    assert_eq!(bytes[0..4], id.to_le_bytes());
     let value2: UnionCustomizedId = from_slice(&bytes, false).unwrap();
     assert_eq!(value, &value2);
 }
 
 fn main() {
     let value = UnionCustomizedId::S1(Struct1 { f1: 100, f2: 200 });
-    test_once(&value);
+    test_once(&value, 4278190081);
+    let value = UnionCustomizedId::T1(Table1 {
+        f1: 1,
+        f2: vec![2, 3],
+    });
+    test_once(&value, 4278190082);
+    // serde_json isn't affected by the union ids
+    println!("{}", serde_json::to_string(&value).unwrap());
 }
@@ -0,0 +1,393 @@
+//! Generate Rust types from a molecule schema.
+//!
+//! The generated types are plain structs and enums deriving `Serialize` and
+//! `Deserialize`, annotated with [`struct_serde`](crate::struct_serde),
+//! [`dynvec_serde`](crate::dynvec_serde) and
+//! [`big_array_serde`](crate::big_array_serde) where needed, like the types
+//! written by hand. Call it from `build.rs`:
+//!
+//! ```ignore
+//! // build.rs
+//! fn main() {
+//!     let out_dir = std::env::var("OUT_DIR").unwrap();
+//!     println!("cargo:rerun-if-changed=schemas");
+//!     serde_molecule::codegen::generate_file(
+//!         "schemas/blockchain.mol",
+//!         format!("{}/blockchain.rs", out_dir),
+//!     )
+//!     .unwrap();
+//! }
+//!
+//! // src/lib.rs
+//! pub mod blockchain {
+//!     include!(concat!(env!("OUT_DIR"), "/blockchain.rs"));
+//! }
+//! ```
+//!
+//! The molecule types are mapped to:
+//! * `byte`: `u8`.
+//! * Arrays of bytes named `Uint16`, `U32`, `Int64`, `I128` and so on, with
+//!   the matching size: the integers. Other arrays: `[T; N]`.
+//! * `vector String <byte>`: `String`. Other vectors: `Vec<T>`.
+//! * Vectors of tables with two fields `key` and `value`: `BTreeMap<K, V>`,
+//!   if the key is a number or bytes.
+//! * Options: `Option<T>`.
+//! * Structs and tables: structs with the same fields.
+//! * Unions: enums with a newtype variant per item, named by the item type.
+//!   Unions with customized ids use `#[molecule(union)]`, which needs the
+//!   `derive` feature.
+//!
+//! Arrays, vectors and options are type aliases. If they need an annotation
+//! and are used as items of other arrays, vectors or options, where
+//! annotations can't be given, they are newtype structs instead, e.g.
+//! `pub struct BytesVec(#[serde(with = "...")] pub Vec<Bytes>);`.
+//!
+//! `String`, `Vec` and `BTreeMap` are imported from [`prelude`], so the
+//! generated code also compiles in `no_std` crates.
+
+use crate::error::{Error, Result};
+use crate::schema::{Decl, DeclKind, Field, Schema};
+use alloc::collections::BTreeSet;
+use alloc::format;
+use alloc::string::{String, ToString};
+use serde::de::Error as _;
+
+const DERIVE: &str = "#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Debug)]";
+
+/// The `alloc` types used by the generated code.
+pub mod prelude {
+    pub use alloc::collections::BTreeMap;
+    pub use alloc::string::String;
+    pub use alloc::vec::Vec;
+}
+
+/// Generate the Rust source of the types in `schema`, including the imported
+/// ones.
+pub fn generate(schema: &Schema) -> Result<String> {
+    let generator = Generator::new(schema);
+    let mut code =
+        String::from("// Generated by serde_molecule from a molecule schema, don't edit.\n");
+    code.push_str("\n#[allow(unused_imports)]\nuse serde_molecule::codegen::prelude::*;\n");
+    let mut block = true;
+    for decl in schema.decls() {
+        if let Some(item) = generator.item(decl)? {
+            // blank lines around multi-line items
+            let multiline = item.trim_end().contains('\n');
+            if multiline || block {
+                code.push('\n');
+            }
+            block = multiline;
+            code.push_str(&item);
+        }
+    }
+    Ok(code)
+}
+
+/// Load the schema file `mol` with its imports, and write the generated Rust
+/// source to `out`. It's meant to be called from `build.rs`.
+#[cfg(feature = "std")]
+pub fn generate_file(
+    mol: impl AsRef<std::path::Path>,
+    out: impl AsRef<std::path::Path>,
+) -> Result<()> {
+    let code = generate(&Schema::load(mol)?)?;
+    std::fs::write(out, code).map_err(Error::Io)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Annotation {
+    Struct,
+    Dynvec,
+    BigArray,
+}
+
+impl Annotation {
+    fn attribute(self) -> &'static str {
+        match self {
+            Annotation::Struct => "#[serde(with = \"serde_molecule::struct_serde\")]",
+            Annotation::Dynvec => "#[serde(with = \"serde_molecule::dynvec_serde\")]",
+            Annotation::BigArray => "#[serde(with = \"serde_molecule::big_array_serde\")]",
+        }
+    }
+}
+
+// serde implements the traits for arrays up to 32 items.
+const MAX_ARRAY: usize = 32;
+
+struct Generator<'a> {
+    schema: &'a Schema,
+    // items of arrays, vectors and options
+    nested: BTreeSet<&'a str>,
+}
+
+impl<'a> Generator<'a> {
+    fn new(schema: &'a Schema) -> Self {
+        let mut nested = BTreeSet::new();
+        for decl in schema.decls() {
+            match &decl.kind {
+                DeclKind::Array { item, .. } | DeclKind::Vector(item) | DeclKind::Option(item) => {
+                    nested.insert(item.as_str());
+                }
+                _ => {}
+            }
+        }
+        Generator { schema, nested }
+    }
+
+    fn decl(&self, ty: &str) -> Option<&'a Decl> {
+        self.schema.get(ty)
+    }
+
+    // The Rust item of a declaration, `None` if it's not needed.
+    fn item(&self, decl: &Decl) -> Result<Option<String>> {
+        let name = &decl.name;
+        Ok(Some(match &decl.kind {
+            DeclKind::Struct(fields) | DeclKind::Table(fields) => {
+                let is_struct = matches!(decl.kind, DeclKind::Struct(_));
+                let mut code = format!("{}\npub struct {} {{\n", DERIVE, name);
+                for field in fields {
+                    code.push_str(&self.field(field, is_struct)?);
+                }
+                code.push_str("}\n");
+                code
+            }
+            DeclKind::Union(items) => {
+                let mut code = String::new();
+                let custom = items
+                    .iter()
+                    .enumerate()
+                    .any(|(i, item)| item.id as usize != i);
+                if custom {
+                    code.push_str("#[serde_molecule::molecule(union)]\n");
+                }
+                code.push_str(&format!("{}\npub enum {} {{\n", DERIVE, name));
+                let mut next = 0u64;
+                for item in items {
+                    if custom && u64::from(item.id) != next {
+                        code.push_str(&format!("    #[molecule(union_id = {})]\n", item.id));
+                    }
+                    next = u64::from(item.id) + 1;
+                    let attribute = match self.annotation(&item.ty)? {
+                        Some(annotation) => format!("{} ", annotation.attribute()),
+                        None => String::new(),
+                    };
+                    code.push_str(&format!(
+                        "    {}({}{}),\n",
+                        variant_name(&item.ty),
+                        attribute,
+                        self.rust_type(&item.ty)
+                    ));
+                }
+                code.push_str("}\n");
+                code
+            }
+            _ if is_string(decl) => return Ok(None),
+            _ => {
+                let ty = self.definition(decl)?;
+                match self.own_annotation(decl)? {
+                    Some(annotation) if self.nested.contains(name.as_str()) => format!(
+                        "{}\npub struct {}({} pub {});\n",
+                        DERIVE,
+                        name,
+                        annotation.attribute(),
+                        ty
+                    ),
+                    _ => format!("pub type {} = {};\n", name, ty),
+                }
+            }
+        }))
+    }
+
+    fn field(&self, field: &Field, is_struct: bool) -> Result<String> {
+        // fields of molecule structs are molecule structs already
+        let annotation = match self.annotation(&field.ty)? {
+            Some(Annotation::Struct) if is_struct => None,
+            annotation => annotation,
+        };
+        let mut code = String::new();
+        if let Some(annotation) = annotation {
+            code.push_str(&format!("    {}\n", annotation.attribute()));
+        }
+        let name = field_name(&field.name);
+        if name.trim_start_matches("r#") != field.name {
+            code.push_str(&format!("    #[serde(rename = \"{}\")]\n", field.name));
+        }
+        code.push_str(&format!(
+            "    pub {}: {},\n",
+            name,
+            self.rust_type(&field.ty)
+        ));
+        Ok(code)
+    }
+
+    // The annotation needed by a field or variant of type `ty`.
+    fn annotation(&self, ty: &str) -> Result<Option<Annotation>> {
+        match self.decl(ty) {
+            None => Ok(None),
+            Some(decl) => match &decl.kind {
+                DeclKind::Struct(_) => Ok(Some(Annotation::Struct)),
+                // newtype structs are annotated inside
+                _ if self.nested.contains(ty) => Ok(None),
+                _ => self.own_annotation(decl),
+            },
+        }
+    }
+
+    // The annotation needed by an array, vector or option.
+    fn own_annotation(&self, decl: &Decl) -> Result<Option<Annotation>> {
+        Ok(match &decl.kind {
+            DeclKind::Array { item, count } => {
+                if number(decl).is_some() {
+                    None
+                } else if *count > MAX_ARRAY {
+                    if item != "byte" && self.decl(item).and_then(number).is_none() {
+                        return Err(Error::custom(format!(
+                            "array `{}` of more than {} items must be bytes or numbers",
+                            decl.name, MAX_ARRAY
+                        )));
+                    }
+                    Some(Annotation::BigArray)
+                } else if item == "byte" {
+                    None
+                } else {
+                    // items are molecule structs
+                    Some(Annotation::Struct)
+                }
+            }
+            // maps are dynvecs already
+            DeclKind::Vector(item)
+                if self.schema.fixed_size(item).is_none() && self.map(item).is_none() =>
+            {
+                Some(Annotation::Dynvec)
+            }
+            DeclKind::Option(item) => match self.decl(item).map(|d| &d.kind) {
+                Some(DeclKind::Struct(_)) => Some(Annotation::Struct),
+                Some(DeclKind::Array { .. }) => match self.annotation(item)? {
+                    Some(Annotation::Struct) => Some(Annotation::Struct),
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        })
+    }
+
+    // The Rust type of an array, vector or option.
+    fn definition(&self, decl: &Decl) -> Result<String> {
+        Ok(match &decl.kind {
+            DeclKind::Array { item, count } => match number(decl) {
+                Some(ty) => ty.to_string(),
+                None => format!("[{}; {}]", self.rust_type(item), count),
+            },
+            DeclKind::Vector(item) => match self.map(item) {
+                Some((key, value)) => format!(
+                    "BTreeMap<{}, {}>",
+                    self.rust_type(key),
+                    self.rust_type(value)
+                ),
+                None => format!("Vec<{}>", self.rust_type(item)),
+            },
+            DeclKind::Option(item) => format!("Option<{}>", self.rust_type(item)),
+            _ => unreachable!(),
+        })
+    }
+
+    // The key and value types if a vector of `item` is a map.
+    fn map(&self, item: &str) -> Option<(&'a str, &'a str)> {
+        let fields = match &self.decl(item)?.kind {
+            DeclKind::Table(fields) if fields.len() == 2 => fields,
+            _ => return None,
+        };
+        if fields[0].name != "key" || fields[1].name != "value" {
+            return None;
+        }
+        let (key, value) = (fields[0].ty.as_str(), fields[1].ty.as_str());
+        // the key must be `Ord`, and annotations can't be given
+        let ordered = match self.decl(key) {
+            None => true,
+            Some(decl) => match &decl.kind {
+                DeclKind::Array { item, .. } => item == "byte",
+                DeclKind::Vector(item) => item == "byte",
+                _ => false,
+            },
+        };
+        let plain = |ty| matches!(self.annotation(ty), Ok(None));
+        if ordered && plain(key) && plain(value) {
+            Some((key, value))
+        } else {
+            None
+        }
+    }
+
+    fn rust_type(&self, ty: &str) -> String {
+        match self.decl(ty) {
+            None => "u8".to_string(),
+            Some(decl) if is_string(decl) => "String".to_string(),
+            Some(_) => ty.to_string(),
+        }
+    }
+}
+
+// `vector String <byte>` is `String`.
+fn is_string(decl: &Decl) -> bool {
+    decl.name == "String" && matches!(&decl.kind, DeclKind::Vector(item) if item == "byte")
+}
+
+// The integer type of an array of bytes named like `Uint32` or `I64`.
+fn number(decl: &Decl) -> Option<&'static str> {
+    let count = match &decl.kind {
+        DeclKind::Array { item, count } if item == "byte" => *count,
+        _ => return None,
+    };
+    let name = decl.name.as_str();
+    let (signed, bits) = if let Some(bits) = name.strip_prefix("Uint") {
+        (false, bits)
+    } else if let Some(bits) = name.strip_prefix("Int") {
+        (true, bits)
+    } else if let Some(bits) = name.strip_prefix('U') {
+        (false, bits)
+    } else if let Some(bits) = name.strip_prefix('I') {
+        (true, bits)
+    } else {
+        return None;
+    };
+    Some(match (signed, bits, count) {
+        (false, "16", 2) => "u16",
+        (false, "32", 4) => "u32",
+        (false, "64", 8) => "u64",
+        (false, "128", 16) => "u128",
+        (true, "16", 2) => "i16",
+        (true, "32", 4) => "i32",
+        (true, "64", 8) => "i64",
+        (true, "128", 16) => "i128",
+        _ => return None,
+    })
+}
+
+fn variant_name(ty: &str) -> String {
+    let mut chars = ty.chars();
+    match chars.next() {
+        Some(c) => c.to_ascii_uppercase().to_string() + chars.as_str(),
+        None => String::new(),
+    }
+}
+
+// Molecule field names can be Rust keywords, e.g. `type`. The ones that
+// can't be raw identifiers get a trailing `_`, and are renamed for serde.
+fn field_name(name: &str) -> String {
+    if matches!(name, "crate" | "self" | "super" | "Self") {
+        return format!("{}_", name);
+    }
+    const KEYWORDS: &[&str] = &[
+        "as", "async", "await", "box", "break", "const", "continue", "dyn", "else", "enum",
+        "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+        "mut", "pub", "ref", "return", "static", "struct", "trait", "true", "try", "type",
+        "unsafe", "use", "where", "while", "yield", "abstract", "become", "do", "final", "macro",
+        "override", "priv", "typeof", "unsized", "virtual", "gen",
+    ];
+    if KEYWORDS.contains(&name) {
+        format!("r#{}", name)
+    } else {
+        name.to_string()
+    }
+}
@@ -1,16 +1,72 @@
 //! Deserialize molecule data to a Rust data structure.
+use crate::ser::to_vec;
 use crate::{
-    error::{Error, Result},
-    molecule::{disassemble_fixvec, disassemble_table, unpack_number},
+    error::{Error, Result, Segment},
+    molecule::{
+        disassemble_fixvec, disassemble_table, fixvec_item_count, table_item_count, unpack_number,
+    },
     struct_serde::MoleculeStructDeserializer,
 };
 use alloc::string::String;
 use alloc::vec;
 use alloc::vec::Vec;
-use serde::de::{self, value::U64Deserializer};
+use serde::de::{
+    self,
+    value::{SeqDeserializer, U32Deserializer, U64Deserializer},
+};
+use serde::Serialize;
 
 //////////////////////////////////////////////////////////////////////////////
 pub(crate) const DYNVEC_STR: &str = "$serde_molecule::DynVec";
+pub(crate) const STRUCT_STR: &str = "$serde_molecule::Struct";
+pub(crate) const RAW_STR: &str = "$serde_molecule::Raw";
+// Prefix of the names of types marked by `#[molecule(struct)]`, the same as
+// the one used by `serde_molecule_derive`.
+pub(crate) const STRUCT_PREFIX: &str = "$serde_molecule::Struct::";
+
+// Prefix of the names of enums marked by `#[molecule(union)]`, followed by
+// the enum name and the union ids: `$serde_molecule::Union::Name::1,2,3`. The
+// id of the fallback variant is `*`.
+pub(crate) const UNION_PREFIX: &str = "$serde_molecule::Union::";
+
+/// The name of a type marked by `#[molecule(struct)]`, without the prefix.
+pub(crate) fn struct_name(name: &str) -> Option<&str> {
+    name.strip_prefix(STRUCT_PREFIX)
+}
+
+/// The name and union ids of an enum marked by `#[molecule(union)]`.
+pub(crate) fn union_ids(name: &str) -> Option<(&str, UnionIds<'_>)> {
+    let (name, ids) = name.strip_prefix(UNION_PREFIX)?.rsplit_once("::")?;
+    Some((name, UnionIds(ids)))
+}
+
+/// The union ids of the variants of an enum, in the order of variants.
+#[derive(Clone, Copy)]
+pub(crate) struct UnionIds<'a>(&'a str);
+
+impl<'a> UnionIds<'a> {
+    /// The union ids, `None` for the fallback variant.
+    pub fn iter(self) -> impl Iterator<Item = Option<u32>> + 'a {
+        self.0.split(',').map(|id| id.parse().ok())
+    }
+
+    /// The union id of the `index`-th variant.
+    pub fn get(self, index: usize) -> Option<u32> {
+        self.iter().nth(index).flatten()
+    }
+
+    /// The index of the fallback variant, which takes unknown union ids.
+    pub fn fallback(self) -> Option<usize> {
+        self.iter().position(|id| id.is_none())
+    }
+
+    /// The variant index of union id `id`, or the fallback variant.
+    pub fn position(self, id: usize) -> Option<usize> {
+        self.iter()
+            .position(|i| i.is_some_and(|i| i as usize == id))
+            .or_else(|| self.fallback())
+    }
+}
 
 //////////////////////////////////////////////////////////////////////////////
 /// Deserialize an instance of type `T` from bytes of molecule.
@@ -21,25 +77,286 @@ pub fn from_slice<'a, T>(v: &'a [u8], is_struct: bool) -> Result<T>
 where
     T: de::Deserialize<'a>,
 {
-    if is_struct {
-        let mut de = MoleculeStructDeserializer::new(v.to_vec());
+    from_slice_with(v, &DeserializerOptions::new().is_struct(is_struct))
+}
+
+/// Deserialize an instance of type `T` from bytes of molecule, with options.
+///
+/// ```
+/// use serde_molecule::{from_slice_with, to_vec, DeserializerOptions};
+///
+/// let bytes = to_vec(&vec![1u8, 2, 3], false).unwrap();
+/// let options = DeserializerOptions::new().compatible(false);
+/// let value: Vec<u8> = from_slice_with(&bytes, &options).unwrap();
+/// assert_eq!(value, vec![1, 2, 3]);
+/// ```
+pub fn from_slice_with<'a, T>(v: &'a [u8], options: &DeserializerOptions) -> Result<T>
+where
+    T: de::Deserialize<'a>,
+{
+    if options.is_struct {
+        let mut de = MoleculeStructDeserializer::new(v, 0, options.canonical);
         let value = de::Deserialize::deserialize(&mut de)?;
+        de.end()?;
         Ok(value)
     } else {
-        let mut de = MoleculeDeserializer::new(v);
+        let mut de = MoleculeDeserializer::with_options(v, options);
         let value = de::Deserialize::deserialize(&mut de)?;
         Ok(value)
     }
 }
 
+/// Deserialize an instance of type `T` from bytes of molecule, and make sure
+/// the bytes are the canonical encoding of `T`: serializing the returned value
+/// with [`to_vec`](crate::to_vec) reproduces exactly the same bytes.
+///
+/// The input is decoded with `canonical(true)`, then the value is serialized
+/// again and compared with the input. It fails with `Error::NonCanonical` at
+/// the first different byte, e.g. for unsorted `BTreeMap` keys. Types without
+/// a canonical encoding, like `HashMap`, are usually rejected.
+///
+/// ```
+/// use serde_molecule::{from_slice_canonical, to_vec, DeserializerOptions};
+///
+/// let bytes = to_vec(&vec![1u8, 2, 3], false).unwrap();
+/// let value: Vec<u8> = from_slice_canonical(&bytes, &DeserializerOptions::new()).unwrap();
+/// assert_eq!(value, vec![1, 2, 3]);
+/// ```
+pub fn from_slice_canonical<'a, T>(v: &'a [u8], options: &DeserializerOptions) -> Result<T>
+where
+    T: de::Deserialize<'a> + Serialize,
+{
+    let options = options.canonical(true);
+    let value: T = from_slice_with(v, &options)?;
+    let bytes = to_vec(&value, options.is_struct)?;
+    if bytes != v {
+        let offset = bytes
+            .iter()
+            .zip(v)
+            .position(|(a, b)| a != b)
+            .unwrap_or_else(|| bytes.len().min(v.len()));
+        return Err(Error::NonCanonical { offset });
+    }
+    Ok(value)
+}
+
+/// Options of deserialization, used by [`from_slice_with`].
+///
+/// The default options are the same as [`from_slice`] with `is_struct` false.
+#[derive(Clone, Copy, Debug)]
+pub struct DeserializerOptions {
+    pub(crate) is_struct: bool,
+    pub(crate) compatible: bool,
+    pub(crate) lossy_utf8: bool,
+    pub(crate) canonical: bool,
+    pub(crate) max_depth: usize,
+    pub(crate) max_items: usize,
+    pub(crate) max_alloc: usize,
+}
+
+impl Default for DeserializerOptions {
+    fn default() -> Self {
+        DeserializerOptions {
+            is_struct: false,
+            compatible: true,
+            lossy_utf8: false,
+            canonical: false,
+            max_depth: 128,
+            max_items: usize::MAX,
+            max_alloc: usize::MAX,
+        }
+    }
+}
+
+impl DeserializerOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Map the top-level type to molecule struct. Set to false(default) to
+    /// map to molecule table.
+    pub fn is_struct(mut self, is_struct: bool) -> Self {
+        self.is_struct = is_struct;
+        self
+    }
+
+    /// Accept tables with more fields than the Rust type, which are written by
+    /// a newer version of the schema. The extra fields are ignored. It's the
+    /// same as `verify(compatible)` of molecule. Defaults to true. Set to false
+    /// to require the exact field count.
+    pub fn compatible(mut self, compatible: bool) -> Self {
+        self.compatible = compatible;
+        self
+    }
+
+    /// Replace invalid UTF-8 sequences in strings with U+FFFD instead of
+    /// returning `Error::InvalidUtf8`. The decoded string can't be serialized
+    /// back to the original bytes. Defaults to false.
+    pub fn lossy_utf8(mut self, lossy_utf8: bool) -> Self {
+        self.lossy_utf8 = lossy_utf8;
+        self
+    }
+
+    /// Reject the encodings that `to_vec` never produces: `bool` other than 0
+    /// or 1, unit variants with a body, structs with trailing bytes, tables
+    /// with extra fields and maps with duplicated keys. It implies
+    /// `compatible(false)` and `lossy_utf8(false)`. Defaults to false.
+    ///
+    /// The order of map keys isn't checked, use [`from_slice_canonical`] to
+    /// verify the whole encoding.
+    pub fn canonical(mut self, canonical: bool) -> Self {
+        self.canonical = canonical;
+        self
+    }
+
+    /// The maximum nesting depth of tables, dynvecs, fixvecs, maps, unions and
+    /// options. Deeper input fails with `Error::DepthLimitExceeded` instead of
+    /// overflowing the stack. Defaults to 128.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// The maximum item count of a single fixvec, dynvec, table or map, checked
+    /// against its header before any item is decoded. Exceeding it fails with
+    /// `Error::ItemLimitExceeded`. Defaults to unlimited.
+    pub fn max_items(mut self, max_items: usize) -> Self {
+        self.max_items = max_items;
+        self
+    }
+
+    /// The budget in bytes of all allocations made while decoding: the item
+    /// lists built from headers and the owned strings and byte buffers.
+    /// Exceeding it fails with `Error::AllocLimitExceeded`. Defaults to
+    /// unlimited.
+    pub fn max_alloc(mut self, max_alloc: usize) -> Self {
+        self.max_alloc = max_alloc;
+        self
+    }
+}
+
 /// A structure that deserializes molecule into Rust values.
 pub struct MoleculeDeserializer<'de> {
     data: &'de [u8],
+    // the position of `data` in the whole input
+    offset: usize,
+    options: DeserializerOptions,
+    depth: usize,
+    // bytes allocated so far, checked against `max_alloc`
+    allocated: usize,
 }
 
 impl<'de> MoleculeDeserializer<'de> {
     pub fn new(data: &'de [u8]) -> Self {
-        MoleculeDeserializer { data }
+        Self::with_options(data, &DeserializerOptions::default())
+    }
+
+    /// Creates a deserializer with options. `is_struct` in options is ignored:
+    /// this deserializer always starts with molecule table.
+    pub fn with_options(data: &'de [u8], options: &DeserializerOptions) -> Self {
+        MoleculeDeserializer {
+            data,
+            offset: 0,
+            options: *options,
+            depth: 0,
+            allocated: 0,
+        }
+    }
+
+    // Run `f` one level deeper, failing if `max_depth` is reached.
+    fn nested<R>(&mut self, f: impl FnOnce(&mut Self) -> Result<R>) -> Result<R> {
+        if self.depth >= self.options.max_depth {
+            return Err(Error::DepthLimitExceeded);
+        }
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    // Deserialize the data as a molecule struct, without copying it.
+    fn packed<R>(
+        &mut self,
+        f: impl FnOnce(&mut MoleculeStructDeserializer<'de>) -> Result<R>,
+    ) -> Result<R> {
+        let mut de =
+            MoleculeStructDeserializer::new(self.data, self.offset, self.options.canonical);
+        let value = f(&mut de)?;
+        de.end()?;
+        Ok(value)
+    }
+
+    // Check the item count of a header before building a list of `T` for it.
+    fn reserve_items<T>(&mut self, count: usize) -> Result<()> {
+        if count > self.options.max_items {
+            return Err(Error::ItemLimitExceeded);
+        }
+        self.charge(count.saturating_mul(core::mem::size_of::<T>()))
+    }
+
+    fn charge(&mut self, size: usize) -> Result<()> {
+        self.allocated = self.allocated.saturating_add(size);
+        if self.allocated > self.options.max_alloc {
+            Err(Error::AllocLimitExceeded)
+        } else {
+            Ok(())
+        }
+    }
+
+    // Visit the str, borrowed from input if it's valid UTF-8. It's charged
+    // once before allocating: if it's `owned`, the visitor copies it into a
+    // `String`, and lossy decoding allocates one.
+    fn str<V>(&mut self, visitor: V, owned: bool) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let s = self.disassemble_bytes()?;
+        match core::str::from_utf8(s) {
+            Ok(v) => {
+                if owned {
+                    self.charge(v.len())?;
+                }
+                visitor.visit_borrowed_str(v)
+            }
+            Err(_) if self.options.lossy_utf8 && !self.options.canonical => {
+                // an invalid sequence is replaced by U+FFFD of 3 bytes
+                let size = s
+                    .utf8_chunks()
+                    .map(|chunk| chunk.valid().len() + 3 * !chunk.invalid().is_empty() as usize)
+                    .sum();
+                self.charge(size)?;
+                visitor.visit_string(String::from_utf8_lossy(s).into_owned())
+            }
+            Err(e) => Err(Error::InvalidUtf8 {
+                offset: self.offset + 4 + e.valid_up_to(),
+            }),
+        }
+    }
+
+    // The position of `part`, a sub-slice of current data, in the whole input.
+    fn offset_of(&self, part: &[u8]) -> usize {
+        self.offset + (part.as_ptr() as usize - self.data.as_ptr() as usize)
+    }
+
+    // Deserialize `part`, which is a sub-slice of current data, with the same
+    // deserializer. The current data is restored afterwards. Errors are located
+    // by `part` and `segment`.
+    fn deserialize_part<T>(
+        &mut self,
+        part: &'de [u8],
+        segment: Segment,
+        seed: T,
+    ) -> Result<T::Value>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        let part_offset = self.offset_of(part);
+        let data = core::mem::replace(&mut self.data, part);
+        let offset = core::mem::replace(&mut self.offset, part_offset);
+        let result = seed.deserialize(&mut *self);
+        self.data = data;
+        self.offset = offset;
+        result.map_err(|e| e.at(part_offset, segment))
     }
 }
 
@@ -47,7 +364,10 @@ macro_rules! as_primitive {
     ($method:ident, $type: ty, $len: expr) => {
         fn $method(&self) -> Result<$type> {
             if self.data.len() != $len {
-                return Err(Error::MismatchedLength);
+                return Err(Error::MismatchedLength {
+                    expected: $len,
+                    actual: self.data.len(),
+                });
             } else {
                 let bytes: [u8; $len] = self.data.try_into().unwrap();
                 Ok(<$type>::from_le_bytes(bytes))
@@ -94,10 +414,13 @@ impl<'de> de::Deserializer<'de> for &mut MoleculeDeserializer<'de> {
     where
         V: de::Visitor<'de>,
     {
-        if self.as_u8()? == 0 {
-            visitor.visit_bool(false)
-        } else {
-            visitor.visit_bool(true)
+        match self.as_u8()? {
+            0 => visitor.visit_bool(false),
+            1 => visitor.visit_bool(true),
+            _ if self.options.canonical => Err(Error::NonCanonical {
+                offset: self.offset,
+            }),
+            _ => visitor.visit_bool(true),
         }
     }
     fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value>
@@ -189,28 +512,27 @@ impl<'de> de::Deserializer<'de> for &mut MoleculeDeserializer<'de> {
     where
         V: de::Visitor<'de>,
     {
-        self.deserialize_string(visitor)
+        self.str(visitor, false)
     }
 
     fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        let s = self.disassemble_bytes()?;
-        let v = String::from_utf8_lossy(s);
-        visitor.visit_string(v.into_owned())
+        self.str(visitor, true)
     }
 
     fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_bytes(self.data)
+        visitor.visit_borrowed_bytes(self.disassemble_bytes()?)
     }
     fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
+        self.charge(self.data.len().saturating_sub(4))?;
         self.deserialize_bytes(visitor)
     }
 
@@ -221,7 +543,7 @@ impl<'de> de::Deserializer<'de> for &mut MoleculeDeserializer<'de> {
         if self.data.is_empty() {
             visitor.visit_none()
         } else {
-            visitor.visit_some(self)
+            self.nested(|de| visitor.visit_some(de))
         }
     }
 
@@ -229,6 +551,12 @@ impl<'de> de::Deserializer<'de> for &mut MoleculeDeserializer<'de> {
     where
         V: de::Visitor<'de>,
     {
+        // e.g. the body of a unit variant
+        if self.options.canonical && !self.data.is_empty() {
+            return Err(Error::NonCanonical {
+                offset: self.offset,
+            });
+        }
         visitor.visit_unit()
     }
 
@@ -240,52 +568,77 @@ impl<'de> de::Deserializer<'de> for &mut MoleculeDeserializer<'de> {
     }
 
     /// Parses a newtype struct as the underlying value.
-    fn deserialize_newtype_struct<V>(self, name: &str, visitor: V) -> Result<V::Value>
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        let _ = name;
-        visitor.visit_newtype_struct(self)
+        if name == STRUCT_STR {
+            // from `struct_serde`: the data is a molecule struct, or empty
+            // for `None`.
+            self.packed(|de| {
+                de.whole = true;
+                visitor.visit_newtype_struct(de)
+            })
+        } else if struct_name(name).is_some() {
+            // from `#[molecule(struct)]`
+            self.packed(|de| de.deserialize_newtype_struct(name, visitor))
+        } else if name == RAW_STR {
+            // from `CollectData`: the whole data is returned without decoding.
+            visitor.visit_borrowed_bytes(self.data)
+        } else {
+            self.nested(|de| visitor.visit_newtype_struct(de))
+        }
     }
 
     fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        let mut access = FixvecAccess::new(self);
-        access.parse()?;
-        visitor.visit_seq(access)
+        self.nested(|de| {
+            let mut access = FixvecAccess::new(de);
+            access.parse()?;
+            visitor.visit_seq(access)
+        })
     }
 
     fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        let mut access = ArrayAccess::new(self, len);
-        access.parse()?;
-        visitor.visit_seq(access)
+        self.nested(|de| {
+            let mut access = ArrayAccess::new(de, len);
+            access.parse()?;
+            visitor.visit_seq(access)
+        })
     }
     fn deserialize_tuple_struct<V>(
         self,
-        _name: &'static str,
+        name: &'static str,
         len: usize,
         visitor: V,
     ) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        let mut access = TableAccess::new(self, len);
-        access.parse()?;
-        visitor.visit_seq(access)
+        if struct_name(name).is_some() {
+            return self.packed(|de| de.deserialize_tuple_struct(name, len, visitor));
+        }
+        self.nested(|de| {
+            let mut access = TableAccess::new(de, len, &[]);
+            access.parse()?;
+            visitor.visit_seq(access)
+        })
     }
 
     fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        let mut access = MappingAccess::new(self);
-        access.parse()?;
-        visitor.visit_map(access)
+        self.nested(|de| {
+            let mut access = MappingAccess::new(de);
+            access.parse()?;
+            visitor.visit_map(access)
+        })
     }
 
     fn deserialize_struct<V>(
@@ -297,26 +650,32 @@ impl<'de> de::Deserializer<'de> for &mut MoleculeDeserializer<'de> {
     where
         V: de::Visitor<'de>,
     {
-        if name == DYNVEC_STR {
-            let mut access = DynvecAccess::new(self);
-            access.parse()?;
-            visitor.visit_map(access)
-        } else {
-            let mut access = TableAccess::new(self, fields.len());
-            access.parse()?;
-            visitor.visit_map(access)
+        if struct_name(name).is_some() {
+            return self.packed(|de| de.deserialize_struct(name, fields, visitor));
         }
+        self.nested(|de| {
+            if name == DYNVEC_STR {
+                let mut access = DynvecAccess::new(de);
+                access.parse()?;
+                visitor.visit_map(access)
+            } else {
+                let mut access = TableAccess::new(de, fields.len(), fields);
+                access.parse()?;
+                visitor.visit_map(access)
+            }
+        })
     }
     fn deserialize_enum<V>(
         self,
-        _name: &str,
-        _variants: &'static [&'static str],
+        name: &'static str,
+        variants: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_enum(UnionAccess::new(self))
+        let ids = union_ids(name).map(|(_, ids)| ids);
+        self.nested(|de| visitor.visit_enum(UnionAccess::new(de, variants, ids)))
     }
 
     fn deserialize_identifier<V>(self, _visitor: V) -> Result<V::Value>
@@ -373,14 +732,18 @@ impl<'de> de::SeqAccess<'de> for ArrayAccess<'de, '_> {
         if self.current_index < self.count {
             let part = &self.de.data
                 [self.current_index * self.item_size..(self.current_index + 1) * self.item_size];
+            let segment = Segment::Index(self.current_index);
             self.current_index += 1;
-            let mut de = MoleculeDeserializer::new(part);
-            let value = seed.deserialize(&mut de)?;
+            let value = self.de.deserialize_part(part, segment, seed)?;
             Ok(Some(value))
         } else {
             Ok(None)
         }
     }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.count - self.current_index)
+    }
 }
 
 struct FixvecAccess<'de, 'a> {
@@ -398,6 +761,14 @@ impl<'de, 'a> FixvecAccess<'de, 'a> {
         }
     }
     fn parse(&mut self) -> Result<()> {
+        let count = fixvec_item_count(self.de.data)?;
+        // the items of an empty fixvec can't be sized, trailing bytes are kept
+        if count == 0 && self.de.data.len() != 4 && self.de.options.canonical {
+            return Err(Error::NonCanonical {
+                offset: self.de.offset + 4,
+            });
+        }
+        self.de.reserve_items::<&[u8]>(count)?;
         self.parts = disassemble_fixvec(self.de.data)?;
         Ok(())
     }
@@ -412,37 +783,56 @@ impl<'de> de::SeqAccess<'de> for FixvecAccess<'de, '_> {
     {
         if self.current_index < self.parts.len() {
             let part = self.parts[self.current_index];
+            let segment = Segment::Index(self.current_index);
             self.current_index += 1;
             // elements in fixvec should be fixed(struct, primitive type, etc)
-            let mut de = MoleculeStructDeserializer::new(part.to_vec());
-            let value = seed.deserialize(&mut de)?;
+            let offset = self.de.offset_of(part);
+            let mut de = MoleculeStructDeserializer::new(part, offset, self.de.options.canonical);
+            let value = seed
+                .deserialize(&mut de)
+                .and_then(|value| de.end().map(|_| value))
+                .map_err(|e| e.at(offset, segment))?;
             Ok(Some(value))
         } else {
             Ok(None)
         }
     }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.parts.len() - self.current_index)
+    }
 }
 
 struct TableAccess<'de, 'a> {
     de: &'a mut MoleculeDeserializer<'de>,
     current_index: usize,
     count: usize,
+    // field names for error path, empty for tuple struct
+    fields: &'static [&'static str],
     parts: Vec<&'de [u8]>,
 }
 
 impl<'de, 'a> TableAccess<'de, 'a> {
-    fn new(de: &'a mut MoleculeDeserializer<'de>, count: usize) -> Self {
+    fn new(
+        de: &'a mut MoleculeDeserializer<'de>,
+        count: usize,
+        fields: &'static [&'static str],
+    ) -> Self {
         TableAccess {
             de,
             current_index: 0,
             count,
+            fields,
             parts: vec![],
         }
     }
     fn parse(&mut self) -> Result<()> {
+        self.de
+            .reserve_items::<&[u8]>(table_item_count(self.de.data)?)?;
         self.parts = disassemble_table(self.de.data)?;
-        // always enable compatible for molecule table
-        if self.parts.len() < self.count {
+        // extra fields are allowed in compatible mode
+        let exact = !self.de.options.compatible || self.de.options.canonical;
+        if self.parts.len() < self.count || (exact && self.parts.len() != self.count) {
             return Err(Error::MismatchedTableFieldCount);
         }
         Ok(())
@@ -470,9 +860,12 @@ impl<'de> de::MapAccess<'de> for TableAccess<'de, '_> {
     {
         assert!(self.current_index < self.parts.len());
         let part = self.parts[self.current_index];
+        let segment = match self.fields.get(self.current_index) {
+            Some(name) => Segment::Field(name),
+            None => Segment::Index(self.current_index),
+        };
         self.current_index += 1;
-        let mut de = MoleculeDeserializer::new(part);
-        seed.deserialize(&mut de)
+        self.de.deserialize_part(part, segment, seed)
     }
 }
 
@@ -503,16 +896,38 @@ impl<'de, 'a> MappingAccess<'de, 'a> {
         }
     }
     fn parse(&mut self) -> Result<()> {
+        let count = table_item_count(self.de.data)?;
+        self.de.reserve_items::<&[u8]>(count)?;
+        self.de.reserve_items::<(&[u8], &[u8])>(count)?;
         let all = disassemble_table(self.de.data)?;
+        self.parts.reserve_exact(all.len());
         for item in all.into_iter() {
+            // the key-value table always has 2 items
+            self.de.charge(2 * core::mem::size_of::<&[u8]>())?;
             let kv = disassemble_table(item)?;
             if kv.len() != 2 {
                 return Err(Error::InvalidMap);
             }
             self.parts.push((kv[0], kv[1]));
         }
+        if self.de.options.canonical {
+            self.check_duplicated_keys()?;
+        }
         Ok(())
     }
+    // Keys are compared by their encoding: equal keys have the same bytes in
+    // canonical encoding.
+    fn check_duplicated_keys(&mut self) -> Result<()> {
+        self.de.reserve_items::<&[u8]>(self.parts.len())?;
+        let mut keys: Vec<&[u8]> = self.parts.iter().map(|(k, _)| *k).collect();
+        keys.sort_unstable();
+        match keys.windows(2).find(|w| w[0] == w[1]) {
+            Some(w) => Err(Error::NonCanonical {
+                offset: self.de.offset_of(w[1]),
+            }),
+            None => Ok(()),
+        }
+    }
 }
 
 impl<'de> de::MapAccess<'de> for MappingAccess<'de, '_> {
@@ -524,8 +939,8 @@ impl<'de> de::MapAccess<'de> for MappingAccess<'de, '_> {
     {
         if self.current_index < self.parts.len() {
             let key_slice = self.parts[self.current_index].0;
-            let mut de = MoleculeDeserializer::new(key_slice);
-            Ok(Some(seed.deserialize(&mut de)?))
+            let segment = Segment::Index(self.current_index);
+            Ok(Some(self.de.deserialize_part(key_slice, segment, seed)?))
         } else {
             Ok(None)
         }
@@ -537,9 +952,13 @@ impl<'de> de::MapAccess<'de> for MappingAccess<'de, '_> {
     {
         assert!(self.current_index < self.parts.len());
         let value_slice = self.parts[self.current_index].1;
+        let segment = Segment::Index(self.current_index);
         self.current_index += 1;
-        let mut de = MoleculeDeserializer::new(value_slice);
-        seed.deserialize(&mut de)
+        self.de.deserialize_part(value_slice, segment, seed)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.parts.len() - self.current_index)
     }
 }
 
@@ -558,6 +977,8 @@ impl<'de, 'a> DynvecAccess<'de, 'a> {
         }
     }
     fn parse(&mut self) -> Result<()> {
+        self.de
+            .reserve_items::<&[u8]>(table_item_count(self.de.data)?)?;
         self.parts = disassemble_table(self.de.data)?;
         Ok(())
     }
@@ -584,19 +1005,68 @@ impl<'de> de::MapAccess<'de> for DynvecAccess<'de, '_> {
     {
         assert!(self.current_index < self.parts.len());
         let part = self.parts[self.current_index];
+        let segment = Segment::Index(self.current_index);
         self.current_index += 1;
-        let mut de = MoleculeDeserializer::new(part);
-        seed.deserialize(&mut de)
+        self.de.deserialize_part(part, segment, seed)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.parts.len() - self.current_index)
     }
 }
 
 struct UnionAccess<'de, 'a> {
     de: &'a mut MoleculeDeserializer<'de>,
+    // variant names for error path
+    variants: &'static [&'static str],
+    // `None` if the union ids are the variant indexes
+    ids: Option<UnionIds<'static>>,
+    id: usize,
+    index: usize,
+    // the position of the variant body in the whole input
+    offset: usize,
 }
 
 impl<'de, 'a> UnionAccess<'de, 'a> {
-    fn new(de: &'a mut MoleculeDeserializer<'de>) -> Self {
-        UnionAccess { de }
+    fn new(
+        de: &'a mut MoleculeDeserializer<'de>,
+        variants: &'static [&'static str],
+        ids: Option<UnionIds<'static>>,
+    ) -> Self {
+        UnionAccess {
+            de,
+            variants,
+            ids,
+            id: 0,
+            index: 0,
+            offset: 0,
+        }
+    }
+
+    fn is_fallback(&self) -> bool {
+        self.ids.and_then(|ids| ids.fallback()) == Some(self.index)
+    }
+
+    // The fallback variant takes the union id and the undecoded body.
+    fn fallback<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let body = self.de.data;
+        self.de.charge(body.len())?;
+        visitor.visit_seq(FallbackAccess {
+            id: self.id as u32,
+            body,
+            index: 0,
+        })
+    }
+    // Locate the errors from the body of variant.
+    fn locate<T>(&self, result: Result<T>) -> Result<T> {
+        let segment = match self.variants.get(self.index) {
+            Some(name) => Segment::Field(name),
+            None => Segment::Index(self.index),
+        };
+        result.map_err(|e| e.at(self.offset, segment))
     }
 }
 
@@ -604,13 +1074,20 @@ impl<'de> de::EnumAccess<'de> for UnionAccess<'de, '_> {
     type Error = Error;
     type Variant = Self;
 
-    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self)>
+    fn variant_seed<V>(mut self, seed: V) -> Result<(V::Value, Self)>
     where
         V: de::DeserializeSeed<'de>,
     {
         let id = unpack_number(self.de.data, 0)?;
+        self.id = id;
+        self.index = match self.ids {
+            Some(ids) => ids.position(id).ok_or(Error::UnknownUnionId { id })?,
+            None => id,
+        };
         self.de.data = &self.de.data[4..];
-        let de = U64Deserializer::<Error>::new(id as u64);
+        self.de.offset += 4;
+        self.offset = self.de.offset;
+        let de = U64Deserializer::<Error>::new(self.index as u64);
         Ok((seed.deserialize(de)?, self))
     }
 }
@@ -619,27 +1096,96 @@ impl<'de> de::VariantAccess<'de> for UnionAccess<'de, '_> {
     type Error = Error;
 
     fn unit_variant(self) -> Result<()> {
-        de::Deserialize::deserialize(self.de)
+        let result = de::Deserialize::deserialize(&mut *self.de);
+        self.locate(result)
     }
 
     fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
     where
         T: de::DeserializeSeed<'de>,
     {
-        seed.deserialize(self.de)
+        let result = seed.deserialize(&mut *self.de);
+        self.locate(result)
     }
 
     fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        de::Deserializer::deserialize_tuple_struct(self.de, "", len, visitor)
+        if self.is_fallback() {
+            return self.fallback(visitor);
+        }
+        let result = de::Deserializer::deserialize_tuple_struct(&mut *self.de, "", len, visitor);
+        self.locate(result)
     }
 
     fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        de::Deserializer::deserialize_struct(self.de, "", fields, visitor)
+        if self.is_fallback() {
+            return self.fallback(visitor);
+        }
+        let result = de::Deserializer::deserialize_struct(&mut *self.de, "", fields, visitor);
+        self.locate(result)
+    }
+}
+
+/// The fields of the fallback variant of `#[molecule(union)]`: the union id
+/// and the undecoded body.
+struct FallbackAccess<'de> {
+    id: u32,
+    body: &'de [u8],
+    index: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for FallbackAccess<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        self.index += 1;
+        match self.index {
+            1 => seed.deserialize(U32Deserializer::new(self.id)).map(Some),
+            2 => seed.deserialize(BodyDeserializer(self.body)).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(2usize.saturating_sub(self.index))
+    }
+}
+
+/// The body of the fallback variant, as bytes or a sequence of `u8`, e.g. for
+/// `Vec<u8>`.
+struct BodyDeserializer<'de>(&'de [u8]);
+
+impl<'de> de::Deserializer<'de> for BodyDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_borrowed_bytes(self.0)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let mut seq = SeqDeserializer::new(self.0.iter().copied());
+        let value = visitor.visit_seq(&mut seq)?;
+        seq.end()?;
+        Ok(value)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct tuple
+        tuple_struct map struct enum identifier ignored_any
     }
 }
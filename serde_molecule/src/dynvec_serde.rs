@@ -3,14 +3,14 @@ use alloc::vec::Vec;
 use core::fmt;
 use core::marker::PhantomData;
 
-use crate::to_vec;
 use crate::{
     de::DYNVEC_STR,
-    molecule::{assemble_table, MOLECULE_DE, MOLECULE_SER},
+    molecule::{MOLECULE_DE, MOLECULE_SER},
 };
 use serde::{
     de::{MapAccess, Visitor},
-    ser, Deserialize, Deserializer, Serialize, Serializer,
+    ser::SerializeTupleStruct,
+    Deserialize, Deserializer, Serialize, Serializer,
 };
 
 pub fn serialize<T, S, V>(value: V, serializer: S) -> Result<S::Ok, S::Error>
@@ -20,22 +20,34 @@ where
     V: IntoIterator<Item = T> + Serialize,
 {
     if core::any::type_name::<S>().contains(MOLECULE_SER) {
-        let parts: Result<Vec<_>, _> = value
-            .into_iter()
-            .map(|v| {
-                to_vec(&v, false)
-                    .map_err(|_| ser::Error::custom("failed to serialize element in vector"))
-            })
-            .collect();
-
-        let parts = parts?; // Propagate error if any element fails to serialize.
-        let data = assemble_table(&parts);
-        serializer.serialize_bytes(&data)
+        let iter = value.into_iter();
+        match iter.size_hint() {
+            (lower, Some(upper)) if lower == upper => serialize_elements(serializer, lower, iter),
+            _ => {
+                // The header needs the element count up front.
+                let elements: Vec<T> = iter.collect();
+                serialize_elements(serializer, elements.len(), elements.iter())
+            }
+        }
     } else {
         value.serialize(serializer) // Use default serialization for others, e.g. serde_json
     }
 }
 
+// A dynvec has the same layout as a table: the elements are its fields.
+fn serialize_elements<S, E, I>(serializer: S, len: usize, elements: I) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    E: Serialize,
+    I: Iterator<Item = E>,
+{
+    let mut table = serializer.serialize_tuple_struct(DYNVEC_STR, len)?;
+    for element in elements {
+        table.serialize_field(&element)?;
+    }
+    table.end()
+}
+
 pub fn deserialize<'de, D, T, V>(deserializer: D) -> Result<V, D::Error>
 where
     D: Deserializer<'de>,
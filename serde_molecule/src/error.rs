@@ -1,3 +1,5 @@
+use alloc::boxed::Box;
+use alloc::format;
 use alloc::string::String;
 use alloc::string::ToString;
 use core::fmt::{self, Debug, Display};
@@ -9,10 +11,10 @@ pub enum Error {
     Message(String),
 
     /// Occurs when the data length is incorrect while parsing a number or molecule header.
-    MismatchedLength,
+    MismatchedLength { expected: usize, actual: usize },
 
     /// Occurs when the data length is insufficient while parsing a number or molecule header.
-    LengthNotEnough,
+    LengthNotEnough { expected: usize, actual: usize },
 
     /// Indicates that the method or type is not implemented. Not all types in Rust can be serialized.
     Unimplemented,
@@ -41,8 +43,9 @@ pub enum Error {
     /// Indicates that the table header is invalid or malformed.
     InvalidTable,
 
-    /// Indicates that the table length is invalid or malformed.
-    InvalidTableLength,
+    /// Indicates that the full size in the table header(`expected`) differs
+    /// from the data length(`actual`).
+    InvalidTableLength { expected: usize, actual: usize },
 
     /// Indicates that the table header is invalid or malformed.
     InvalidTableHeader,
@@ -53,12 +56,133 @@ pub enum Error {
     /// Indicates that non-fixed size fields are not allowed in a molecule struct, e.g., `Option`, `Vec`, `DynVec`, `enum`.
     MixTableAndStruct,
 
+    /// Occurs when the union id isn't one of the ids given by `#[molecule(union)]`.
+    UnknownUnionId { id: usize },
+
     /// Invalid char
     InvalidChar,
+
+    /// Occurs when a string is not valid UTF-8. `offset` is the position of the
+    /// first invalid byte in the input.
+    InvalidUtf8 { offset: usize },
+
+    /// Occurs when the input isn't the canonical encoding produced by `to_vec`,
+    /// in canonical mode. `offset` is the position of the first offending byte.
+    NonCanonical { offset: usize },
+
+    /// Occurs when the input is nested deeper than `max_depth` of `DeserializerOptions`.
+    DepthLimitExceeded,
+
+    /// Occurs when a fixvec, dynvec, table or map has more items than
+    /// `max_items` of `DeserializerOptions`.
+    ItemLimitExceeded,
+
+    /// Occurs when decoding allocates more bytes than `max_alloc` of `DeserializerOptions`.
+    AllocLimitExceeded,
+
+    /// Wraps an error that occurred below the top level while deserializing.
+    /// `offset` is the position in the input of the innermost table, vector,
+    /// union or struct field being decoded, and `path` locates it from the top
+    /// level, e.g. `raw.outputs[3].type_.args`. Use [`Error::inner`] to get the
+    /// wrapped error.
+    Context {
+        offset: usize,
+        path: String,
+        source: Box<Error>,
+    },
+
+    /// Occurs when the buffer of `to_slice` is too small for the encoding.
+    BufferTooSmall,
+
+    /// Occurs when a molecule schema fails to parse or resolve. `line` is the
+    /// line of the offending token or declaration, starting from 1.
+    InvalidSchema { line: usize, message: String },
+
+    /// Occurs when a view of table, dynvec or fixvec is accessed at `index`,
+    /// beyond its item count `len`.
+    IndexOutOfBounds { index: usize, len: usize },
+
+    /// Occurs when the sink of `to_writer` fails to accept the bytes.
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
 }
 
 pub type Result<T> = result::Result<T, Error>;
 
+/// A step of the path in [`Error::Context`].
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Segment {
+    /// A named field of table, struct or union.
+    Field(&'static str),
+    /// An element of vector, map, tuple or array, or a field of tuple struct.
+    Index(usize),
+}
+
+impl Error {
+    /// The error without context.
+    pub fn inner(&self) -> &Error {
+        match self {
+            Self::Context { source, .. } => source.inner(),
+            _ => self,
+        }
+    }
+
+    /// The position in the input where the error occurred, if known.
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            Self::Context { offset, .. } => Some(*offset),
+            _ => None,
+        }
+    }
+
+    /// The path from the top level where the error occurred, if known.
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            Self::Context { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    // Called while unwinding from the innermost part: the first call records
+    // the offset, later calls only prepend `segment` to the path.
+    pub(crate) fn at(self, offset: usize, segment: Segment) -> Error {
+        match segment {
+            Segment::Field(name) => self.at_field(offset, name),
+            Segment::Index(index) => self.at_path(offset, format!("[{}]", index)),
+        }
+    }
+
+    // Same as `at` with a field name not known at compile time.
+    pub(crate) fn at_field(self, offset: usize, name: &str) -> Error {
+        self.at_path(offset, name.to_string())
+    }
+
+    fn at_path(self, offset: usize, mut prefix: String) -> Error {
+        match self {
+            Self::Context {
+                offset,
+                path,
+                source,
+            } => {
+                if !path.starts_with('[') {
+                    prefix.push('.');
+                }
+                prefix.push_str(&path);
+                Self::Context {
+                    offset,
+                    path: prefix,
+                    source,
+                }
+            }
+            error => Self::Context {
+                offset,
+                path: prefix,
+                source: Box::new(error),
+            },
+        }
+    }
+}
+
 impl ser::Error for Error {
     fn custom<T: Display>(msg: T) -> Error {
         Error::Message(msg.to_string())
@@ -73,7 +197,12 @@ impl de::Error for Error {
 
 impl de::StdError for Error {
     fn source(&self) -> Option<&(dyn de::StdError + 'static)> {
-        None
+        match self {
+            #[cfg(feature = "std")]
+            Self::Io(e) => Some(e),
+            Self::Context { source, .. } => Some(source),
+            _ => None,
+        }
     }
 }
 
@@ -81,6 +210,12 @@ impl Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Self::Message(m) => f.write_str(m),
+            Self::Context {
+                offset,
+                path,
+                source,
+            } => write!(f, "{} at offset {}, path `{}`", source, offset, path),
+            Self::InvalidSchema { line, message } => write!(f, "{} at line {}", message, line),
             _ => {
                 write!(f, "{:?}", self)
             }
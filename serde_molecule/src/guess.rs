@@ -0,0 +1,337 @@
+//! Guess the structure of molecule data without a schema.
+//!
+//! Tables and dynvecs start with their full size and the offsets of their
+//! items, fixvecs with their item count. Random bytes rarely pass these header
+//! checks, so when they pass, the bytes are likely molecule data. [`guess`]
+//! tries every part of the data as a table, a fixvec and raw bytes,
+//! recursively, and ranks the structure trees by the evidence of their
+//! headers. The trees are made of [`inspect`](crate::inspect) nodes of type
+//! `unknown`, the items are labeled `[i]`.
+//!
+//! Some formats can't be told apart without a schema:
+//! * A table and a dynvec share the format, it's guessed as a dynvec if it has
+//!   two or more items of similar structure.
+//! * Structs, arrays and numbers are raw bytes.
+//! * An option is guessed as its value, or empty raw bytes if it's none.
+//! * A union is guessed as a fixvec or raw bytes, with the id read as the item
+//!   count or the first bytes.
+//!
+//! The content of a byte fixvec, e.g. the `lock` of `WitnessArgs`, is also
+//! guessed, since it often holds molecule data. Parts nested deeper than 64
+//! levels are raw bytes.
+//!
+//! ```
+//! use serde_molecule::guess::guess;
+//! use serde_molecule::inspect::Kind;
+//! use serde_molecule::to_vec;
+//!
+//! #[derive(serde::Serialize)]
+//! struct WitnessArgs {
+//!     lock: Option<Vec<u8>>,
+//!     input_type: Option<Vec<u8>>,
+//!     output_type: Option<Vec<u8>>,
+//! }
+//!
+//! let witness = WitnessArgs {
+//!     lock: Some(vec![1, 2, 3]),
+//!     input_type: None,
+//!     output_type: None,
+//! };
+//! let guesses = guess(&to_vec(&witness, false).unwrap(), 3);
+//! assert_eq!(
+//!     guesses[0].node.to_string(),
+//!     "\
+//! 0           23  unknown table, header [23, 16, 23, 23]
+//! 16           7    [0]: unknown fixvec, count 3 = 0x010203
+//! 23           0    [1]: unknown raw = 0x
+//! 23           0    [2]: unknown raw = 0x
+//! "
+//! );
+//! // the next guesses take `lock` and then the whole data as raw bytes
+//! assert_eq!(guesses[1].node.children[0].kind, Kind::Raw);
+//! assert_eq!(guesses[2].node.kind, Kind::Raw);
+//! assert_eq!(guesses[0].score, 18);
+//! assert!(guesses[0].confidence > 0.9);
+//! ```
+use crate::inspect::{hex, range_of, Kind, Node};
+use crate::molecule::{disassemble_table, unpack_number};
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+// The evidence in bits of the header numbers: how unlikely they are
+// consistent by chance. The full size of a table must be the length, the
+// offsets must be ascending, and the item count of a fixvec must divide the
+// length.
+const FULL_SIZE_BITS: u32 = 8;
+const OFFSET_BITS: u32 = 2;
+const COUNT_BITS: u32 = 4;
+
+// The data is guessed as raw bytes below this depth, the nesting is
+// controlled by the data.
+const MAX_DEPTH: usize = 64;
+
+/// A guessed structure of the data.
+#[derive(Clone, Debug)]
+pub struct Guess {
+    pub node: Node,
+    /// The evidence of the headers in bits, summed over all tables, dynvecs
+    /// and fixvecs in the tree. Raw bytes have no evidence.
+    pub score: u32,
+    /// The probability of this guess among the returned ones, from 0 to 1.
+    pub confidence: f64,
+}
+
+/// Guess the structure of `data`, see the [module documentation](self).
+///
+/// Returns at most `limit` guesses, the most plausible first. The last
+/// resort, the whole data as raw bytes, is always a candidate.
+pub fn guess(data: &[u8], limit: usize) -> Vec<Guess> {
+    let mut guesser = Guesser {
+        data,
+        limit: limit.max(1),
+        memo: BTreeMap::new(),
+    };
+    let range = 0..data.len();
+    let scores = guesser.guess(range.clone(), 0);
+    let best = scores[0];
+    let total: f64 = scores.iter().map(|score| likelihood(best - score)).sum();
+    scores
+        .into_iter()
+        .enumerate()
+        .map(|(index, score)| Guess {
+            node: guesser.build(range.clone(), 0, index),
+            score,
+            confidence: likelihood(best - score) / total,
+        })
+        .collect()
+}
+
+// 2 to the power of `-bits`.
+fn likelihood(bits: u32) -> f64 {
+    1.0 / (1u64 << bits.min(63)) as f64
+}
+
+// A candidate structure of a range. The nodes are only built for the
+// returned guesses.
+#[derive(Clone, Debug)]
+enum Shape {
+    Raw,
+    // the chosen candidate of every item
+    Table(Vec<usize>),
+    // items as raw bytes, or the bytes as a value
+    Fixvec,
+    // bytes with the chosen candidate of the content
+    Bytes(usize),
+}
+
+struct Guesser<'a> {
+    data: &'a [u8],
+    limit: usize,
+    // The candidates of the ranges already guessed, by range and depth.
+    memo: BTreeMap<(usize, usize, usize), Vec<(u32, Shape)>>,
+}
+
+impl Guesser<'_> {
+    // The scores of the best candidates of `range`, best first. On ties,
+    // tables go before fixvecs and raw bytes.
+    fn guess(&mut self, range: Range<usize>, depth: usize) -> Vec<u32> {
+        let key = (range.start, range.end, depth);
+        if let Some(candidates) = self.memo.get(&key) {
+            return candidates.iter().map(|(score, _)| *score).collect();
+        }
+        let mut candidates = vec![];
+        if depth < MAX_DEPTH {
+            self.table(range.clone(), depth, &mut candidates);
+            self.fixvec(range, depth, &mut candidates);
+        }
+        candidates.push((0, Shape::Raw));
+        candidates.sort_by(|a, b| b.0.cmp(&a.0));
+        candidates.truncate(self.limit);
+        let scores = candidates.iter().map(|(score, _)| *score).collect();
+        self.memo.insert(key, candidates);
+        scores
+    }
+
+    fn table(&mut self, range: Range<usize>, depth: usize, candidates: &mut Vec<(u32, Shape)>) {
+        let items = match self.table_items(range) {
+            Some(items) => items,
+            None => return,
+        };
+        let score = FULL_SIZE_BITS + OFFSET_BITS * items.len() as u32;
+        for (items_score, choice) in self.combine(&items, depth + 1) {
+            candidates.push((score + items_score, Shape::Table(choice)));
+        }
+    }
+
+    fn fixvec(&mut self, range: Range<usize>, depth: usize, candidates: &mut Vec<(u32, Shape)>) {
+        let count = match fixvec_count(&self.data[range.clone()]) {
+            Some(count) => count,
+            None => return,
+        };
+        let body = range.start + 4..range.end;
+        if count == body.len() {
+            // bytes, which may hold molecule data
+            for (index, score) in self.guess(body, depth + 1).into_iter().enumerate() {
+                if score > 0 {
+                    candidates.push((COUNT_BITS + score, Shape::Bytes(index)));
+                }
+            }
+        }
+        candidates.push((COUNT_BITS, Shape::Fixvec));
+    }
+
+    // The best combinations of the candidates of `items`.
+    fn combine(&mut self, items: &[Range<usize>], depth: usize) -> Vec<(u32, Vec<usize>)> {
+        // The best combinations of the first items: the score, the index of
+        // the extended combination in the previous layer, and the candidate.
+        let mut layers: Vec<Vec<(u32, usize, usize)>> = Vec::with_capacity(items.len());
+        let mut scores = vec![0];
+        for item in items {
+            let item_scores = self.guess(item.clone(), depth);
+            let mut next: Vec<_> = scores
+                .iter()
+                .enumerate()
+                .flat_map(|(parent, score)| {
+                    item_scores
+                        .iter()
+                        .enumerate()
+                        .map(move |(j, item_score)| (score + item_score, parent, j))
+                })
+                .collect();
+            next.sort_by(|a, b| b.0.cmp(&a.0));
+            next.truncate(self.limit);
+            scores = next.iter().map(|(score, _, _)| *score).collect();
+            layers.push(next);
+        }
+        // follow the combinations back to the first item
+        (0..scores.len())
+            .map(|index| {
+                let mut choice = vec![0; layers.len()];
+                let mut index_in_layer = index;
+                for (k, layer) in layers.iter().enumerate().rev() {
+                    let (_, parent, j) = layer[index_in_layer];
+                    choice[k] = j;
+                    index_in_layer = parent;
+                }
+                (scores[index], choice)
+            })
+            .collect()
+    }
+
+    fn table_items(&self, range: Range<usize>) -> Option<Vec<Range<usize>>> {
+        let items = disassemble_table(&self.data[range]).ok()?;
+        Some(
+            items
+                .into_iter()
+                .map(|item| range_of(self.data, item))
+                .collect(),
+        )
+    }
+
+    // The node of the `index`-th candidate of `range`, which is guessed.
+    fn build(&self, range: Range<usize>, depth: usize, index: usize) -> Node {
+        let part = &self.data[range.clone()];
+        let shape = &self.memo[&(range.start, range.end, depth)][index].1;
+        match shape {
+            Shape::Raw => {
+                let mut node = Node::new("unknown", "unknown", Kind::Raw, range);
+                node.value = Some(hex(part));
+                node
+            }
+            Shape::Table(choice) => {
+                let items = self.table_items(range.clone()).unwrap_or_default();
+                let children: Vec<_> = items
+                    .iter()
+                    .zip(choice)
+                    .enumerate()
+                    .map(|(i, (item, &j))| {
+                        let mut node = self.build(item.clone(), depth + 1, j);
+                        node.label = format!("[{}]", i);
+                        node
+                    })
+                    .collect();
+                let dynvec = children.len() >= 2
+                    && children.iter().all(|child| similar(&children[0], child));
+                let kind = if dynvec { Kind::Dynvec } else { Kind::Table };
+                let mut node = Node::new("unknown", "unknown", kind, range.clone());
+                node.header = vec![range.len()];
+                node.header
+                    .extend(items.iter().map(|item| item.start - range.start));
+                node.children = children;
+                node
+            }
+            Shape::Fixvec | Shape::Bytes(_) => {
+                let count = fixvec_count(part).unwrap_or_default();
+                let body = range.start + 4..range.end;
+                let mut node = Node::new("unknown", "unknown", Kind::Fixvec, range.clone());
+                node.header = vec![count];
+                match shape {
+                    Shape::Bytes(j) => node.children = vec![self.build(body, depth + 1, *j)],
+                    _ if count == body.len() => node.value = Some(hex(&self.data[body])),
+                    _ => {
+                        let size = body.len() / count;
+                        node.children = (0..count)
+                            .map(|i| {
+                                let start = body.start + i * size;
+                                let item = start..start + size;
+                                let mut child = Node::new(
+                                    format!("[{}]", i),
+                                    "unknown",
+                                    Kind::Raw,
+                                    item.clone(),
+                                );
+                                child.value = Some(hex(&self.data[item]));
+                                child
+                            })
+                            .collect();
+                    }
+                }
+                node
+            }
+        }
+    }
+}
+
+// The item count of fixvec `part`, which must divide the length of the items.
+// An empty fixvec has nothing after the count.
+fn fixvec_count(part: &[u8]) -> Option<usize> {
+    let count = unpack_number(part, 0).ok()?;
+    let size = part.len() - 4;
+    match count {
+        0 if size == 0 => Some(0),
+        0 => None,
+        _ if size > 0 && size % count == 0 => Some(count),
+        _ => None,
+    }
+}
+
+// Whether `a` and `b` can be items of the same dynvec.
+fn similar(a: &Node, b: &Node) -> bool {
+    match (a.kind, b.kind) {
+        (Kind::Raw, Kind::Raw) => a.range.len() == b.range.len(),
+        (Kind::Fixvec, Kind::Fixvec) => match (item_size(a), item_size(b)) {
+            (Some(a), Some(b)) => a == b,
+            _ => true,
+        },
+        (Kind::Table | Kind::Dynvec, Kind::Table | Kind::Dynvec) => {
+            a.children.len() == b.children.len()
+                && a.children
+                    .iter()
+                    .zip(&b.children)
+                    .all(|(a, b)| similar(a, b))
+        }
+        _ => false,
+    }
+}
+
+// The item size of fixvec `node`, unknown if it's empty.
+fn item_size(node: &Node) -> Option<usize> {
+    match node.header[..] {
+        [0] => None,
+        [count] => Some((node.range.len() - 4) / count),
+        _ => None,
+    }
+}
@@ -0,0 +1,867 @@
+//! Inspect encoded molecule data: an annotated layout tree and a hexdump.
+//!
+//! When decoding fails, the error tells where, but not what the bytes around
+//! it look like. [`inspect`] and [`inspect_with_schema`] split the data into a
+//! tree of [`Node`]s following a Rust type or a schema, with the byte range,
+//! the header of tables and vectors, and the decoded value of primitives.
+//! Malformed parts don't stop the inspection: the error is kept in the node,
+//! see [`Node::first_error`].
+//!
+//! ```
+//! use serde_molecule::inspect::inspect;
+//! use serde_molecule::to_vec;
+//!
+//! #[derive(serde::Serialize, serde::Deserialize)]
+//! struct Script {
+//!     hash_type: u8,
+//!     args: Vec<u8>,
+//! }
+//!
+//! let mut bytes = to_vec(&Script { hash_type: 1, args: vec![2, 3] }, false).unwrap();
+//! let node = inspect::<Script>(&bytes, false).unwrap();
+//! assert_eq!(
+//!     node.to_string(),
+//!     "\
+//! 0           19  Script table, header [19, 12, 13]
+//! 12           1    hash_type: u8 = 1
+//! 13           6    args: Vec<u8> fixvec, count 2 = 0x0203
+//! "
+//! );
+//!
+//! bytes[13] = 3;
+//! let node = inspect::<Script>(&bytes, false).unwrap();
+//! assert_eq!(node.first_error().unwrap().label, "args");
+//! assert_eq!(
+//!     node.hexdump(&bytes),
+//!     "\
+//! 00000000  13 00 00 00 0c 00 00 00 0d 00 00 00              Script table, header [19, 12, 13]
+//! 0000000c  01                                                 hash_type: u8 = 1
+//! 0000000d  03 00 00 00 02 03                                  args: Vec<u8>, error: InvalidFixvec
+//! "
+//! );
+//! ```
+use crate::error::{Error, Result};
+use crate::molecule::{disassemble_fixvec, disassemble_table, unpack_number};
+use crate::schema::{DeclKind, Schema};
+use crate::trace::{ContainerFormat, Format, Layout, Registry, VariantFormat};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::{self, Display, Write as _};
+use core::ops::Range;
+use serde::de::{self, Error as _};
+
+/// The molecule kind of a [`Node`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Table,
+    Dynvec,
+    Fixvec,
+    Struct,
+    Array,
+    Option,
+    Union,
+    /// Numbers, `byte`, `bool`, `char` and the unit type.
+    Primitive,
+    /// Undecoded bytes: `CollectData`, the body of a fallback union variant,
+    /// or the extra fields of a table.
+    Raw,
+}
+
+/// A part of the inspected data.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Node {
+    /// The field name, `[i]` for items, or the type name for the top level,
+    /// the value of options and the body of unions.
+    pub label: String,
+    /// The type name in the schema, or the Rust type, e.g. `Vec<u32>`.
+    pub ty: String,
+    pub kind: Kind,
+    /// The byte range in the inspected data.
+    pub range: Range<usize>,
+    /// The header numbers: the full size and the offsets of tables and
+    /// dynvecs, the item count of fixvecs, or the id of unions.
+    pub header: Vec<usize>,
+    /// The decoded value of primitives, strings and bytes, e.g. `42`,
+    /// `"abc"` or `0x0102`. Arrays and vectors of bytes are shown as one value
+    /// without children.
+    pub value: Option<String>,
+    /// Why this part is malformed. The children aren't inspected then.
+    pub error: Option<String>,
+    pub children: Vec<Node>,
+}
+
+impl Node {
+    pub(crate) fn new(
+        label: impl ToString,
+        ty: impl ToString,
+        kind: Kind,
+        range: Range<usize>,
+    ) -> Node {
+        Node {
+            label: label.to_string(),
+            ty: ty.to_string(),
+            kind,
+            range,
+            header: vec![],
+            value: None,
+            error: None,
+            children: vec![],
+        }
+    }
+
+    fn failed(mut self, error: Error) -> Node {
+        self.error = Some(error.to_string());
+        self
+    }
+
+    /// The first node with error, depth first.
+    pub fn first_error(&self) -> Option<&Node> {
+        if self.error.is_some() {
+            return Some(self);
+        }
+        self.children.iter().find_map(Node::first_error)
+    }
+
+    /// Render `data`, the inspected bytes, as a hexdump with 16 bytes per
+    /// line, annotated with the nodes. The header of a node is shown on the
+    /// lines of its own, followed by its children.
+    pub fn hexdump(&self, data: &[u8]) -> String {
+        let mut out = String::new();
+        self.dump(data, 0, &mut out);
+        out
+    }
+
+    fn dump(&self, data: &[u8], depth: usize, out: &mut String) {
+        let annotation = format!("{}{}", "  ".repeat(depth), self.describe());
+        let header_end = match self.children.first() {
+            Some(child) => child.range.start,
+            None => self.range.end,
+        };
+        dump_lines(data, self.range.start..header_end, &annotation, out);
+        let mut pos = header_end;
+        for child in &self.children {
+            if child.range.start > pos {
+                dump_lines(data, pos..child.range.start, "", out);
+            }
+            child.dump(data, depth + 1, out);
+            pos = pos.max(child.range.end);
+        }
+        if self.range.end > pos {
+            dump_lines(data, pos..self.range.end, "", out);
+        }
+    }
+
+    // e.g. `args: Bytes fixvec, count 2 = 0x0102`
+    fn describe(&self) -> String {
+        let mut text = if self.label == self.ty {
+            self.ty.clone()
+        } else {
+            format!("{}: {}", self.label, self.ty)
+        };
+        let kind = match self.kind {
+            Kind::Table => "table",
+            Kind::Dynvec => "dynvec",
+            Kind::Fixvec => "fixvec",
+            Kind::Struct => "struct",
+            Kind::Array => "array",
+            Kind::Option => "option",
+            Kind::Union => "union",
+            Kind::Primitive => "",
+            Kind::Raw => "raw",
+        };
+        if self.error.is_none() {
+            if !kind.is_empty() {
+                text.push(' ');
+                text.push_str(kind);
+            }
+            match (self.kind, self.header.as_slice()) {
+                (Kind::Table | Kind::Dynvec, header) => {
+                    let header: Vec<_> = header.iter().map(|n| n.to_string()).collect();
+                    let _ = write!(text, ", header [{}]", header.join(", "));
+                }
+                (Kind::Fixvec, [count]) => {
+                    let _ = write!(text, ", count {}", count);
+                }
+                (Kind::Union, [id]) => {
+                    let _ = write!(text, ", id {}", id);
+                }
+                (Kind::Option, _) if self.children.is_empty() => text.push_str(", none"),
+                _ => {}
+            }
+        }
+        if let Some(value) = &self.value {
+            let _ = write!(text, " = {}", value);
+        }
+        if let Some(error) = &self.error {
+            let _ = write!(text, ", error: {}", error);
+        }
+        text
+    }
+
+    fn fmt_tree(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<8} {:>5}  {}{}",
+            self.range.start,
+            self.range.len(),
+            "  ".repeat(depth),
+            self.describe()
+        )?;
+        for child in &self.children {
+            child.fmt_tree(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// The layout tree, one node per line with the offset and size.
+impl Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_tree(f, 0)
+    }
+}
+
+fn dump_lines(data: &[u8], range: Range<usize>, annotation: &str, out: &mut String) {
+    if range.is_empty() {
+        if !annotation.is_empty() {
+            let _ = writeln!(out, "{:08x}  {:47}  {}", range.start, "", annotation);
+        }
+        return;
+    }
+    let mut annotation = annotation;
+    for start in range.clone().step_by(16) {
+        let end = (start + 16).min(range.end);
+        let hex: Vec<_> = data[start..end]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        let line = format!("{:08x}  {:47}  {}", start, hex.join(" "), annotation);
+        out.push_str(line.trim_end());
+        out.push('\n');
+        annotation = "";
+    }
+}
+
+// The range of `part`, a sub-slice of `data`.
+pub(crate) fn range_of(data: &[u8], part: &[u8]) -> Range<usize> {
+    let start = part.as_ptr() as usize - data.as_ptr() as usize;
+    start..start + part.len()
+}
+
+// The ranges of the items of table or dynvec `node`, with the header of
+// `node` filled. There must be at least `count` items.
+fn table_items(data: &[u8], node: &mut Node, count: usize) -> Result<Vec<Range<usize>>> {
+    let part = &data[node.range.clone()];
+    let items = disassemble_table(part)?;
+    node.header = (0..=items.len())
+        .map(|i| unpack_number(part, i * 4))
+        .collect::<Result<_>>()?;
+    if items.len() < count {
+        return Err(Error::MismatchedTableFieldCount);
+    }
+    Ok(items.into_iter().map(|item| range_of(data, item)).collect())
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    let mut text = String::from("0x");
+    for b in bytes {
+        let _ = write!(text, "{:02x}", b);
+    }
+    text
+}
+
+/// Inspect `data` as type `T`, see the [module documentation](self).
+///
+/// The layout of `T` is traced on every call, see [`Layout::of`].
+pub fn inspect<'de, T>(data: &[u8], is_struct: bool) -> Result<Node>
+where
+    T: de::Deserialize<'de>,
+{
+    Ok(Layout::of::<T>()?.inspect(data, is_struct))
+}
+
+/// Inspect `data` as type `name` of `schema`, see the
+/// [module documentation](self).
+pub fn inspect_with_schema(data: &[u8], schema: &Schema, name: &str) -> Result<Node> {
+    if name != "byte" && schema.get(name).is_none() {
+        return Err(Error::custom(format!("undefined type `{}`", name)));
+    }
+    let inspector = SchemaInspector { schema, data };
+    Ok(inspector.node(name, name, 0..data.len()))
+}
+
+impl Layout {
+    /// Inspect `data` as the traced type, see the [module
+    /// documentation](crate::inspect). If `is_struct` is true, the data is a
+    /// molecule struct.
+    pub fn inspect(&self, data: &[u8], is_struct: bool) -> Node {
+        let inspector = Inspector {
+            registry: &self.registry,
+            data,
+        };
+        let name = type_name(&self.root);
+        if is_struct {
+            inspector.packed_all(&name, &self.root, 0..data.len())
+        } else {
+            inspector.value(&name, &self.root, 0..data.len())
+        }
+    }
+}
+
+// The Rust type of `format`, e.g. `Vec<u8>`.
+fn type_name(format: &Format) -> String {
+    match format {
+        Format::Unit => "()".into(),
+        Format::Bool => "bool".into(),
+        Format::U8 => "u8".into(),
+        Format::U16 => "u16".into(),
+        Format::U32 => "u32".into(),
+        Format::U64 => "u64".into(),
+        Format::U128 => "u128".into(),
+        Format::I8 => "i8".into(),
+        Format::I16 => "i16".into(),
+        Format::I32 => "i32".into(),
+        Format::I64 => "i64".into(),
+        Format::I128 => "i128".into(),
+        Format::F32 => "f32".into(),
+        Format::F64 => "f64".into(),
+        Format::Char => "char".into(),
+        Format::Str => "String".into(),
+        Format::Bytes => "bytes".into(),
+        Format::Option(f) => format!("Option<{}>", type_name(f)),
+        Format::Fixvec(f) | Format::Dynvec(f) => format!("Vec<{}>", type_name(f)),
+        Format::Map(k, v) => format!("BTreeMap<{}, {}>", type_name(k), type_name(v)),
+        Format::Tuple(fs) => match fs.first() {
+            Some(first) if fs.len() > 1 && fs.iter().all(|f| f == first) => {
+                format!("[{}; {}]", type_name(first), fs.len())
+            }
+            _ => {
+                let names: Vec<_> = fs.iter().map(type_name).collect();
+                format!("({})", names.join(", "))
+            }
+        },
+        Format::Struct(f) => type_name(f),
+        Format::Raw => "CollectData".into(),
+        Format::TypeName(name) => (*name).into(),
+    }
+}
+
+// The items of a fixvec, whose length must be `4 + count * size`. Without
+// `size`, it's the size of the items, and an empty fixvec has no body.
+fn fixvec_items(part: &[u8], size: Option<usize>) -> Result<Vec<&[u8]>> {
+    let items = disassemble_fixvec(part)?;
+    let size = size.or_else(|| items.first().map(|item| item.len()));
+    let expected = items
+        .len()
+        .checked_mul(size.unwrap_or_default())
+        .and_then(|n| n.checked_add(4))
+        .ok_or(Error::Overflow)?;
+    if part.len() != expected {
+        return Err(Error::InvalidFixvec);
+    }
+    Ok(items)
+}
+
+fn is_byte(format: &Format) -> bool {
+    matches!(format, Format::U8 | Format::I8)
+}
+
+// The size of primitive `format`.
+fn primitive_size(format: &Format) -> Option<usize> {
+    match format {
+        Format::Bool | Format::U8 | Format::I8 => Some(1),
+        Format::U16 | Format::I16 => Some(2),
+        Format::U32 | Format::I32 | Format::F32 | Format::Char => Some(4),
+        Format::U64 | Format::I64 | Format::F64 => Some(8),
+        Format::U128 | Format::I128 => Some(16),
+        _ => None,
+    }
+}
+
+// The decoded text of primitive `format`, `b` has the size of it.
+fn primitive_value(format: &Format, b: &[u8]) -> String {
+    macro_rules! number {
+        ($t:ty) => {
+            <$t>::from_le_bytes(b.try_into().unwrap()).to_string()
+        };
+    }
+    match format {
+        Format::Bool => (b[0] != 0).to_string(),
+        Format::U8 => number!(u8),
+        Format::I8 => number!(i8),
+        Format::U16 => number!(u16),
+        Format::I16 => number!(i16),
+        Format::U32 => number!(u32),
+        Format::I32 => number!(i32),
+        Format::F32 => number!(f32),
+        Format::U64 => number!(u64),
+        Format::I64 => number!(i64),
+        Format::F64 => number!(f64),
+        Format::U128 => number!(u128),
+        Format::I128 => number!(i128),
+        _ => {
+            let value = u32::from_le_bytes(b.try_into().unwrap());
+            match char::from_u32(value) {
+                Some(c) => format!("{:?}", c),
+                None => format!("{:#x}", value),
+            }
+        }
+    }
+}
+
+struct Inspector<'a> {
+    registry: &'a Registry,
+    data: &'a [u8],
+}
+
+impl Inspector<'_> {
+    // Inspect the data at `range` in table mode, like `MoleculeDeserializer`.
+    fn value(&self, label: &str, format: &Format, range: Range<usize>) -> Node {
+        let part = &self.data[range.clone()];
+        let ty = type_name(format);
+        if let Some(size) = primitive_size(format) {
+            let node = Node::new(label, ty, Kind::Primitive, range);
+            if part.len() != size {
+                return node.failed(Error::MismatchedLength {
+                    expected: size,
+                    actual: part.len(),
+                });
+            }
+            return Node {
+                value: Some(primitive_value(format, part)),
+                ..node
+            };
+        }
+        match format {
+            Format::Unit => Node {
+                value: Some("()".into()),
+                ..Node::new(label, ty, Kind::Primitive, range)
+            },
+            Format::Str | Format::Bytes => {
+                let mut node = Node::new(label, ty, Kind::Fixvec, range);
+                match unpack_number(part, 0) {
+                    Ok(count) if count == part.len() - 4 => {
+                        node.header = vec![count];
+                        node.value = Some(match format {
+                            Format::Str => format!("{:?}", String::from_utf8_lossy(&part[4..])),
+                            _ => hex(&part[4..]),
+                        });
+                        node
+                    }
+                    Ok(_) => node.failed(Error::InvalidFixvec),
+                    Err(e) => node.failed(e),
+                }
+            }
+            Format::Option(f) => {
+                let mut node = Node::new(label, ty, Kind::Option, range.clone());
+                if !part.is_empty() {
+                    node.children.push(self.value(&type_name(f), f, range));
+                }
+                node
+            }
+            Format::Fixvec(f) => {
+                let mut node = Node::new(label, ty, Kind::Fixvec, range);
+                let items = match fixvec_items(part, None) {
+                    Ok(items) => items,
+                    Err(e) => return node.failed(e),
+                };
+                node.header = vec![items.len()];
+                if is_byte(f) {
+                    node.value = Some(hex(&part[4..]));
+                    return node;
+                }
+                for (i, item) in items.into_iter().enumerate() {
+                    let item = self.packed_all(&format!("[{}]", i), f, range_of(self.data, item));
+                    let failed = item.error.is_some();
+                    node.children.push(item);
+                    if failed {
+                        break;
+                    }
+                }
+                node
+            }
+            Format::Dynvec(f) => {
+                let node = Node::new(label, ty, Kind::Dynvec, range);
+                self.table(node, |i| Some((format!("[{}]", i), &**f)), 0)
+            }
+            Format::Map(key, value) => {
+                let mut node = Node::new(label, ty, Kind::Dynvec, range);
+                let entries = match table_items(self.data, &mut node, 0) {
+                    Ok(entries) => entries,
+                    Err(e) => return node.failed(e),
+                };
+                // the entries are tables of key and value
+                let fields = [("key", &**key), ("value", &**value)];
+                for (i, range) in entries.into_iter().enumerate() {
+                    let entry = Node::new(format!("[{}]", i), "entry", Kind::Table, range);
+                    let field = |i: usize| fields.get(i).map(|(n, f)| (n.to_string(), *f));
+                    node.children.push(self.table(entry, field, 2));
+                }
+                node
+            }
+            Format::Tuple(fs) => {
+                let mut node = Node::new(label, ty, Kind::Array, range.clone());
+                if fs.is_empty() || part.len() % fs.len() != 0 {
+                    return node.failed(Error::InvalidArray);
+                }
+                if fs.iter().all(is_byte) {
+                    node.value = Some(hex(part));
+                    return node;
+                }
+                let size = part.len() / fs.len();
+                for (i, f) in fs.iter().enumerate() {
+                    let start = range.start + i * size;
+                    node.children
+                        .push(self.value(&format!("[{}]", i), f, start..start + size));
+                }
+                node
+            }
+            Format::Struct(f) => match &**f {
+                Format::Option(_) if part.is_empty() => Node::new(label, ty, Kind::Option, range),
+                Format::Option(inner) => {
+                    let mut node = Node::new(label, ty, Kind::Option, range.clone());
+                    node.children
+                        .push(self.packed_all(&type_name(inner), inner, range));
+                    node
+                }
+                f => self.packed_all(label, f, range),
+            },
+            Format::Raw => Node::new(label, ty, Kind::Raw, range),
+            Format::TypeName(name) => match self.registry.get(name) {
+                None => Node::new(label, ty, Kind::Raw, range)
+                    .failed(Error::custom(format!("`{}` isn't in the registry", name))),
+                Some(ContainerFormat::UnitStruct) => Node {
+                    value: Some("()".into()),
+                    ..Node::new(label, ty, Kind::Primitive, range)
+                },
+                Some(ContainerFormat::NewtypeStruct(f)) => Node {
+                    ty,
+                    ..self.value(label, f, range)
+                },
+                Some(ContainerFormat::TupleStruct(fs)) => {
+                    let node = Node::new(label, ty, Kind::Table, range);
+                    self.table(
+                        node,
+                        |i| fs.get(i).map(|f| (format!("[{}]", i), f)),
+                        fs.len(),
+                    )
+                }
+                Some(ContainerFormat::Struct(fs)) => {
+                    let node = Node::new(label, ty, Kind::Table, range);
+                    self.table(
+                        node,
+                        |i| fs.get(i).map(|(n, f)| (n.to_string(), f)),
+                        fs.len(),
+                    )
+                }
+                Some(ContainerFormat::Enum(variants)) => self.union(label, ty, range, |id| {
+                    variants
+                        .get(id)
+                        .map(|(name, variant)| (*name, Some(variant)))
+                }),
+                Some(ContainerFormat::Union { variants, fallback }) => {
+                    self.union(label, ty, range, |id| {
+                        match variants.iter().find(|(i, _, _)| *i as usize == id) {
+                            Some((_, name, variant)) => Some((*name, Some(variant))),
+                            None => fallback.map(|name| (name, None)),
+                        }
+                    })
+                }
+            },
+            _ => unreachable!("primitives are handled above"),
+        }
+    }
+
+    // Inspect table or dynvec `node`, `field` gives the label and the format
+    // of the `i`-th item, `None` for the extra fields. `count` is the number of
+    // the expected fields.
+    fn table<'f>(
+        &self,
+        mut node: Node,
+        field: impl Fn(usize) -> Option<(String, &'f Format)>,
+        count: usize,
+    ) -> Node {
+        let items = match table_items(self.data, &mut node, count) {
+            Ok(items) => items,
+            Err(e) => return node.failed(e),
+        };
+        for (i, range) in items.into_iter().enumerate() {
+            node.children.push(match field(i) {
+                Some((label, f)) => self.value(&label, f, range),
+                None => Node::new(format!("[{}]", i), "unknown", Kind::Raw, range),
+            });
+        }
+        node
+    }
+
+    // `variant` returns the name and the format of the variant with union id,
+    // the format is `None` for the fallback variant.
+    fn union<'f>(
+        &self,
+        label: &str,
+        ty: String,
+        range: Range<usize>,
+        variant: impl Fn(usize) -> Option<(&'static str, Option<&'f VariantFormat>)>,
+    ) -> Node {
+        let mut node = Node::new(label, ty, Kind::Union, range.clone());
+        let id = match unpack_number(&self.data[range.clone()], 0) {
+            Ok(id) => id,
+            Err(e) => return node.failed(e),
+        };
+        node.header = vec![id];
+        let body = range.start + 4..range.end;
+        let child = match variant(id) {
+            None => return node.failed(Error::UnknownUnionId { id }),
+            Some((name, None)) => Node::new(name, name, Kind::Raw, body),
+            Some((name, Some(VariantFormat::Unit))) => Node {
+                value: Some("()".into()),
+                ..Node::new(name, name, Kind::Primitive, body)
+            },
+            Some((name, Some(VariantFormat::Newtype(f)))) => self.value(name, f, body),
+            Some((name, Some(VariantFormat::Tuple(fs)))) => {
+                let table = Node::new(name, name, Kind::Table, body);
+                self.table(
+                    table,
+                    |i| fs.get(i).map(|f| (format!("[{}]", i), f)),
+                    fs.len(),
+                )
+            }
+            Some((name, Some(VariantFormat::Struct(fs)))) => {
+                let table = Node::new(name, name, Kind::Table, body);
+                self.table(
+                    table,
+                    |i| fs.get(i).map(|(n, f)| (n.to_string(), f)),
+                    fs.len(),
+                )
+            }
+        };
+        node.children.push(child);
+        node
+    }
+
+    // Inspect the molecule struct at `range`, which must be used up.
+    fn packed_all(&self, label: &str, format: &Format, range: Range<usize>) -> Node {
+        let mut pos = range.start;
+        let node = self.packed(label, format, range.end, &mut pos);
+        if node.error.is_none() && pos != range.end {
+            let error = Error::MismatchedLength {
+                expected: pos - range.start,
+                actual: range.len(),
+            };
+            return Node { range, ..node }.failed(error);
+        }
+        node
+    }
+
+    // Inspect data from `pos` in struct mode, like `MoleculeStructDeserializer`.
+    fn packed(&self, label: &str, format: &Format, end: usize, pos: &mut usize) -> Node {
+        let start = *pos;
+        let ty = type_name(format);
+        if let Some(size) = primitive_size(format) {
+            if start + size > end {
+                return Node::new(label, ty, Kind::Primitive, start..end).failed(
+                    Error::MismatchedLength {
+                        expected: size,
+                        actual: end - start,
+                    },
+                );
+            }
+            *pos += size;
+            return Node {
+                value: Some(primitive_value(format, &self.data[start..*pos])),
+                ..Node::new(label, ty, Kind::Primitive, start..*pos)
+            };
+        }
+        let fields: Vec<(String, &Format)> = match format {
+            Format::Tuple(fs) if !fs.is_empty() && fs.iter().all(is_byte) => {
+                let node = Node::new(label, ty, Kind::Array, start..end);
+                if start + fs.len() > end {
+                    return node.failed(Error::MismatchedLength {
+                        expected: fs.len(),
+                        actual: end - start,
+                    });
+                }
+                *pos += fs.len();
+                return Node {
+                    range: start..*pos,
+                    value: Some(hex(&self.data[start..*pos])),
+                    ..node
+                };
+            }
+            Format::Tuple(fs) => fs
+                .iter()
+                .enumerate()
+                .map(|(i, f)| (format!("[{}]", i), f))
+                .collect(),
+            Format::Struct(f) => return self.packed(label, f, end, pos),
+            Format::Raw => {
+                *pos = end;
+                return Node::new(label, ty, Kind::Raw, start..end);
+            }
+            Format::TypeName(name) => match self.registry.get(name) {
+                Some(ContainerFormat::NewtypeStruct(f)) => {
+                    return Node {
+                        ty,
+                        ..self.packed(label, f, end, pos)
+                    }
+                }
+                Some(ContainerFormat::TupleStruct(fs)) => fs
+                    .iter()
+                    .enumerate()
+                    .map(|(i, f)| (format!("[{}]", i), f))
+                    .collect(),
+                Some(ContainerFormat::Struct(fs)) => {
+                    fs.iter().map(|(n, f)| (n.to_string(), f)).collect()
+                }
+                _ => {
+                    return Node::new(label, ty, Kind::Raw, start..end)
+                        .failed(Error::InvalidStructField)
+                }
+            },
+            _ => {
+                return Node::new(label, ty, Kind::Raw, start..end)
+                    .failed(Error::InvalidStructField)
+            }
+        };
+        let kind = match format {
+            Format::Tuple(_) => Kind::Array,
+            _ => Kind::Struct,
+        };
+        let mut node = Node::new(label, ty, kind, start..end);
+        for (label, f) in fields {
+            let child = self.packed(&label, f, end, pos);
+            let failed = child.error.is_some();
+            node.children.push(child);
+            if failed {
+                break;
+            }
+        }
+        node.range = start..*pos;
+        node
+    }
+}
+
+struct SchemaInspector<'a> {
+    schema: &'a Schema,
+    data: &'a [u8],
+}
+
+impl SchemaInspector<'_> {
+    fn node(&self, label: &str, name: &str, range: Range<usize>) -> Node {
+        let part = &self.data[range.clone()];
+        let kind = match self.schema.get(name).map(|decl| &decl.kind) {
+            Some(DeclKind::Array { .. }) => Kind::Array,
+            Some(DeclKind::Struct(_)) => Kind::Struct,
+            Some(DeclKind::Vector(item)) if self.schema.fixed_size(item).is_some() => Kind::Fixvec,
+            Some(DeclKind::Vector(_)) => Kind::Dynvec,
+            Some(DeclKind::Option(_)) => Kind::Option,
+            Some(DeclKind::Union(_)) => Kind::Union,
+            Some(DeclKind::Table(_)) => Kind::Table,
+            None => Kind::Primitive,
+        };
+        let mut node = Node::new(label, name, kind, range.clone());
+        if let Some(size) = self.schema.fixed_size(name) {
+            if part.len() != size {
+                return node.failed(Error::MismatchedLength {
+                    expected: size,
+                    actual: part.len(),
+                });
+            }
+        }
+        let decl = match self.schema.get(name) {
+            Some(decl) => decl,
+            None => {
+                node.value = Some(hex(part));
+                return node;
+            }
+        };
+        match &decl.kind {
+            DeclKind::Array { item, count } => {
+                if item == "byte" {
+                    node.value = Some(hex(part));
+                    return node;
+                }
+                let size = part.len() / count;
+                for i in 0..*count {
+                    let start = range.start + i * size;
+                    node.children
+                        .push(self.node(&format!("[{}]", i), item, start..start + size));
+                }
+            }
+            DeclKind::Struct(fields) => {
+                let mut start = range.start;
+                for field in fields {
+                    let size = self.schema.fixed_size(&field.ty).unwrap_or_default();
+                    node.children
+                        .push(self.node(&field.name, &field.ty, start..start + size));
+                    start += size;
+                }
+            }
+            DeclKind::Vector(item) => match self.schema.fixed_size(item) {
+                Some(size) => {
+                    let items = match fixvec_items(part, Some(size)) {
+                        Ok(items) => items,
+                        Err(e) => return node.failed(e),
+                    };
+                    node.header = vec![items.len()];
+                    if item == "byte" {
+                        node.value = Some(hex(&part[4..]));
+                        return node;
+                    }
+                    for (i, part) in items.into_iter().enumerate() {
+                        let range = range_of(self.data, part);
+                        node.children
+                            .push(self.node(&format!("[{}]", i), item, range));
+                    }
+                }
+                None => return self.table(node, |i| Some((format!("[{}]", i), item.as_str())), 0),
+            },
+            DeclKind::Option(item) => {
+                if !part.is_empty() {
+                    node.children.push(self.node(item, item, range));
+                }
+            }
+            DeclKind::Union(items) => {
+                let id = match unpack_number(part, 0) {
+                    Ok(id) => id,
+                    Err(e) => return node.failed(e),
+                };
+                node.header = vec![id];
+                match items.iter().find(|item| item.id as usize == id) {
+                    Some(item) => {
+                        let body = range.start + 4..range.end;
+                        node.children.push(self.node(&item.ty, &item.ty, body));
+                    }
+                    None => return node.failed(Error::UnknownUnionId { id }),
+                }
+            }
+            DeclKind::Table(fields) => {
+                let field = |i: usize| fields.get(i).map(|f| (f.name.clone(), f.ty.as_str()));
+                return self.table(node, field, fields.len());
+            }
+        }
+        node
+    }
+
+    // Same as `Inspector::table`, with type names instead of formats.
+    fn table<'f>(
+        &self,
+        mut node: Node,
+        field: impl Fn(usize) -> Option<(String, &'f str)>,
+        count: usize,
+    ) -> Node {
+        let items = match table_items(self.data, &mut node, count) {
+            Ok(items) => items,
+            Err(e) => return node.failed(e),
+        };
+        for (i, range) in items.into_iter().enumerate() {
+            node.children.push(match field(i) {
+                Some((label, ty)) => self.node(&label, ty, range),
+                None => Node::new(format!("[{}]", i), "unknown", Kind::Raw, range),
+            });
+        }
+        node
+    }
+}
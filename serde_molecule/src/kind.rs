@@ -0,0 +1,98 @@
+//! Declare once whether a type is a molecule struct or a molecule table, and
+//! the size of fixed size types.
+//!
+//! With the `derive` feature, the attribute `#[molecule(struct)]` or
+//! `#[molecule(table)]` implements [`MoleculeKind`] for a struct. A type
+//! marked as molecule struct is encoded as molecule struct everywhere: by
+//! [`to_vec`](crate::to_vec) and [`from_slice`](crate::from_slice) whatever
+//! `is_struct` is, and as a field of tables without `struct_serde`.
+//!
+//! `#[molecule(struct)]` also implements [`FixedSize`]. It fails to compile if
+//! any field isn't fixed size, e.g. `Vec`, `String`, `Option` or enum:
+//!
+#![cfg_attr(feature = "derive", doc = "```compile_fail")]
+#![cfg_attr(not(feature = "derive"), doc = "```ignore")]
+//! use serde::{Deserialize, Serialize};
+//! use serde_molecule::molecule;
+//!
+//! #[molecule(struct)]
+//! #[derive(Serialize, Deserialize)]
+//! struct Script {
+//!     code_hash: [u8; 32],
+//!     args: Vec<u8>,
+//! }
+//! ```
+//!
+//! ```
+//! # #[cfg(feature = "derive")]
+//! # {
+//! use serde::{Deserialize, Serialize};
+//! use serde_molecule::{from_slice, molecule, to_vec, FixedSize, MoleculeKind};
+//!
+//! #[molecule(struct)]
+//! #[derive(Serialize, Deserialize, PartialEq, Debug)]
+//! struct OutPoint {
+//!     tx_hash: [u8; 32],
+//!     index: u32,
+//! }
+//!
+//! #[molecule(table)]
+//! #[derive(Serialize, Deserialize, PartialEq, Debug)]
+//! struct CellInput {
+//!     since: u64,
+//!     previous_output: OutPoint,
+//! }
+//!
+//! assert!(OutPoint::IS_STRUCT);
+//! assert_eq!(OutPoint::SIZE, 36);
+//! assert!(!CellInput::IS_STRUCT);
+//! let input = CellInput {
+//!     since: 0,
+//!     previous_output: OutPoint {
+//!         tx_hash: [0; 32],
+//!         index: 1,
+//!     },
+//! };
+//! let bytes = to_vec(&input, false).unwrap();
+//! assert_eq!(bytes.len(), 12 + 8 + 36);
+//! let value: CellInput = from_slice(&bytes, false).unwrap();
+//! assert_eq!(value, input);
+//! # }
+//! ```
+
+/// The molecule kind of a type.
+pub trait MoleculeKind {
+    /// true for molecule struct, false for molecule table.
+    const IS_STRUCT: bool;
+}
+
+/// Types with a fixed size encoding: they can be fields of molecule structs
+/// and elements of fixvecs.
+///
+/// It's implemented for primitive types, arrays of fixed size types and
+/// types marked by `#[molecule(struct)]`.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` isn't fixed size",
+    label = "molecule struct fields must be fixed size",
+    note = "use a molecule table for `Vec`, `String`, `Option` or enum fields"
+)]
+pub trait FixedSize {
+    /// The number of bytes of the encoding.
+    const SIZE: usize;
+}
+
+macro_rules! impl_fixed_size {
+    ($($ty:ty),*) => {
+        $(
+            impl FixedSize for $ty {
+                const SIZE: usize = core::mem::size_of::<$ty>();
+            }
+        )*
+    };
+}
+
+impl_fixed_size!(bool, u8, i8, u16, i16, u32, i32, u64, i64, u128, i128, f32, f64, char);
+
+impl<T: FixedSize, const N: usize> FixedSize for [T; N] {
+    const SIZE: usize = T::SIZE * N;
+}
@@ -11,16 +11,37 @@ extern crate alloc;
 #[cfg(feature = "std")]
 extern crate std;
 
-pub use crate::de::from_slice;
+pub use crate::de::{from_slice, from_slice_canonical, from_slice_with, DeserializerOptions};
 pub use crate::error::{Error, Result};
-pub use crate::ser::to_vec;
+pub use crate::kind::{FixedSize, MoleculeKind};
+pub use crate::reader::MoleculeReader;
+pub use crate::ser::{serialized_size, to_slice, to_vec, to_writer};
+pub use crate::value::{
+    from_compatible_slice_with_schema, from_slice_with_schema, to_vec_with_schema, Value,
+};
+pub use crate::verify::verify;
+#[cfg(feature = "derive")]
+pub use serde_molecule_derive::{molecule, MoleculeReader};
 
 pub mod big_array_serde;
+pub mod codegen;
 pub mod de;
 pub mod dynvec_serde;
 pub mod error;
+pub mod guess;
+pub mod inspect;
+pub mod kind;
+pub mod mol;
 pub mod molecule;
+pub mod reader;
+pub mod schema;
 pub mod ser;
 pub mod struct_serde;
 #[cfg(test)]
 mod tests;
+pub mod trace;
+#[cfg(feature = "json")]
+pub mod transcode;
+pub mod value;
+pub mod verify;
+pub mod view;
@@ -0,0 +1,458 @@
+//! Generate a molecule schema(`.mol`) from a Rust type.
+//!
+//! The schema is generated from the traced [`Layout`] of the type, so the
+//! Rust types stay the source of truth and the schema can be given to the
+//! molecule code generators of other languages.
+//!
+//! ```
+//! use serde_molecule::mol::to_mol;
+//!
+//! #[derive(serde::Deserialize)]
+//! struct Script {
+//!     code_hash: [u8; 32],
+//!     hash_type: u8,
+//!     args: Vec<u8>,
+//! }
+//!
+//! assert_eq!(
+//!     to_mol::<Script>(false).unwrap(),
+//!     "\
+//! array Byte32 [byte; 32];
+//! vector Bytes <byte>;
+//!
+//! table Script {
+//!     code_hash: Byte32,
+//!     hash_type: byte,
+//!     args: Bytes,
+//! }
+//! "
+//! );
+//! ```
+//!
+//! The molecule types are named deterministically:
+//! * `u8`, `i8` and `bool` are `byte`. Other numbers are arrays of bytes named
+//!   `U16`, `I32`, `F64` and so on, `char` is `Char`.
+//! * `String` is `String`, `Vec<u8>` and bytes are `Bytes`.
+//! * Arrays are `Byte{N}` for bytes, otherwise `{Item}Array{N}`.
+//! * Vectors are `{Item}Vec`, options are `{Item}Opt`.
+//! * Maps are `{Key}{Value}Map`, vectors of `{Key}{Value}MapEntry` tables.
+//! * Structs and enums keep their Rust names. A struct used as both molecule
+//!   table and molecule struct is `{Name}Struct` in the latter case. Tuple and
+//!   struct variants are tables named `{Enum}{Variant}`.
+//! * Fields of tuple structs and tuple variants are named `f0`, `f1` and so on.
+//!
+//! Some Rust types have no molecule schema and fail: unit types and unit
+//! variants, `CollectData`, dynvecs of fixed size items(molecule makes them
+//! fixvecs), arrays of tables and different types with the same name.
+
+use crate::error::{Error, Result};
+use crate::trace::{ContainerFormat, Format, Layout, Registry, VariantFormat};
+use alloc::collections::BTreeSet;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use serde::de::{self, Error as _};
+
+/// Generate the molecule schema of type `T`. `is_struct` is the same as
+/// [`to_vec`](crate::to_vec): whether the root value is a molecule struct.
+pub fn to_mol<'de, T>(is_struct: bool) -> Result<String>
+where
+    T: de::Deserialize<'de>,
+{
+    Layout::of::<T>()?.to_mol(is_struct)
+}
+
+impl Layout {
+    /// Generate the molecule schema of the traced type, with the definitions
+    /// of all the types it uses. `is_struct` is the same as
+    /// [`to_vec`](crate::to_vec).
+    pub fn to_mol(&self, is_struct: bool) -> Result<String> {
+        // The first pass finds the containers used as molecule tables, so
+        // the names of molecule structs don't depend on the order of fields.
+        let mut generator = Generator::new(&self.registry, None);
+        generator.root(&self.root, is_struct)?;
+        let tables = generator.tables;
+        let mut generator = Generator::new(&self.registry, Some(tables));
+        generator.root(&self.root, is_struct)?;
+        Ok(generator.finish())
+    }
+}
+
+struct Generator<'a> {
+    registry: &'a Registry,
+    // containers used as molecule tables in the first pass
+    known_tables: Option<BTreeSet<&'static str>>,
+    // containers used as molecule tables in this pass
+    tables: BTreeSet<&'static str>,
+    // declarations in dependency order: (name, declaration)
+    decls: Vec<(String, String)>,
+    // names of fixed size types
+    fixed: BTreeSet<String>,
+}
+
+impl<'a> Generator<'a> {
+    fn new(registry: &'a Registry, known_tables: Option<BTreeSet<&'static str>>) -> Self {
+        let mut fixed = BTreeSet::new();
+        fixed.insert("byte".to_string());
+        Generator {
+            registry,
+            known_tables,
+            tables: BTreeSet::new(),
+            decls: Vec::new(),
+            fixed,
+        }
+    }
+
+    fn root(&mut self, format: &Format, is_struct: bool) -> Result<()> {
+        if is_struct {
+            self.packed(format)?;
+        } else {
+            self.value(format)?;
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> String {
+        let mut mol = String::new();
+        let mut block = false;
+        for (i, (_, decl)) in self.decls.iter().enumerate() {
+            // blank lines around multi-line declarations
+            let multiline = decl.contains('\n');
+            if i > 0 && (multiline || block) {
+                mol.push('\n');
+            }
+            block = multiline;
+            mol.push_str(decl);
+            mol.push('\n');
+        }
+        mol
+    }
+
+    fn container(&self, name: &str) -> Result<&'a ContainerFormat> {
+        self.registry
+            .get(name)
+            .ok_or_else(|| Error::custom(format!("`{}` isn't in the registry", name)))
+    }
+
+    // Add a declaration, or check that it's the same as the one with the
+    // same name.
+    fn declare(&mut self, name: String, decl: String, fixed: bool) -> Result<String> {
+        match self.decls.iter().find(|(n, _)| *n == name) {
+            Some((_, d)) if *d == decl => {}
+            Some(_) => {
+                return Err(Error::custom(format!(
+                    "different molecule types are named `{}`",
+                    name
+                )))
+            }
+            None => {
+                if fixed {
+                    self.fixed.insert(name.clone());
+                }
+                self.decls.push((name.clone(), decl));
+            }
+        }
+        Ok(name)
+    }
+
+    fn number(&mut self, name: &str, size: usize) -> Result<String> {
+        if size == 1 {
+            return Ok("byte".to_string());
+        }
+        let decl = format!("array {} [byte; {}];", name, size);
+        self.declare(name.to_string(), decl, true)
+    }
+
+    fn bytes(&mut self, name: &str) -> Result<String> {
+        let decl = format!("vector {} <byte>;", name);
+        self.declare(name.to_string(), decl, false)
+    }
+
+    // The molecule type of a value in table mode.
+    fn value(&mut self, format: &Format) -> Result<String> {
+        if let Some((name, size)) = number(format) {
+            return self.number(name, size);
+        }
+        match format {
+            Format::Str => self.bytes("String"),
+            Format::Bytes => self.bytes("Bytes"),
+            Format::Option(f) => {
+                let item = self.value(f)?;
+                self.option(item)
+            }
+            Format::Fixvec(f) => {
+                let item = self.packed(f)?;
+                self.vector(item)
+            }
+            Format::Dynvec(f) => {
+                let item = self.value(f)?;
+                if self.fixed.contains(&item) {
+                    return Err(Error::custom(format!(
+                        "dynvec of fixed size `{}` has no molecule schema",
+                        item
+                    )));
+                }
+                self.vector(item)
+            }
+            Format::Map(key, value) => {
+                let key = self.value(key)?;
+                let value = self.value(value)?;
+                let name = format!("{}{}Map", upper(&key), upper(&value));
+                let entry = format!("{}Entry", name);
+                let fields = [("key".to_string(), key), ("value".to_string(), value)];
+                let entry = self.compound("table", entry, &fields, false)?;
+                let decl = format!("vector {} <{}>;", name, entry);
+                self.declare(name, decl, false)
+            }
+            Format::Tuple(fs) => {
+                let items = fs
+                    .iter()
+                    .map(|f| self.value(f))
+                    .collect::<Result<Vec<_>>>()?;
+                if let Some(item) = items.iter().find(|item| !self.fixed.contains(*item)) {
+                    return Err(Error::custom(format!(
+                        "array of `{}` has no molecule schema, the items must be fixed size",
+                        item
+                    )));
+                }
+                self.array(items)
+            }
+            Format::Struct(f) => match &**f {
+                // `struct_serde` on `Option`
+                Format::Option(f) => {
+                    let item = self.packed(f)?;
+                    self.option(item)
+                }
+                f => self.packed(f),
+            },
+            Format::TypeName(name) => {
+                self.tables.insert(name);
+                match self.container(name)? {
+                    ContainerFormat::NewtypeStruct(f) => self.value(f),
+                    ContainerFormat::TupleStruct(fs) => {
+                        let fields = self.tuple_fields(fs, Self::value)?;
+                        self.compound("table", name.to_string(), &fields, false)
+                    }
+                    ContainerFormat::Struct(fs) => {
+                        let fields = self.struct_fields(fs, Self::value)?;
+                        self.compound("table", name.to_string(), &fields, false)
+                    }
+                    ContainerFormat::Enum(variants) => {
+                        let variants = variants.iter().map(|(n, v)| (None, *n, v));
+                        self.union(name, variants)
+                    }
+                    ContainerFormat::Union { variants, .. } => {
+                        // the fallback variant isn't a union item
+                        let variants = variants.iter().map(|(id, n, v)| (Some(*id), *n, v));
+                        self.union(name, variants)
+                    }
+                    ContainerFormat::UnitStruct => Err(no_schema(name)),
+                }
+            }
+            Format::Unit => Err(no_schema("()")),
+            Format::Raw => Err(no_schema("CollectData")),
+            // numbers are handled above
+            _ => unreachable!(),
+        }
+    }
+
+    // The molecule type of a value in struct mode: a field of molecule
+    // struct or an item of fixvec.
+    fn packed(&mut self, format: &Format) -> Result<String> {
+        if let Some((name, size)) = number(format) {
+            return self.number(name, size);
+        }
+        match format {
+            Format::Tuple(fs) => {
+                let items = fs
+                    .iter()
+                    .map(|f| self.packed(f))
+                    .collect::<Result<Vec<_>>>()?;
+                if items.windows(2).all(|w| w[0] == w[1]) {
+                    return self.array(items);
+                }
+                let name = format!(
+                    "{}Tuple",
+                    items.iter().map(|i| upper(i)).collect::<String>()
+                );
+                let fields: Vec<_> = items
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, item)| (format!("f{}", i), item))
+                    .collect();
+                self.compound("struct", name, &fields, true)
+            }
+            Format::Struct(f) => self.packed(f),
+            Format::TypeName(name) => {
+                let container = self.container(name)?;
+                let name = match &self.known_tables {
+                    Some(tables) if !tables.contains(name) => name.to_string(),
+                    _ => format!("{}Struct", name),
+                };
+                match container {
+                    ContainerFormat::NewtypeStruct(f) => self.packed(f),
+                    ContainerFormat::TupleStruct(fs) => {
+                        let fields = self.tuple_fields(fs, Self::packed)?;
+                        self.molecule_struct(name, &fields)
+                    }
+                    ContainerFormat::Struct(fs) => {
+                        let fields = self.struct_fields(fs, Self::packed)?;
+                        self.molecule_struct(name, &fields)
+                    }
+                    _ => Err(Error::InvalidStructField),
+                }
+            }
+            Format::Raw => Err(no_schema("CollectData")),
+            _ => Err(Error::InvalidStructField),
+        }
+    }
+
+    fn option(&mut self, item: String) -> Result<String> {
+        let name = format!("{}Opt", upper(&item));
+        let decl = format!("option {} ({});", name, item);
+        self.declare(name, decl, false)
+    }
+
+    fn vector(&mut self, item: String) -> Result<String> {
+        if item == "byte" {
+            return self.bytes("Bytes");
+        }
+        let name = format!("{}Vec", upper(&item));
+        let decl = format!("vector {} <{}>;", name, item);
+        self.declare(name, decl, false)
+    }
+
+    // `items` are fixed size.
+    fn array(&mut self, items: Vec<String>) -> Result<String> {
+        let item = match items.first() {
+            Some(item) if items.iter().all(|i| i == item) => item,
+            Some(_) => {
+                return Err(Error::custom(
+                    "tuple of different types has no molecule schema, use a molecule struct",
+                ))
+            }
+            None => return Err(no_schema("empty array")),
+        };
+        let name = if item == "byte" {
+            format!("Byte{}", items.len())
+        } else {
+            format!("{}Array{}", item, items.len())
+        };
+        let decl = format!("array {} [{}; {}];", name, item, items.len());
+        self.declare(name, decl, true)
+    }
+
+    fn molecule_struct(&mut self, name: String, fields: &[(String, String)]) -> Result<String> {
+        if fields.is_empty() {
+            return Err(Error::custom(format!(
+                "molecule struct `{}` must have fields",
+                name
+            )));
+        }
+        self.compound("struct", name, fields, true)
+    }
+
+    fn compound(
+        &mut self,
+        kind: &str,
+        name: String,
+        fields: &[(String, String)],
+        fixed: bool,
+    ) -> Result<String> {
+        let mut decl = format!("{} {} {{\n", kind, name);
+        for (field, ty) in fields {
+            decl.push_str(&format!("    {}: {},\n", field, ty));
+        }
+        decl.push('}');
+        self.declare(name, decl, fixed)
+    }
+
+    fn union<'f>(
+        &mut self,
+        name: &str,
+        variants: impl Iterator<Item = (Option<u32>, &'static str, &'f VariantFormat)>,
+    ) -> Result<String> {
+        let mut items: Vec<String> = Vec::new();
+        let mut decl = format!("union {} {{\n", name);
+        for (id, variant, format) in variants {
+            let item = match format {
+                VariantFormat::Unit => return Err(no_schema(&format!("{}::{}", name, variant))),
+                VariantFormat::Newtype(f) => self.value(f)?,
+                VariantFormat::Tuple(fs) => {
+                    let fields = self.tuple_fields(fs, Self::value)?;
+                    self.compound("table", format!("{}{}", name, variant), &fields, false)?
+                }
+                VariantFormat::Struct(fs) => {
+                    let fields = self.struct_fields(fs, Self::value)?;
+                    self.compound("table", format!("{}{}", name, variant), &fields, false)?
+                }
+            };
+            if items.contains(&item) {
+                return Err(Error::custom(format!(
+                    "`{}` is used by more than one variant of union `{}`",
+                    item, name
+                )));
+            }
+            match id {
+                Some(id) => decl.push_str(&format!("    {}: {},\n", item, id)),
+                None => decl.push_str(&format!("    {},\n", item)),
+            }
+            items.push(item);
+        }
+        decl.push('}');
+        self.declare(name.to_string(), decl, false)
+    }
+
+    fn tuple_fields(
+        &mut self,
+        fs: &[Format],
+        f: fn(&mut Self, &Format) -> Result<String>,
+    ) -> Result<Vec<(String, String)>> {
+        fs.iter()
+            .enumerate()
+            .map(|(i, format)| Ok((format!("f{}", i), f(self, format)?)))
+            .collect()
+    }
+
+    fn struct_fields(
+        &mut self,
+        fs: &[(&'static str, Format)],
+        f: fn(&mut Self, &Format) -> Result<String>,
+    ) -> Result<Vec<(String, String)>> {
+        fs.iter()
+            .map(|(name, format)| Ok((name.to_string(), f(self, format)?)))
+            .collect()
+    }
+}
+
+// The molecule name and size of numbers.
+fn number(format: &Format) -> Option<(&'static str, usize)> {
+    Some(match format {
+        Format::Bool | Format::U8 | Format::I8 => ("byte", 1),
+        Format::U16 => ("U16", 2),
+        Format::I16 => ("I16", 2),
+        Format::U32 => ("U32", 4),
+        Format::I32 => ("I32", 4),
+        Format::F32 => ("F32", 4),
+        Format::Char => ("Char", 4),
+        Format::U64 => ("U64", 8),
+        Format::I64 => ("I64", 8),
+        Format::F64 => ("F64", 8),
+        Format::U128 => ("U128", 16),
+        Format::I128 => ("I128", 16),
+        _ => return None,
+    })
+}
+
+// `byte` is the only lowercase molecule type, it's `Byte` in derived names.
+fn upper(name: &str) -> &str {
+    if name == "byte" {
+        "Byte"
+    } else {
+        name
+    }
+}
+
+fn no_schema(name: &str) -> Error {
+    Error::custom(format!("`{}` has no molecule schema", name))
+}
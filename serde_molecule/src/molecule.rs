@@ -79,13 +79,31 @@ pub fn assemble_struct(parts: Vec<Vec<u8>>) -> Vec<u8> {
 
 pub fn unpack_number(data: &[u8], offset: usize) -> Result<usize, Error> {
     if data.len() < (4 + offset) {
-        Err(Error::LengthNotEnough)
+        Err(Error::LengthNotEnough {
+            expected: offset + 4,
+            actual: data.len(),
+        })
     } else {
         let bytes: [u8; 4] = data[offset..offset + 4].try_into().unwrap();
         Ok(u32::from_le_bytes(bytes) as usize)
     }
 }
 
+/// Item count of molecule fixvec, read from its header without validation.
+pub fn fixvec_item_count(data: &[u8]) -> Result<usize, Error> {
+    unpack_number(data, 0)
+}
+
+/// Item count of molecule table or dynvec, read from its header without
+/// validation.
+pub fn table_item_count(data: &[u8]) -> Result<usize, Error> {
+    if unpack_number(data, 0)? == NUMBER_SIZE {
+        return Ok(0);
+    }
+    let first_offset = unpack_number(data, NUMBER_SIZE)?;
+    Ok((first_offset / NUMBER_SIZE).saturating_sub(1))
+}
+
 /// Disassemble molecule table or dynvec
 pub fn disassemble_fixvec(data: &[u8]) -> Result<Vec<&[u8]>, Error> {
     let item_count = unpack_number(data, 0)?;
@@ -100,7 +118,7 @@ pub fn disassemble_fixvec(data: &[u8]) -> Result<Vec<&[u8]>, Error> {
         return Err(Error::InvalidFixvec);
     }
     let item_size = remaining / item_count;
-    let mut result = vec![];
+    let mut result = Vec::with_capacity(item_count);
     for i in 0..item_count {
         result.push(&data[4 + i * item_size..4 + (i + 1) * item_size]);
     }
@@ -110,16 +128,29 @@ pub fn disassemble_fixvec(data: &[u8]) -> Result<Vec<&[u8]>, Error> {
 
 /// Disassemble molecule table or dynvec
 pub fn disassemble_table(data: &[u8]) -> Result<Vec<&[u8]>, Error> {
-    let mut result = vec![];
+    let count = verify_table(data)?;
+    let mut result = Vec::with_capacity(count);
+    for index in 0..count {
+        result.push(table_item(data, index)?);
+    }
+    Ok(result)
+}
+
+/// Validate the header of molecule table or dynvec and return the item count,
+/// without allocation.
+pub fn verify_table(data: &[u8]) -> Result<usize, Error> {
     let total_size = unpack_number(data, 0)?;
     if data.len() != total_size {
-        return Err(Error::InvalidTableLength);
+        return Err(Error::InvalidTableLength {
+            expected: total_size,
+            actual: data.len(),
+        });
     }
     if total_size == NUMBER_SIZE {
-        return Ok(result);
+        return Ok(0);
     }
     if total_size < NUMBER_SIZE * 2 {
-        return Err(Error::InvalidTableLength);
+        return Err(Error::InvalidTableHeader);
     }
     let mut cur = 0;
     cur += NUMBER_SIZE;
@@ -132,21 +163,37 @@ pub fn disassemble_table(data: &[u8]) -> Result<Vec<&[u8]>, Error> {
     }
     let count = first_offset / 4 - 1;
     let mut last_offset = first_offset;
-    let mut offset = first_offset;
     cur += NUMBER_SIZE;
     for _ in 1..count {
-        offset = unpack_number(data, cur)?;
+        let offset = unpack_number(data, cur)?;
         if last_offset > offset {
             return Err(Error::InvalidTable);
         }
         if offset > data.len() {
             return Err(Error::InvalidTable);
         }
-        result.push(&data[last_offset..offset]);
         last_offset = offset;
         cur += NUMBER_SIZE;
     }
-    result.push(&data[offset..]);
+    Ok(count)
+}
 
-    Ok(result)
+/// The `index`-th item of molecule table or dynvec, whose header is validated
+/// by [`verify_table`]. Fails with `InvalidTable` if `index` is out of range
+/// or the offsets are out of order.
+pub fn table_item(data: &[u8], index: usize) -> Result<&[u8], Error> {
+    let count = table_item_count(data)?;
+    if index >= count {
+        return Err(Error::InvalidTable);
+    }
+    let start = unpack_number(data, NUMBER_SIZE * (index + 1))?;
+    let end = if index + 1 == count {
+        data.len()
+    } else {
+        unpack_number(data, NUMBER_SIZE * (index + 2))?
+    };
+    if start > end || end > data.len() {
+        return Err(Error::InvalidTable);
+    }
+    Ok(&data[start..end])
 }
@@ -0,0 +1,375 @@
+//! Typed zero-copy readers over encoded molecule data.
+//!
+//! [`MoleculeReader`] gives a type a reader: primitives are read as their
+//! values, strings as `&str`, vectors and arrays as [`FixvecReader`],
+//! [`DynvecReader`] and [`ArrayReader`], and options as `Option` of the
+//! reader. With the `derive` feature, `#[derive(MoleculeReader)]` generates a
+//! reader for a struct or enum, named after the type with a `Reader` suffix,
+//! with an accessor method for every field. It's the ergonomics of the
+//! `*Reader` types generated by molecule, driven by the Rust types used with
+//! [`to_vec`](crate::to_vec) and [`from_slice`](crate::from_slice).
+//!
+//! Like the views of the [`view`](crate::view) module which they are built
+//! on, readers validate only the header when they are created, and nothing is
+//! allocated. An accessor validates the part it reads, so it returns a
+//! `Result`. Tables may have extra fields, appended by newer schemas.
+//!
+//! The derive supports:
+//! * Structs with named fields: a molecule table, or a molecule struct if the
+//!   type is marked by `#[molecule(struct)]`. Use the marker instead of
+//!   `struct_serde`, which is rejected. `Vec` fields with `dynvec_serde` are
+//!   read by [`DynvecReader`].
+//! * Newtype structs, read as the inner type.
+//! * Enums with unit and newtype variants and the fallback variant, as molecule
+//!   union. The reader is an enum with the readers of the variants, and the
+//!   union id and the body for the fallback variant.
+//!
+//! The types of fields must implement `MoleculeReader`: maps, tuples and
+//! generic types aren't supported.
+//!
+//! ```
+//! # #[cfg(feature = "derive")]
+//! # {
+//! use serde::{Deserialize, Serialize};
+//! use serde_molecule::{dynvec_serde, molecule, to_vec, MoleculeReader};
+//!
+//! #[molecule(struct)]
+//! #[derive(Serialize, Deserialize, MoleculeReader)]
+//! struct OutPoint {
+//!     tx_hash: [u8; 32],
+//!     index: u32,
+//! }
+//!
+//! #[derive(Serialize, Deserialize, MoleculeReader)]
+//! struct Script {
+//!     code_hash: [u8; 32],
+//!     hash_type: u8,
+//!     args: Vec<u8>,
+//! }
+//!
+//! #[derive(Serialize, Deserialize, MoleculeReader)]
+//! struct CellOutput {
+//!     capacity: u64,
+//!     lock: Script,
+//!     type_: Option<Script>,
+//! }
+//!
+//! #[derive(Serialize, Deserialize, MoleculeReader)]
+//! struct Transaction {
+//!     inputs: Vec<OutPoint>,
+//!     #[serde(with = "dynvec_serde")]
+//!     outputs: Vec<CellOutput>,
+//! }
+//!
+//! let script = Script {
+//!     code_hash: [1; 32],
+//!     hash_type: 1,
+//!     args: vec![2, 3],
+//! };
+//! let tx = Transaction {
+//!     inputs: vec![OutPoint { tx_hash: [4; 32], index: 5 }],
+//!     outputs: vec![CellOutput { capacity: 100, lock: script, type_: None }],
+//! };
+//! let bytes = to_vec(&tx, false).unwrap();
+//!
+//! let tx = TransactionReader::new(&bytes).unwrap();
+//! assert_eq!(tx.inputs().unwrap().get(0).unwrap().index().unwrap(), 5);
+//! let output = tx.outputs().unwrap().get(0).unwrap();
+//! assert_eq!(output.capacity().unwrap(), 100);
+//! assert_eq!(output.lock().unwrap().args().unwrap().body(), &[2, 3]);
+//! assert!(output.type_().unwrap().is_none());
+//! # }
+//! ```
+use crate::error::{Error, Result, Segment};
+use crate::from_slice;
+use crate::inspect::range_of;
+use crate::kind::FixedSize;
+use crate::view::{DynvecView, FixvecView, TableView};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Debug};
+use core::marker::PhantomData;
+
+/// Types with a zero-copy reader over their encoding, see the
+/// [module documentation](self).
+pub trait MoleculeReader {
+    /// The reader, or the value itself for primitives.
+    type Reader<'a>: Copy + Debug;
+
+    /// Read `data`, the encoding of the type. Only the header is validated.
+    fn read(data: &[u8]) -> Result<Self::Reader<'_>>;
+}
+
+macro_rules! impl_primitive_reader {
+    ($($ty:ty),*) => {
+        $(
+            impl MoleculeReader for $ty {
+                type Reader<'a> = $ty;
+
+                fn read(data: &[u8]) -> Result<$ty> {
+                    from_slice(data, true)
+                }
+            }
+        )*
+    };
+}
+
+impl_primitive_reader!(bool, u8, i8, u16, i16, u32, i32, u64, i64, u128, i128, f32, f64, char);
+
+impl MoleculeReader for String {
+    type Reader<'a> = &'a str;
+
+    fn read(data: &[u8]) -> Result<&str> {
+        let body = FixvecView::new(data, 1)?.body();
+        core::str::from_utf8(body).map_err(|e| Error::InvalidUtf8 {
+            offset: 4 + e.valid_up_to(),
+        })
+    }
+}
+
+impl<T: MoleculeReader> MoleculeReader for Option<T> {
+    type Reader<'a> = Option<T::Reader<'a>>;
+
+    fn read(data: &[u8]) -> Result<Option<T::Reader<'_>>> {
+        if data.is_empty() {
+            Ok(None)
+        } else {
+            T::read(data).map(Some)
+        }
+    }
+}
+
+impl<T: MoleculeReader + FixedSize> MoleculeReader for Vec<T> {
+    type Reader<'a> = FixvecReader<'a, T>;
+
+    fn read(data: &[u8]) -> Result<FixvecReader<'_, T>> {
+        FixvecReader::new(data)
+    }
+}
+
+impl<T: MoleculeReader + FixedSize, const N: usize> MoleculeReader for [T; N] {
+    type Reader<'a> = ArrayReader<'a, T, N>;
+
+    fn read(data: &[u8]) -> Result<ArrayReader<'_, T, N>> {
+        ArrayReader::new(data)
+    }
+}
+
+/// Marks a `Vec<T>` encoded as dynvec, with `dynvec_serde`. Its reader is
+/// [`DynvecReader`].
+pub struct Dynvec<T>(PhantomData<T>);
+
+impl<T: MoleculeReader> MoleculeReader for Dynvec<T> {
+    type Reader<'a> = DynvecReader<'a, T>;
+
+    fn read(data: &[u8]) -> Result<DynvecReader<'_, T>> {
+        DynvecReader::new(data)
+    }
+}
+
+/// The reader of fixvec, `Vec<T>` by default.
+pub struct FixvecReader<'a, T> {
+    view: FixvecView<'a>,
+    _marker: PhantomData<T>,
+}
+
+impl<'a, T: MoleculeReader + FixedSize> FixvecReader<'a, T> {
+    /// Validate the item count of `data`.
+    pub fn new(data: &'a [u8]) -> Result<Self> {
+        Ok(FixvecReader {
+            view: FixvecView::new(data, T::SIZE)?,
+            _marker: PhantomData,
+        })
+    }
+
+    /// The number of items.
+    pub fn len(&self) -> usize {
+        self.view.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.view.is_empty()
+    }
+
+    /// The read data, with the item count.
+    pub fn as_slice(&self) -> &'a [u8] {
+        self.view.as_slice()
+    }
+
+    /// The items without the item count, e.g. the content of `Vec<u8>`.
+    pub fn body(&self) -> &'a [u8] {
+        self.view.body()
+    }
+
+    /// Read the `index`-th item.
+    pub fn get(&self, index: usize) -> Result<T::Reader<'a>> {
+        let item = self.view.item(index)?;
+        T::read(item).map_err(|e| e.at(4 + index * T::SIZE, Segment::Index(index)))
+    }
+
+    /// Read the items.
+    pub fn iter(&self) -> impl Iterator<Item = Result<T::Reader<'a>>> + 'a
+    where
+        T: 'a,
+    {
+        let reader = *self;
+        (0..self.len()).map(move |index| reader.get(index))
+    }
+}
+
+/// The reader of dynvec, `Vec<T>` with `dynvec_serde`.
+pub struct DynvecReader<'a, T> {
+    view: DynvecView<'a>,
+    _marker: PhantomData<T>,
+}
+
+impl<'a, T: MoleculeReader> DynvecReader<'a, T> {
+    /// Validate the header of `data`: the full size and the offsets.
+    pub fn new(data: &'a [u8]) -> Result<Self> {
+        Ok(DynvecReader {
+            view: DynvecView::new(data)?,
+            _marker: PhantomData,
+        })
+    }
+
+    /// The number of items.
+    pub fn len(&self) -> usize {
+        self.view.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.view.is_empty()
+    }
+
+    /// The read data, with the header.
+    pub fn as_slice(&self) -> &'a [u8] {
+        self.view.as_slice()
+    }
+
+    /// Read the `index`-th item.
+    pub fn get(&self, index: usize) -> Result<T::Reader<'a>> {
+        let item = self.view.item(index)?;
+        let offset = range_of(self.as_slice(), item).start;
+        T::read(item).map_err(|e| e.at(offset, Segment::Index(index)))
+    }
+
+    /// Read the items.
+    pub fn iter(&self) -> impl Iterator<Item = Result<T::Reader<'a>>> + 'a
+    where
+        T: 'a,
+    {
+        let reader = *self;
+        (0..self.len()).map(move |index| reader.get(index))
+    }
+}
+
+/// The reader of array `[T; N]`.
+pub struct ArrayReader<'a, T, const N: usize> {
+    data: &'a [u8],
+    _marker: PhantomData<T>,
+}
+
+impl<'a, T: MoleculeReader + FixedSize, const N: usize> ArrayReader<'a, T, N> {
+    /// Check the length of `data`.
+    pub fn new(data: &'a [u8]) -> Result<Self> {
+        let expected = T::SIZE * N;
+        if data.len() != expected {
+            return Err(Error::MismatchedLength {
+                expected,
+                actual: data.len(),
+            });
+        }
+        Ok(ArrayReader {
+            data,
+            _marker: PhantomData,
+        })
+    }
+
+    /// The read data.
+    pub fn as_slice(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Read the `index`-th item.
+    pub fn get(&self, index: usize) -> Result<T::Reader<'a>> {
+        if index >= N {
+            return Err(Error::IndexOutOfBounds { index, len: N });
+        }
+        let offset = index * T::SIZE;
+        T::read(&self.data[offset..offset + T::SIZE])
+            .map_err(|e| e.at(offset, Segment::Index(index)))
+    }
+}
+
+impl<'a, const N: usize> ArrayReader<'a, u8, N> {
+    /// The bytes, e.g. of a hash.
+    pub fn as_array(&self) -> &'a [u8; N] {
+        self.data.try_into().unwrap()
+    }
+}
+
+// Derives would require `T: Clone` and `T: Debug`, `T` is only a marker.
+macro_rules! impl_reader_traits {
+    ($name:ident, $field:ident $(, $n:ident)?) => {
+        impl<T $(, const $n: usize)?> Clone for $name<'_, T $(, $n)?> {
+            fn clone(&self) -> Self {
+                *self
+            }
+        }
+
+        impl<T $(, const $n: usize)?> Copy for $name<'_, T $(, $n)?> {}
+
+        impl<T $(, const $n: usize)?> Debug for $name<'_, T $(, $n)?> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.debug_tuple(stringify!($name)).field(&self.$field).finish()
+            }
+        }
+    };
+}
+
+impl_reader_traits!(FixvecReader, view);
+impl_reader_traits!(DynvecReader, view);
+impl_reader_traits!(ArrayReader, data, N);
+
+/// Read the `index`-th field of table `view`, used by the derive.
+#[doc(hidden)]
+pub fn read_field<'a, T: MoleculeReader>(
+    view: &TableView<'a>,
+    index: usize,
+    name: &'static str,
+) -> Result<T::Reader<'a>> {
+    let item = view.item(index)?;
+    let offset = range_of(view.as_slice(), item).start;
+    T::read(item).map_err(|e| e.at(offset, Segment::Field(name)))
+}
+
+/// Read the field at `offset` of molecule struct `data`, used by the derive.
+#[doc(hidden)]
+pub fn read_struct_field<'a, T: MoleculeReader + FixedSize>(
+    data: &'a [u8],
+    offset: usize,
+    name: &'static str,
+) -> Result<T::Reader<'a>> {
+    T::read(&data[offset..offset + T::SIZE]).map_err(|e| e.at(offset, Segment::Field(name)))
+}
+
+/// Read the body of union variant `name`, used by the derive.
+#[doc(hidden)]
+pub fn read_variant<'a, T: MoleculeReader>(
+    body: &'a [u8],
+    name: &'static str,
+) -> Result<T::Reader<'a>> {
+    T::read(body).map_err(|e| e.at(4, Segment::Field(name)))
+}
+
+/// Check the length of molecule struct `data`, used by the derive.
+#[doc(hidden)]
+pub fn check_struct<T: FixedSize>(data: &[u8]) -> Result<()> {
+    if data.len() != T::SIZE {
+        return Err(Error::MismatchedLength {
+            expected: T::SIZE,
+            actual: data.len(),
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,647 @@
+//! Parse and resolve molecule schemas(`.mol`).
+//!
+//! [`parse`] turns the text of one schema file into an [`Ast`].
+//! [`Schema`] follows the imports, resolves the references between types and
+//! checks the schema like `moleculec` does: undefined or duplicated types,
+//! recursive types, and dynamic size types in structs or arrays are rejected.
+//!
+//! ```
+//! use serde_molecule::schema::{DeclKind, Schema};
+//!
+//! let schema = Schema::parse(
+//!     "
+//!     array Byte32 [byte; 32];
+//!     vector Bytes <byte>;
+//!     struct OutPoint {
+//!         tx_hash: Byte32,
+//!         index: Uint32,
+//!     }
+//!     ",
+//! );
+//! assert_eq!(schema.unwrap_err().to_string(), "undefined type `Uint32` at line 4");
+//!
+//! let schema = Schema::parse(
+//!     "
+//!     array Uint32 [byte; 4];
+//!     array Byte32 [byte; 32];
+//!     vector Bytes <byte>;
+//!     struct OutPoint {
+//!         tx_hash: Byte32,
+//!         index: Uint32,
+//!     }
+//!     // ids are the previous one plus one if not given
+//!     union Lock {
+//!         OutPoint: 3,
+//!         Bytes,
+//!     }
+//!     ",
+//! )
+//! .unwrap();
+//! assert_eq!(schema.fixed_size("OutPoint"), Some(36));
+//! assert_eq!(schema.fixed_size("Bytes"), None);
+//! match &schema.get("Lock").unwrap().kind {
+//!     DeclKind::Union(items) => assert_eq!(items[1].id, 4),
+//!     _ => unreachable!(),
+//! }
+//! ```
+//!
+//! Imports are paths relative to the importing file, without the `.mol`
+//! extension, e.g. `import ../common/basic_types;`. The declarations of
+//! imported files come first in [`Schema::decls`].
+
+use crate::error::{Error, Result};
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use serde::de::Error as _;
+
+/// The syntax tree of one schema file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Ast {
+    pub imports: Vec<Import>,
+    pub decls: Vec<Decl>,
+}
+
+/// `import path;`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Import {
+    /// The path as written, without the `.mol` extension.
+    pub path: String,
+    pub line: usize,
+}
+
+/// A type declaration.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Decl {
+    pub name: String,
+    pub kind: DeclKind,
+    /// The line of the declaration in its file, starting from 1.
+    pub line: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DeclKind {
+    /// `array Name [Item; count];`
+    Array { item: String, count: usize },
+    /// `struct Name { field: Type, ... }`
+    Struct(Vec<Field>),
+    /// `vector Name <Item>;`: fixvec if the item is fixed size, otherwise
+    /// dynvec.
+    Vector(String),
+    /// `option Name (Item);`
+    Option(String),
+    /// `union Name { Item, Item: id, ... }`
+    Union(Vec<UnionItem>),
+    /// `table Name { field: Type, ... }`
+    Table(Vec<Field>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Field {
+    pub name: String,
+    pub ty: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnionItem {
+    pub ty: String,
+    /// The given union id, or the id of the previous item plus one, starting
+    /// from 0.
+    pub id: u32,
+}
+
+/// Parse the text of one schema file. References aren't resolved, see
+/// [`Schema`].
+pub fn parse(text: &str) -> Result<Ast> {
+    let mut parser = Parser {
+        text,
+        pos: 0,
+        line: 1,
+    };
+    let mut ast = Ast {
+        imports: Vec::new(),
+        decls: Vec::new(),
+    };
+    loop {
+        parser.skip()?;
+        if parser.pos == text.len() {
+            return Ok(ast);
+        }
+        let line = parser.line;
+        let keyword = parser.ident()?;
+        let (name, kind) = match keyword.as_str() {
+            "import" => {
+                let path = parser.import_path()?;
+                ast.imports.push(Import { path, line });
+                continue;
+            }
+            "array" => {
+                let name = parser.ident()?;
+                parser.expect('[')?;
+                let item = parser.ident()?;
+                parser.expect(';')?;
+                let count = parser.number()?;
+                parser.expect(']')?;
+                parser.expect(';')?;
+                (name, DeclKind::Array { item, count })
+            }
+            "struct" => {
+                let name = parser.ident()?;
+                (name, DeclKind::Struct(parser.fields()?))
+            }
+            "table" => {
+                let name = parser.ident()?;
+                (name, DeclKind::Table(parser.fields()?))
+            }
+            "vector" => {
+                let name = parser.ident()?;
+                parser.expect('<')?;
+                let item = parser.ident()?;
+                parser.expect('>')?;
+                parser.expect(';')?;
+                (name, DeclKind::Vector(item))
+            }
+            "option" => {
+                let name = parser.ident()?;
+                parser.expect('(')?;
+                let item = parser.ident()?;
+                parser.expect(')')?;
+                parser.expect(';')?;
+                (name, DeclKind::Option(item))
+            }
+            "union" => {
+                let name = parser.ident()?;
+                (name, DeclKind::Union(parser.union_items()?))
+            }
+            _ => {
+                return Err(schema_error(
+                    line,
+                    format!("expected declaration, found `{}`", keyword),
+                ))
+            }
+        };
+        ast.decls.push(Decl { name, kind, line });
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    line: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn error(&self, message: impl ToString) -> Error {
+        schema_error(self.line, message)
+    }
+
+    fn advance(&mut self, len: usize) {
+        self.line += self.rest()[..len].matches('\n').count();
+        self.pos += len;
+    }
+
+    // Skip whitespaces and comments.
+    fn skip(&mut self) -> Result<()> {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.advance(rest.len() - trimmed.len());
+            if trimmed.starts_with("//") {
+                self.advance(trimmed.find('\n').unwrap_or(trimmed.len()));
+            } else if trimmed.starts_with("/*") {
+                match trimmed.find("*/") {
+                    Some(end) => self.advance(end + 2),
+                    None => return Err(self.error("unterminated comment")),
+                }
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn found(&self) -> String {
+        match self.rest().chars().next() {
+            Some(c) => format!("`{}`", c),
+            None => "end of file".to_string(),
+        }
+    }
+
+    fn eat(&mut self, c: char) -> Result<bool> {
+        self.skip()?;
+        if self.rest().starts_with(c) {
+            self.advance(c.len_utf8());
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        if self.eat(c)? {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected `{}`, found {}", c, self.found())))
+        }
+    }
+
+    // Take the longest prefix matching `f`.
+    fn take(&mut self, f: impl Fn(char) -> bool) -> &'a str {
+        let rest = self.rest();
+        let len = rest.find(|c| !f(c)).unwrap_or(rest.len());
+        self.advance(len);
+        &rest[..len]
+    }
+
+    fn ident(&mut self) -> Result<String> {
+        self.skip()?;
+        if !self
+            .rest()
+            .starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        {
+            return Err(self.error(format!("expected identifier, found {}", self.found())));
+        }
+        Ok(self
+            .take(|c| c.is_ascii_alphanumeric() || c == '_')
+            .to_string())
+    }
+
+    fn number(&mut self) -> Result<usize> {
+        self.skip()?;
+        let digits = self.take(|c| c.is_ascii_digit());
+        if digits.is_empty() {
+            return Err(self.error(format!("expected number, found {}", self.found())));
+        }
+        digits
+            .parse()
+            .map_err(|_| self.error(format!("number `{}` is too large", digits)))
+    }
+
+    fn import_path(&mut self) -> Result<String> {
+        self.skip()?;
+        let path = self.take(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '/' | '-'));
+        if path.is_empty() {
+            return Err(self.error(format!("expected import path, found {}", self.found())));
+        }
+        let path = path.to_string();
+        self.expect(';')?;
+        Ok(path)
+    }
+
+    // `{ name: Type, ... }`, the last comma is optional.
+    fn fields(&mut self) -> Result<Vec<Field>> {
+        self.expect('{')?;
+        let mut fields = Vec::new();
+        while !self.eat('}')? {
+            let name = self.ident()?;
+            self.expect(':')?;
+            let ty = self.ident()?;
+            fields.push(Field { name, ty });
+            if !self.eat(',')? {
+                self.expect('}')?;
+                break;
+            }
+        }
+        Ok(fields)
+    }
+
+    // `{ Item, Item: id, ... }`, the last comma is optional.
+    fn union_items(&mut self) -> Result<Vec<UnionItem>> {
+        self.expect('{')?;
+        let mut items: Vec<UnionItem> = Vec::new();
+        while !self.eat('}')? {
+            let ty = self.ident()?;
+            let id = if self.eat(':')? {
+                let id = self.number()?;
+                u32::try_from(id).map_err(|_| self.error(format!("union id {} overflows", id)))?
+            } else {
+                match items.last() {
+                    None => 0,
+                    Some(last) => last
+                        .id
+                        .checked_add(1)
+                        .ok_or_else(|| self.error("union id overflows"))?,
+                }
+            };
+            items.push(UnionItem { ty, id });
+            if !self.eat(',')? {
+                self.expect('}')?;
+                break;
+            }
+        }
+        Ok(items)
+    }
+}
+
+/// A resolved molecule schema: all types are defined, and fixed size types
+/// are known.
+#[derive(Clone, Debug)]
+pub struct Schema {
+    decls: Vec<Decl>,
+    index: BTreeMap<String, usize>,
+    // the size of fixed size types, in the order of `decls`
+    sizes: Vec<Option<usize>>,
+}
+
+impl Schema {
+    /// Parse and resolve a schema without imports.
+    pub fn parse(text: &str) -> Result<Schema> {
+        Self::parse_with(text, |path| {
+            Err(Error::custom(format!(
+                "can't import `{}`, use `Schema::parse_with` to load imports",
+                path
+            )))
+        })
+    }
+
+    /// Parse and resolve a schema, `load` returns the text of imported files.
+    /// It's called once per file, with the path relative to the directory of
+    /// `text` and the `.mol` extension, e.g. `common/basic_types.mol`.
+    pub fn parse_with(text: &str, load: impl FnMut(&str) -> Result<String>) -> Result<Schema> {
+        let mut imports = Imports {
+            load,
+            loaded: BTreeSet::new(),
+            stack: Vec::new(),
+            decls: Vec::new(),
+        };
+        imports.add("", text)?;
+        resolve(imports.decls)
+    }
+
+    /// Load and resolve the schema file at `path` and its imports.
+    #[cfg(feature = "std")]
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Schema> {
+        let path = path.as_ref();
+        let dir = path.parent().unwrap_or_else(|| std::path::Path::new(""));
+        let text = std::fs::read_to_string(path).map_err(Error::Io)?;
+        Self::parse_with(&text, |import| {
+            std::fs::read_to_string(dir.join(import)).map_err(Error::Io)
+        })
+    }
+
+    /// All declarations, imported ones first.
+    pub fn decls(&self) -> &[Decl] {
+        &self.decls
+    }
+
+    /// The declaration of type `name`, `None` for `byte` or undefined types.
+    pub fn get(&self, name: &str) -> Option<&Decl> {
+        self.index.get(name).map(|&i| &self.decls[i])
+    }
+
+    /// The size of `byte`, arrays and structs, `None` for dynamic size or
+    /// undefined types.
+    pub fn fixed_size(&self, name: &str) -> Option<usize> {
+        if name == "byte" {
+            return Some(1);
+        }
+        self.index.get(name).and_then(|&i| self.sizes[i])
+    }
+}
+
+struct Imports<F> {
+    load: F,
+    loaded: BTreeSet<String>,
+    // the files being imported, to detect circular imports
+    stack: Vec<String>,
+    decls: Vec<Decl>,
+}
+
+impl<F: FnMut(&str) -> Result<String>> Imports<F> {
+    // Add the declarations of file `path` after the ones it imports.
+    fn add(&mut self, path: &str, text: &str) -> Result<()> {
+        let ast = parse(text).map_err(|e| in_file(e, path))?;
+        let dir = match path.rfind('/') {
+            Some(i) => &path[..i],
+            None => "",
+        };
+        self.stack.push(path.to_string());
+        for import in ast.imports {
+            let file = format!("{}.mol", join(dir, &import.path));
+            if self.stack.contains(&file) {
+                let error = schema_error(import.line, format!("circular import `{}`", file));
+                return Err(in_file(error, path));
+            }
+            if self.loaded.insert(file.clone()) {
+                let text = (self.load)(&file)?;
+                self.add(&file, &text)?;
+            }
+        }
+        self.stack.pop();
+        self.decls.extend(ast.decls);
+        Ok(())
+    }
+}
+
+// Join the relative paths `dir` and `path`, removing `.` and `..`.
+fn join(dir: &str, path: &str) -> String {
+    let mut segments: Vec<&str> = dir.split('/').filter(|s| !s.is_empty()).collect();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." if segments.last().is_some_and(|s| *s != "..") => {
+                segments.pop();
+            }
+            _ => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
+
+fn in_file(error: Error, path: &str) -> Error {
+    match error {
+        Error::InvalidSchema { line, message } if !path.is_empty() => Error::InvalidSchema {
+            line,
+            message: format!("{} in `{}`", message, path),
+        },
+        error => error,
+    }
+}
+
+fn schema_error(line: usize, message: impl ToString) -> Error {
+    Error::InvalidSchema {
+        line,
+        message: message.to_string(),
+    }
+}
+
+fn resolve(decls: Vec<Decl>) -> Result<Schema> {
+    let mut index = BTreeMap::new();
+    for (i, decl) in decls.iter().enumerate() {
+        if decl.name == "byte" || index.insert(decl.name.clone(), i).is_some() {
+            return Err(schema_error(
+                decl.line,
+                format!("type `{}` is defined more than once", decl.name),
+            ));
+        }
+    }
+    for decl in &decls {
+        check(decl, &index)?;
+    }
+    let mut resolver = Resolver {
+        decls: &decls,
+        index: &index,
+        sizes: Vec::new(),
+        state: Vec::new(),
+    };
+    resolver.sizes.resize(decls.len(), None);
+    resolver.state.resize(decls.len(), State::New);
+    for i in 0..decls.len() {
+        resolver.visit(i)?;
+    }
+    let sizes = resolver.sizes;
+    Ok(Schema {
+        decls,
+        index,
+        sizes,
+    })
+}
+
+// The checks of one declaration, sizes and recursion aside.
+fn check(decl: &Decl, index: &BTreeMap<String, usize>) -> Result<()> {
+    let error = |message: String| schema_error(decl.line, message);
+    for ty in references(&decl.kind) {
+        if ty != "byte" && !index.contains_key(ty) {
+            return Err(error(format!("undefined type `{}`", ty)));
+        }
+    }
+    match &decl.kind {
+        DeclKind::Array { count: 0, .. } => Err(error(format!(
+            "array `{}` must have at least one item",
+            decl.name
+        ))),
+        DeclKind::Struct(fields) if fields.is_empty() => Err(error(format!(
+            "struct `{}` must have at least one field",
+            decl.name
+        ))),
+        DeclKind::Union(items) if items.is_empty() => Err(error(format!(
+            "union `{}` must have at least one item",
+            decl.name
+        ))),
+        DeclKind::Struct(fields) | DeclKind::Table(fields) => {
+            for (i, field) in fields.iter().enumerate() {
+                if fields[..i].iter().any(|f| f.name == field.name) {
+                    return Err(error(format!(
+                        "field `{}` of `{}` is defined more than once",
+                        field.name, decl.name
+                    )));
+                }
+            }
+            Ok(())
+        }
+        DeclKind::Union(items) => {
+            for (i, item) in items.iter().enumerate() {
+                if items[..i].iter().any(|x| x.ty == item.ty) {
+                    return Err(error(format!(
+                        "`{}` is more than once in union `{}`",
+                        item.ty, decl.name
+                    )));
+                }
+                if items[..i].iter().any(|x| x.id == item.id) {
+                    return Err(error(format!(
+                        "union id {} is used more than once in union `{}`",
+                        item.id, decl.name
+                    )));
+                }
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn references(kind: &DeclKind) -> Vec<&str> {
+    match kind {
+        DeclKind::Array { item, .. } | DeclKind::Vector(item) | DeclKind::Option(item) => {
+            alloc::vec![item.as_str()]
+        }
+        DeclKind::Struct(fields) | DeclKind::Table(fields) => {
+            fields.iter().map(|f| f.ty.as_str()).collect()
+        }
+        DeclKind::Union(items) => items.iter().map(|i| i.ty.as_str()).collect(),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    New,
+    Visiting,
+    Done,
+}
+
+struct Resolver<'a> {
+    decls: &'a [Decl],
+    index: &'a BTreeMap<String, usize>,
+    sizes: Vec<Option<usize>>,
+    state: Vec<State>,
+}
+
+impl Resolver<'_> {
+    // Compute the size of type `i` after the types it references, failing on
+    // recursive types.
+    fn visit(&mut self, i: usize) -> Result<()> {
+        let decl = &self.decls[i];
+        match self.state[i] {
+            State::Done => return Ok(()),
+            State::Visiting => {
+                return Err(schema_error(
+                    decl.line,
+                    format!("recursive type `{}`", decl.name),
+                ))
+            }
+            State::New => {}
+        }
+        self.state[i] = State::Visiting;
+        for ty in references(&decl.kind) {
+            if let Some(&j) = self.index.get(ty) {
+                self.visit(j)?;
+            }
+        }
+        self.state[i] = State::Done;
+
+        let error = |message: String| schema_error(decl.line, message);
+        self.sizes[i] = match &decl.kind {
+            DeclKind::Array { item, count } => {
+                let size = self.size(item).ok_or_else(|| {
+                    error(format!(
+                        "array `{}` can't have dynamic size item `{}`",
+                        decl.name, item
+                    ))
+                })?;
+                let size = size
+                    .checked_mul(*count)
+                    .ok_or_else(|| error(format!("size of `{}` overflows", decl.name)))?;
+                Some(size)
+            }
+            DeclKind::Struct(fields) => {
+                let mut total = 0usize;
+                for field in fields {
+                    let size = self.size(&field.ty).ok_or_else(|| {
+                        error(format!(
+                            "struct `{}` can't have dynamic size field `{}: {}`",
+                            decl.name, field.name, field.ty
+                        ))
+                    })?;
+                    total = total
+                        .checked_add(size)
+                        .ok_or_else(|| error(format!("size of `{}` overflows", decl.name)))?;
+                }
+                Some(total)
+            }
+            _ => None,
+        };
+        Ok(())
+    }
+
+    fn size(&self, ty: &str) -> Option<usize> {
+        if ty == "byte" {
+            Some(1)
+        } else {
+            self.sizes[self.index[ty]]
+        }
+    }
+}
@@ -1,6 +1,6 @@
 //! Serialize a Rust data structure into molecule data.
+use crate::de::{struct_name, union_ids, RAW_STR, STRUCT_STR};
 use crate::error::{Error, Result};
-use crate::molecule::{assemble_fixvec, assemble_struct, assemble_table};
 use alloc::vec;
 use alloc::vec::Vec;
 use serde::ser::{self, Serialize};
@@ -13,158 +13,471 @@ pub fn to_vec<T>(value: &T, is_struct: bool) -> Result<Vec<u8>>
 where
     T: ?Sized + Serialize,
 {
-    let mut serializer = MoleculeSerializer::new(is_struct);
+    let mut serializer = MoleculeSerializer::new(vec![], is_struct);
     value.serialize(&mut serializer)?;
-    Ok(serializer.into())
+    Ok(serializer.output)
+}
+
+/// Serialize the given data structure into `buf`, returning the number of
+/// bytes written. Nothing is allocated, so together with
+/// [`FixedSize`](crate::FixedSize) it can encode molecule structs on the stack:
+///
+/// ```
+/// use serde_molecule::{to_slice, FixedSize};
+///
+/// let mut buf = [0u8; <[u32; 2]>::SIZE];
+/// let len = to_slice(&[1u32, 2], &mut buf, true).unwrap();
+/// assert_eq!(len, 8);
+/// assert_eq!(buf, [1, 0, 0, 0, 2, 0, 0, 0]);
+/// ```
+///
+/// It fails with `Error::BufferTooSmall` if the encoding doesn't fit in `buf`.
+///
+/// Arguments
+/// * is_struct - mapping to molecule struct. Set to false to map to molecule table.
+pub fn to_slice<T>(value: &T, buf: &mut [u8], is_struct: bool) -> Result<usize>
+where
+    T: ?Sized + Serialize,
+{
+    let mut serializer = MoleculeSerializer::new(SliceOutput { buf, len: 0 }, is_struct);
+    value.serialize(&mut serializer)?;
+    Ok(serializer.output.len)
+}
+
+/// Serialize the given data structure into a [`Write`] sink.
+///
+/// The molecule format requires the header of a table or dynvec before its
+/// body. The value is walked twice: the first pass only records the header
+/// numbers, the second pass streams the header and the body into `writer`.
+/// No intermediate buffer is allocated for the encoded fields.
+///
+/// When `writer` is a file or a socket, wrap it with a buffered writer: the
+/// header numbers are written 4 bytes at a time.
+///
+/// Arguments
+/// * is_struct - mapping to molecule struct. Set to false to map to molecule table.
+pub fn to_writer<W, T>(writer: &mut W, value: &T, is_struct: bool) -> Result<()>
+where
+    W: ?Sized + Write,
+    T: ?Sized + Serialize,
+{
+    let mut measure = MoleculeSerializer::new(Measure::default(), is_struct);
+    value.serialize(&mut measure)?;
+    let headers = measure.output.headers;
+    let mut emit = MoleculeSerializer::new(Emit::new(writer, &headers), is_struct);
+    value.serialize(&mut emit)
+}
+
+/// Compute the length of the molecule encoding of the given data structure,
+/// including table headers and fixvec counts. Nothing is written and no heap
+/// memory is allocated.
+///
+/// Arguments
+/// * is_struct - mapping to molecule struct. Set to false to map to molecule table.
+pub fn serialized_size<T>(value: &T, is_struct: bool) -> Result<usize>
+where
+    T: ?Sized + Serialize,
+{
+    let mut serializer = MoleculeSerializer::new(SizeCounter::default(), is_struct);
+    value.serialize(&mut serializer)?;
+    Ok(serializer.output.size)
+}
+
+/// A sink of molecule bytes, used by [`to_writer`].
+///
+/// With the `std` feature, it's implemented for every `std::io::Write`.
+/// Otherwise, it's implemented for `Vec<u8>`.
+pub trait Write {
+    /// Write the whole buffer into the sink.
+    fn write_all(&mut self, buf: &[u8]) -> Result<()>;
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write + ?Sized> Write for W {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        std::io::Write::write_all(self, buf).map_err(Error::Io)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl Write for Vec<u8> {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        self.extend_from_slice(buf);
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl<W: Write + ?Sized> Write for &mut W {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        (**self).write_all(buf)
+    }
+}
+
+/// The destination of the bytes produced by [`MoleculeSerializer`].
+///
+/// Molecule headers(full size and offsets) are placed before the body, but
+/// their values are only known after the body is serialized. The output
+/// reserves room for them first and gets the values patched in later.
+pub(crate) trait Output {
+    /// Number of bytes produced so far.
+    fn position(&self) -> usize;
+    fn write(&mut self, data: &[u8]) -> Result<()>;
+    /// Reserve `count` header numbers in front of the upcoming body. Returns a
+    /// handle used by `patch`.
+    fn reserve(&mut self, count: usize) -> Result<usize>;
+    /// Set the `index`-th header number reserved by `reserve`.
+    fn patch(&mut self, handle: usize, index: usize, value: u32);
+    /// True if only the number of bytes matters: the header numbers can be
+    /// reserved after the body.
+    const COUNT_ONLY: bool = false;
+}
+
+/// Used by [`serialized_size`]: only the number of bytes is counted.
+#[derive(Default)]
+pub(crate) struct SizeCounter {
+    size: usize,
+}
+
+impl Output for SizeCounter {
+    fn position(&self) -> usize {
+        self.size
+    }
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.size += data.len();
+        Ok(())
+    }
+    fn reserve(&mut self, count: usize) -> Result<usize> {
+        self.size += count * NUMBER_SIZE;
+        Ok(0)
+    }
+    fn patch(&mut self, _handle: usize, _index: usize, _value: u32) {}
+    const COUNT_ONLY: bool = true;
+}
+
+/// Used by [`to_vec`]: everything is written into one buffer, header numbers
+/// are written as zeros and patched in place.
+impl Output for Vec<u8> {
+    fn position(&self) -> usize {
+        self.len()
+    }
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.extend_from_slice(data);
+        Ok(())
+    }
+    fn reserve(&mut self, count: usize) -> Result<usize> {
+        let handle = self.len();
+        self.resize(handle + count * NUMBER_SIZE, 0);
+        Ok(handle)
+    }
+    fn patch(&mut self, handle: usize, index: usize, value: u32) {
+        let start = handle + index * NUMBER_SIZE;
+        self[start..start + NUMBER_SIZE].copy_from_slice(&value.to_le_bytes());
+    }
+}
+
+/// Used by [`to_slice`]: the same as `Vec<u8>`, without growing.
+pub(crate) struct SliceOutput<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl Output for SliceOutput<'_> {
+    fn position(&self) -> usize {
+        self.len
+    }
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        let end = self.len + data.len();
+        self.buf
+            .get_mut(self.len..end)
+            .ok_or(Error::BufferTooSmall)?
+            .copy_from_slice(data);
+        self.len = end;
+        Ok(())
+    }
+    fn reserve(&mut self, count: usize) -> Result<usize> {
+        let handle = self.len;
+        let end = handle + count * NUMBER_SIZE;
+        self.buf
+            .get_mut(handle..end)
+            .ok_or(Error::BufferTooSmall)?
+            .fill(0);
+        self.len = end;
+        Ok(handle)
+    }
+    fn patch(&mut self, handle: usize, index: usize, value: u32) {
+        let start = handle + index * NUMBER_SIZE;
+        self.buf[start..start + NUMBER_SIZE].copy_from_slice(&value.to_le_bytes());
+    }
+}
+
+/// The first pass of [`to_writer`]: count the bytes and record all header
+/// numbers in the order they are reserved.
+#[derive(Default)]
+pub(crate) struct Measure {
+    size: usize,
+    headers: Vec<u32>,
+}
+
+impl Output for Measure {
+    fn position(&self) -> usize {
+        self.size
+    }
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.size += data.len();
+        Ok(())
+    }
+    fn reserve(&mut self, count: usize) -> Result<usize> {
+        let handle = self.headers.len();
+        self.headers.resize(handle + count, 0);
+        self.size += count * NUMBER_SIZE;
+        Ok(handle)
+    }
+    fn patch(&mut self, handle: usize, index: usize, value: u32) {
+        self.headers[handle + index] = value;
+    }
+}
+
+/// The second pass of [`to_writer`]: the headers recorded by [`Measure`] are
+/// replayed in the same order, so everything can be written sequentially.
+pub(crate) struct Emit<'a, W: ?Sized> {
+    writer: &'a mut W,
+    headers: &'a [u32],
+    cursor: usize,
+    size: usize,
+}
+
+impl<'a, W: ?Sized + Write> Emit<'a, W> {
+    pub fn new(writer: &'a mut W, headers: &'a [u32]) -> Self {
+        Emit {
+            writer,
+            headers,
+            cursor: 0,
+            size: 0,
+        }
+    }
+}
+
+impl<W: ?Sized + Write> Output for Emit<'_, W> {
+    fn position(&self) -> usize {
+        self.size
+    }
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.writer.write_all(data)?;
+        self.size += data.len();
+        Ok(())
+    }
+    fn reserve(&mut self, count: usize) -> Result<usize> {
+        let end = self.cursor + count;
+        let headers = self.headers.get(self.cursor..end).ok_or_else(|| {
+            <Error as ser::Error>::custom("value is serialized differently in two passes")
+        })?;
+        for number in headers {
+            self.writer.write_all(&number.to_le_bytes())?;
+        }
+        self.cursor = end;
+        self.size += count * NUMBER_SIZE;
+        Ok(0)
+    }
+    fn patch(&mut self, _handle: usize, _index: usize, _value: u32) {}
+}
+
+const NUMBER_SIZE: usize = 4;
+
+fn is_fallback(name: &str, variant_index: u32) -> bool {
+    union_ids(name).and_then(|(_, ids)| ids.fallback()) == Some(variant_index as usize)
+}
+
+// The union id of a variant: the one given by `#[molecule(union)]`, or the
+// variant index.
+fn union_id(name: &str, variant_index: u32) -> Result<u32> {
+    match union_ids(name) {
+        Some((_, ids)) => ids
+            .get(variant_index as usize)
+            .ok_or_else(|| <Error as ser::Error>::custom("the variant has no union id")),
+        None => Ok(variant_index),
+    }
 }
 
 /// A structure for serializing Rust values into molecule.
-pub(crate) struct MoleculeSerializer {
-    //
-    // The molecule format requires a header before the body. It should output
-    // the body first, then the header. We can't gain any benefit from utilizing the
-    // "Write" trait since it is sequential.
-    data: Vec<u8>,
+pub(crate) struct MoleculeSerializer<O> {
+    output: O,
 
     //
     // true if the rust `struct` is mapping to molecule struct.
     // By default, all rust `struct` is mapping to molecule table.
     is_struct: bool,
+
+    //
+    // true if the next bytes are written as they are, without fixvec header.
+    // It's set by `CollectData`.
+    raw_bytes: bool,
 }
 
-impl MoleculeSerializer {
+impl<O: Output> MoleculeSerializer<O> {
     /// Creates a new molecule serializer.
-    pub fn new(is_struct: bool) -> Self {
+    pub fn new(output: O, is_struct: bool) -> Self {
         MoleculeSerializer {
-            data: vec![],
+            output,
             is_struct,
+            raw_bytes: false,
         }
     }
-}
 
-impl From<MoleculeSerializer> for Vec<u8> {
-    fn from(value: MoleculeSerializer) -> Self {
-        value.data
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.output.write(data)
+    }
+
+    fn serialize_with_mode<T>(&mut self, value: &T, is_struct: bool) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let saved = core::mem::replace(&mut self.is_struct, is_struct);
+        let result = value.serialize(&mut *self);
+        self.is_struct = saved;
+        result
+    }
+
+    /// Start a molecule table or dynvec with `count` fields.
+    fn begin_header(&mut self, count: usize) -> Result<Header> {
+        let start = self.output.position();
+        let handle = self.output.reserve(count + 1)?;
+        Ok(Header { start, handle })
+    }
+
+    /// Record the offset of the `index`-th field, which starts at current position.
+    fn patch_offset(&mut self, header: &Header, index: usize) -> Result<()> {
+        let offset = self.output.position() - header.start;
+        let offset = u32::try_from(offset).map_err(|_| Error::Overflow)?;
+        self.output.patch(header.handle, index + 1, offset);
+        Ok(())
     }
-}
 
-impl MoleculeSerializer {
-    pub fn extend<I: IntoIterator<Item = u8>>(&mut self, iter: I) {
-        self.data.extend(iter);
+    /// Record the full size of the table or dynvec, which ends at current position.
+    fn patch_size(&mut self, header: &Header) -> Result<()> {
+        let size = self.output.position() - header.start;
+        let size = u32::try_from(size).map_err(|_| Error::Overflow)?;
+        self.output.patch(header.handle, 0, size);
+        Ok(())
     }
-    pub fn is_struct(&self) -> bool {
-        self.is_struct
+
+    /// Start a `MapEntry` table and serialize its key.
+    fn begin_entry<T>(&mut self, key: &T) -> Result<Header>
+    where
+        T: ?Sized + Serialize,
+    {
+        let header = self.begin_header(2)?;
+        self.patch_offset(&header, 0)?;
+        self.serialize_with_mode(key, false)?;
+        self.patch_offset(&header, 1)?;
+        Ok(header)
     }
+
+    /// Serialize the value of a `MapEntry` table and finish it.
+    fn end_entry<T>(&mut self, header: &Header, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.serialize_with_mode(value, false)?;
+        self.patch_size(header)
+    }
+}
+
+struct Header {
+    start: usize,
+    handle: usize,
 }
 
-impl<'a> ser::Serializer for &'a mut MoleculeSerializer {
+impl<'a, O: Output> ser::Serializer for &'a mut MoleculeSerializer<O> {
     type Ok = ();
     type Error = Error;
 
-    type SerializeSeq = FixVec<'a>;
-    type SerializeTuple = Tuple<'a>;
-    type SerializeStruct = Table<'a>;
-    type SerializeMap = Map<'a>;
-    type SerializeStructVariant = Variant<'a>;
-    type SerializeTupleVariant = Variant<'a>;
-    type SerializeTupleStruct = Table<'a>;
+    type SerializeSeq = FixVec<'a, O>;
+    type SerializeTuple = Tuple<'a, O>;
+    type SerializeStruct = Table<'a, O>;
+    type SerializeMap = Map<'a, O>;
+    type SerializeStructVariant = Table<'a, O>;
+    type SerializeTupleVariant = Table<'a, O>;
+    type SerializeTupleStruct = Table<'a, O>;
 
     fn serialize_bool(self, value: bool) -> Result<()> {
         let value = match value {
             true => 1u8,
             false => 0u8,
         };
-        self.data.push(value);
-        Ok(())
+        self.write(&[value])
     }
 
     fn serialize_i8(self, value: i8) -> Result<()> {
-        let value = u8::from_le_bytes(value.to_le_bytes());
-        self.data.push(value);
-        Ok(())
+        self.write(&value.to_le_bytes())
     }
 
     fn serialize_i16(self, value: i16) -> Result<()> {
-        let value = value.to_le_bytes();
-        self.data.extend(value);
-        Ok(())
+        self.write(&value.to_le_bytes())
     }
 
     fn serialize_i32(self, value: i32) -> Result<()> {
-        let value = value.to_le_bytes();
-        self.data.extend(value);
-        Ok(())
+        self.write(&value.to_le_bytes())
     }
 
     fn serialize_i64(self, value: i64) -> Result<()> {
-        let value = value.to_le_bytes();
-        self.data.extend(value);
-        Ok(())
+        self.write(&value.to_le_bytes())
     }
 
     fn serialize_i128(self, value: i128) -> Result<()> {
-        let value = value.to_le_bytes();
-        self.data.extend(value);
-        Ok(())
+        self.write(&value.to_le_bytes())
     }
 
     fn serialize_u8(self, value: u8) -> Result<()> {
-        self.data.push(value);
-        Ok(())
+        self.write(&[value])
     }
 
     fn serialize_u16(self, value: u16) -> Result<()> {
-        let value = value.to_le_bytes();
-        self.data.extend(value);
-        Ok(())
+        self.write(&value.to_le_bytes())
     }
 
     fn serialize_u32(self, value: u32) -> Result<()> {
-        let value = value.to_le_bytes();
-        self.data.extend(value);
-        Ok(())
+        self.write(&value.to_le_bytes())
     }
 
     fn serialize_u64(self, value: u64) -> Result<()> {
-        let value = value.to_le_bytes();
-        self.data.extend(value);
-        Ok(())
+        self.write(&value.to_le_bytes())
     }
 
     fn serialize_u128(self, value: u128) -> Result<()> {
-        let value = value.to_le_bytes();
-        self.data.extend(value);
-        Ok(())
+        self.write(&value.to_le_bytes())
     }
 
     fn serialize_f32(self, value: f32) -> Result<()> {
-        let value = value.to_le_bytes();
-        self.data.extend(value);
-        Ok(())
+        self.write(&value.to_le_bytes())
     }
 
     fn serialize_f64(self, value: f64) -> Result<()> {
-        let value = value.to_le_bytes();
-        self.data.extend(value);
-        Ok(())
+        self.write(&value.to_le_bytes())
     }
 
     fn serialize_char(self, value: char) -> Result<()> {
         let value: u32 = value.into();
-        self.data.extend(value.to_le_bytes());
-        Ok(())
+        self.write(&value.to_le_bytes())
     }
 
     fn serialize_str(self, value: &str) -> Result<()> {
-        let len = value.len() as u32;
-        self.data.extend(len.to_le_bytes());
-        self.data.extend(value.as_bytes());
-        Ok(())
+        let len = u32::try_from(value.len()).map_err(|_| Error::Overflow)?;
+        self.write(&len.to_le_bytes())?;
+        self.write(value.as_bytes())
     }
 
     fn serialize_bytes(self, value: &[u8]) -> Result<()> {
-        self.data.extend(value);
-        Ok(())
+        if core::mem::take(&mut self.raw_bytes) {
+            return self.write(value);
+        }
+        // same as `Vec<u8>` and `&[u8]`: fixvec of byte
+        if self.is_struct {
+            return Err(Error::MixTableAndStruct);
+        }
+        let len = u32::try_from(value.len()).map_err(|_| Error::Overflow)?;
+        self.write(&len.to_le_bytes())?;
+        self.write(value)
     }
 
     fn serialize_unit(self) -> Result<()> {
@@ -177,24 +490,36 @@ impl<'a> ser::Serializer for &'a mut MoleculeSerializer {
 
     fn serialize_unit_variant(
         self,
-        _name: &'static str,
+        name: &'static str,
         variant_index: u32,
         _variant: &'static str,
     ) -> Result<()> {
-        self.serialize_u32(variant_index)
+        self.serialize_u32(union_id(name, variant_index)?)
     }
 
     /// Serialize newtypes without an object wrapper.
-    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<()>
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(self)
+        if name == STRUCT_STR || struct_name(name).is_some() {
+            // from `struct_serde` or `#[molecule(struct)]`: the inner value is
+            // a molecule struct.
+            self.serialize_with_mode(value, true)
+        } else if name == RAW_STR {
+            // from `CollectData`: the inner bytes are already encoded.
+            self.raw_bytes = true;
+            let result = value.serialize(&mut *self);
+            self.raw_bytes = false;
+            result
+        } else {
+            value.serialize(self)
+        }
     }
 
     fn serialize_newtype_variant<T>(
         self,
-        _name: &'static str,
+        name: &'static str,
         variant_index: u32,
         _variant: &'static str,
         value: &T,
@@ -202,7 +527,7 @@ impl<'a> ser::Serializer for &'a mut MoleculeSerializer {
     where
         T: ?Sized + Serialize,
     {
-        self.serialize_u32(variant_index)?;
+        self.serialize_u32(union_id(name, variant_index)?)?;
         value.serialize(self)
     }
 
@@ -218,10 +543,12 @@ impl<'a> ser::Serializer for &'a mut MoleculeSerializer {
     }
 
     fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
-        if self.is_struct {
+        // from the body of fallback variant: the elements are written as they are.
+        let raw = core::mem::take(&mut self.raw_bytes);
+        if self.is_struct && !raw {
             return Err(Error::MixTableAndStruct);
         }
-        Ok(FixVec::new(self))
+        FixVec::new(self, raw)
     }
 
     fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
@@ -230,57 +557,79 @@ impl<'a> ser::Serializer for &'a mut MoleculeSerializer {
 
     fn serialize_tuple_struct(
         self,
-        _name: &'static str,
+        name: &'static str,
         len: usize,
     ) -> Result<Self::SerializeTupleStruct> {
-        Ok(Table::new(self, len, false))
+        Table::new(self, len, struct_name(name).is_some())
     }
 
     fn serialize_tuple_variant(
         self,
-        _name: &'static str,
+        name: &'static str,
         variant_index: u32,
         _variant: &'static str,
         len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
-        Ok(Variant::new(self, len, false, variant_index))
+        if is_fallback(name, variant_index) {
+            return Table::fallback(self, len);
+        }
+        self.serialize_u32(union_id(name, variant_index)?)?;
+        Table::new(self, len, false)
     }
 
-    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
         if self.is_struct {
             return Err(Error::MixTableAndStruct);
         }
-        Ok(Map::new(self))
+        Map::new(self, len)
     }
 
-    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
+    fn serialize_struct(self, name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
         // In molecule struct, the inner fields must be molecule struct.
-        Ok(Table::new(self, len, self.is_struct()))
+        let is_struct = self.is_struct || struct_name(name).is_some();
+        Table::new(self, len, is_struct)
     }
 
     fn serialize_struct_variant(
         self,
-        _name: &'static str,
+        name: &'static str,
         variant_index: u32,
         _variant: &'static str,
         len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        Ok(Variant::new(self, len, false, variant_index))
+        if is_fallback(name, variant_index) {
+            return Table::fallback(self, len);
+        }
+        self.serialize_u32(union_id(name, variant_index)?)?;
+        Table::new(self, len, false)
     }
 }
 
-pub(crate) struct FixVec<'a> {
-    ser: &'a mut MoleculeSerializer,
-    parts: Vec<Vec<u8>>,
+pub(crate) struct FixVec<'a, O> {
+    ser: &'a mut MoleculeSerializer<O>,
+    // `None` if the item count isn't written
+    handle: Option<usize>,
+    count: usize,
+    item_size: usize,
 }
 
-impl<'a> FixVec<'a> {
-    pub fn new(ser: &'a mut MoleculeSerializer) -> Self {
-        FixVec { ser, parts: vec![] }
+impl<'a, O: Output> FixVec<'a, O> {
+    pub fn new(ser: &'a mut MoleculeSerializer<O>, raw: bool) -> Result<Self> {
+        let handle = if raw {
+            None
+        } else {
+            Some(ser.output.reserve(1)?)
+        };
+        Ok(FixVec {
+            ser,
+            handle,
+            count: 0,
+            item_size: 0,
+        })
     }
 }
 
-impl ser::SerializeSeq for FixVec<'_> {
+impl<O: Output> ser::SerializeSeq for FixVec<'_, O> {
     type Ok = ();
     type Error = Error;
 
@@ -288,29 +637,42 @@ impl ser::SerializeSeq for FixVec<'_> {
     where
         T: ?Sized + Serialize,
     {
-        self.parts.push(to_vec(value, true)?);
+        // elements in fixvec should be fixed(struct, primitive type, etc)
+        let start = self.ser.output.position();
+        self.ser.serialize_with_mode(value, true)?;
+        let item_size = self.ser.output.position() - start;
+        if self.count > 0 && item_size != self.item_size {
+            return Err(Error::AssembleFixvec);
+        }
+        self.item_size = item_size;
+        self.count += 1;
         Ok(())
     }
 
     fn end(self) -> Result<()> {
-        self.ser.extend(assemble_fixvec(&self.parts)?);
+        if self.count > 0 && self.item_size == 0 {
+            return Err(Error::AssembleFixvec);
+        }
+        if let Some(handle) = self.handle {
+            let count = u32::try_from(self.count).map_err(|_| Error::Overflow)?;
+            self.ser.output.patch(handle, 0, count);
+        }
         Ok(())
     }
 }
 
 // this tuple is used in serialization of [T; N]
-pub(crate) struct Tuple<'a> {
-    ser: &'a mut MoleculeSerializer,
-    data: Vec<u8>,
+pub(crate) struct Tuple<'a, O> {
+    ser: &'a mut MoleculeSerializer<O>,
 }
 
-impl<'a> Tuple<'a> {
-    pub fn new(ser: &'a mut MoleculeSerializer) -> Self {
-        Self { ser, data: vec![] }
+impl<'a, O: Output> Tuple<'a, O> {
+    pub fn new(ser: &'a mut MoleculeSerializer<O>) -> Self {
+        Self { ser }
     }
 }
 
-impl ser::SerializeTuple for Tuple<'_> {
+impl<O: Output> ser::SerializeTuple for Tuple<'_, O> {
     type Ok = ();
     type Error = Error;
 
@@ -318,35 +680,56 @@ impl ser::SerializeTuple for Tuple<'_> {
     where
         T: ?Sized + Serialize,
     {
-        let data = to_vec(value, true)?;
-        self.data.extend(data);
-        Ok(())
+        self.ser.serialize_with_mode(value, true)
     }
     fn end(self) -> Result<()> {
-        self.ser.extend(self.data);
         Ok(())
     }
 }
 
-pub(crate) struct Table<'a> {
-    ser: &'a mut MoleculeSerializer,
-    parts: Vec<Vec<u8>>,
+/// Molecule table, or molecule struct when `is_struct` is true. It's also
+/// used for dynvec, tuple struct and the body of struct/tuple variant.
+pub(crate) struct Table<'a, O> {
+    ser: &'a mut MoleculeSerializer<O>,
+    // `None` for molecule struct, which has no header
+    header: Option<Header>,
+    index: usize,
     count: usize,
     is_struct: bool,
+    // the fallback variant of `#[molecule(union)]`
+    fallback: bool,
 }
 
-impl<'a> Table<'a> {
-    pub fn new(ser: &'a mut MoleculeSerializer, count: usize, is_struct: bool) -> Self {
-        Table {
+impl<'a, O: Output> Table<'a, O> {
+    pub fn new(ser: &'a mut MoleculeSerializer<O>, count: usize, is_struct: bool) -> Result<Self> {
+        let header = if is_struct {
+            None
+        } else {
+            Some(ser.begin_header(count)?)
+        };
+        Ok(Table {
             ser,
-            parts: vec![],
+            header,
+            index: 0,
             count,
             is_struct,
+            fallback: false,
+        })
+    }
+
+    /// The fallback variant of `#[molecule(union)]` has two fields: the union
+    /// id and the body, which is written as it is.
+    pub fn fallback(ser: &'a mut MoleculeSerializer<O>, count: usize) -> Result<Self> {
+        if count != 2 {
+            return Err(Error::InvalidTableCount);
         }
+        let mut table = Table::new(ser, count, true)?;
+        table.fallback = true;
+        Ok(table)
     }
 }
 
-impl ser::SerializeStruct for Table<'_> {
+impl<O: Output> ser::SerializeStruct for Table<'_, O> {
     type Ok = ();
     type Error = Error;
 
@@ -354,26 +737,34 @@ impl ser::SerializeStruct for Table<'_> {
     where
         T: ?Sized + Serialize,
     {
-        self.parts.push(to_vec(value, self.is_struct)?);
-        Ok(())
+        if self.index >= self.count {
+            return Err(Error::InvalidTableCount);
+        }
+        if let Some(header) = &self.header {
+            self.ser.patch_offset(header, self.index)?;
+        }
+        self.index += 1;
+        if self.fallback && self.index == 2 {
+            self.ser.raw_bytes = true;
+            let result = self.ser.serialize_with_mode(value, true);
+            self.ser.raw_bytes = false;
+            return result;
+        }
+        self.ser.serialize_with_mode(value, self.is_struct)
     }
 
     fn end(self) -> Result<()> {
-        if self.parts.len() != self.count {
+        if self.index != self.count {
             return Err(Error::InvalidTableCount);
         }
-        if self.is_struct {
-            let data = assemble_struct(self.parts);
-            self.ser.extend(data);
-        } else {
-            let data = assemble_table(&self.parts);
-            self.ser.extend(data);
+        if let Some(header) = &self.header {
+            self.ser.patch_size(header)?;
         }
         Ok(())
     }
 }
 
-impl ser::SerializeTupleStruct for Table<'_> {
+impl<O: Output> ser::SerializeTupleStruct for Table<'_, O> {
     type Ok = ();
     type Error = Error;
 
@@ -388,115 +779,144 @@ impl ser::SerializeTupleStruct for Table<'_> {
     }
 }
 
-pub(crate) struct Map<'a> {
-    ser: &'a mut MoleculeSerializer,
-    parts: Vec<Vec<u8>>,
-    temp_key: Vec<u8>,
-}
-
-impl<'a> Map<'a> {
-    pub fn new(ser: &'a mut MoleculeSerializer) -> Self {
-        Self {
-            ser,
-            parts: vec![],
-            temp_key: vec![],
-        }
-    }
-}
-
-impl ser::SerializeMap for Map<'_> {
+impl<O: Output> ser::SerializeStructVariant for Table<'_, O> {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, _key: &'static str, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
-        let mut ser = MoleculeSerializer::new(false);
-        let result = key.serialize(&mut ser);
-        self.temp_key = ser.into();
-        result
+        ser::SerializeStruct::serialize_field(self, "", value)
     }
 
-    fn serialize_value<T>(&mut self, value: &T) -> Result<()>
+    fn end(self) -> Result<()> {
+        ser::SerializeStruct::end(self)
+    }
+}
+
+impl<O: Output> ser::SerializeTupleVariant for Table<'_, O> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
-        let mut ser = MoleculeSerializer::new(false);
-        let result = value.serialize(&mut ser);
-        let parts = vec![self.temp_key.clone(), ser.into()];
-        self.parts.push(assemble_table(&parts));
-        result
+        ser::SerializeStruct::serialize_field(self, "", value)
     }
-
     fn end(self) -> Result<()> {
-        self.ser.extend(assemble_table(&self.parts));
-        Ok(())
+        ser::SerializeStruct::end(self)
     }
 }
 
-pub(crate) struct Variant<'a> {
-    ser: &'a mut MoleculeSerializer,
-    parts: Vec<Vec<u8>>,
-    count: usize,
-    is_struct: bool,
-    variant_index: u32,
+/// A map is a dynvec of `MapEntry` tables, each with two fields: key and value.
+pub(crate) struct Map<'a, O> {
+    ser: &'a mut MoleculeSerializer<O>,
+    state: MapState,
+    entry: Option<Header>,
+    index: usize,
 }
 
-impl<'a> Variant<'a> {
-    pub fn new(
-        ser: &'a mut MoleculeSerializer,
+enum MapState {
+    // The number of entries is known: the header is reserved and patched.
+    Direct {
+        header: Header,
         count: usize,
-        is_struct: bool,
-        variant_index: u32,
-    ) -> Self {
-        Variant {
+    },
+    // The number of entries is unknown, but only the size is counted: the
+    // header is reserved in `end`.
+    Counted,
+    // The number of entries is unknown: the entries are serialized into a
+    // separate buffer and written after the header in `end`.
+    Buffered {
+        entries: MoleculeSerializer<Vec<u8>>,
+        offsets: Vec<usize>,
+    },
+}
+
+impl<'a, O: Output> Map<'a, O> {
+    pub fn new(ser: &'a mut MoleculeSerializer<O>, len: Option<usize>) -> Result<Self> {
+        let state = match len {
+            Some(count) => MapState::Direct {
+                header: ser.begin_header(count)?,
+                count,
+            },
+            None if O::COUNT_ONLY => MapState::Counted,
+            None => MapState::Buffered {
+                entries: MoleculeSerializer::new(vec![], false),
+                offsets: vec![],
+            },
+        };
+        Ok(Self {
             ser,
-            parts: vec![],
-            count,
-            is_struct,
-            variant_index,
-        }
+            state,
+            entry: None,
+            index: 0,
+        })
     }
 }
 
-impl ser::SerializeStructVariant for Variant<'_> {
+impl<O: Output> ser::SerializeMap for Map<'_, O> {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T>(&mut self, _key: &'static str, value: &T) -> Result<()>
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
-        self.parts.push(to_vec(value, self.is_struct)?);
-        Ok(())
-    }
-
-    fn end(self) -> Result<()> {
-        if self.parts.len() != self.count {
-            return Err(Error::InvalidTableCount);
-        }
-        if self.is_struct {
-            return Err(Error::Unimplemented);
-        }
-        let data = assemble_table(&self.parts);
-        self.ser.extend(self.variant_index.to_le_bytes());
-        self.ser.extend(data);
+        let entry = match &mut self.state {
+            MapState::Direct { header, count } => {
+                if self.index >= *count {
+                    return Err(Error::InvalidMap);
+                }
+                self.ser.patch_offset(header, self.index)?;
+                self.ser.begin_entry(key)?
+            }
+            MapState::Counted => self.ser.begin_entry(key)?,
+            MapState::Buffered { entries, offsets } => {
+                offsets.push(entries.output.len());
+                entries.begin_entry(key)?
+            }
+        };
+        self.entry = Some(entry);
         Ok(())
     }
-}
-
-impl ser::SerializeTupleVariant for Variant<'_> {
-    type Ok = ();
-    type Error = Error;
 
-    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    fn serialize_value<T>(&mut self, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
-        ser::SerializeStructVariant::serialize_field(self, "", value)
+        let entry = self.entry.take().ok_or(Error::InvalidMap)?;
+        match &mut self.state {
+            MapState::Direct { .. } | MapState::Counted => self.ser.end_entry(&entry, value)?,
+            MapState::Buffered { entries, .. } => entries.end_entry(&entry, value)?,
+        }
+        self.index += 1;
+        Ok(())
     }
+
     fn end(self) -> Result<()> {
-        ser::SerializeStructVariant::end(self)
+        match self.state {
+            MapState::Direct { header, count } => {
+                if self.index != count {
+                    return Err(Error::InvalidMap);
+                }
+                self.ser.patch_size(&header)
+            }
+            MapState::Counted => self.ser.output.reserve(self.index + 1).map(|_| ()),
+            MapState::Buffered { entries, offsets } => {
+                let header_size = (offsets.len() + 1) * NUMBER_SIZE;
+                let entries = entries.output;
+                let total = header_size + entries.len();
+                let numbers =
+                    core::iter::once(total).chain(offsets.iter().map(|o| o + header_size));
+                for number in numbers {
+                    let number = u32::try_from(number).map_err(|_| Error::Overflow)?;
+                    self.ser.write(&number.to_le_bytes())?;
+                }
+                self.ser.write(&entries)
+            }
+        }
     }
 }
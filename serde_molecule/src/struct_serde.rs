@@ -1,14 +1,14 @@
 use core::fmt;
+use core::marker::PhantomData;
 
-use crate::error::Error;
+use crate::de::{RAW_STR, STRUCT_STR};
+use crate::error::{Error, Segment};
 use crate::molecule::{MOLECULE_DE, MOLECULE_SER};
-use crate::ser::to_vec;
 use alloc::vec::Vec;
-use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use serde::{
     de::{self, value::U64Deserializer, DeserializeSeed, MapAccess, SeqAccess, Visitor},
-    ser, Deserializer, Serialize, Serializer,
+    Deserializer, Serialize, Serializer,
 };
 
 pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
@@ -17,8 +17,8 @@ where
     T: Serialize,
 {
     if core::any::type_name::<S>().contains(MOLECULE_SER) {
-        let data = to_vec(value, true).map_err(ser::Error::custom)?;
-        serializer.serialize_bytes(&data)
+        // The serializer switches to molecule struct for the inner value.
+        serializer.serialize_newtype_struct(STRUCT_STR, value)
     } else {
         value.serialize(serializer) // Use default serialization for others, e.g. serde_json
     }
@@ -27,37 +27,72 @@ where
 pub fn deserialize<'de, D, T>(deserializer: D) -> Result<T, D::Error>
 where
     D: Deserializer<'de>,
-    T: DeserializeOwned,
+    T: Deserialize<'de>,
 {
     if core::any::type_name::<D>().contains(MOLECULE_DE) {
-        // This is a tricky approach: use this method to indicate that it is the top
-        // level of the Molecule struct. When the inner `deserialize` is invoked, it
-        // does not create a new instance. This ensures that the `index` status
-        // propagates across the calling functions. When it is MoleculeDeserializer,
-        // it defaults to true, which is exactly what we want.
-        if deserializer.is_human_readable() {
-            let data = CollectData::deserialize(deserializer)?.data;
-            let mut de = MoleculeStructDeserializer::new(data);
-            T::deserialize(&mut de).map_err(de::Error::custom)
-        } else {
-            T::deserialize(deserializer)
-        }
+        // The deserializer switches to `MoleculeStructDeserializer` over the
+        // same data, nothing is copied.
+        deserializer.deserialize_newtype_struct(
+            STRUCT_STR,
+            StructVisitor {
+                marker: PhantomData,
+            },
+        )
     } else {
         T::deserialize(deserializer)
     }
 }
 
+struct StructVisitor<T> {
+    marker: PhantomData<T>,
+}
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for StructVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a molecule struct")
+    }
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        T::deserialize(deserializer)
+    }
+}
+
+/// The undecoded molecule data of a value. It's used to implement
+/// `Serialize`/`Deserialize` by hand: deserializing collects the whole data,
+/// serializing writes the data as it is.
 pub struct CollectData {
     pub data: Vec<u8>,
 }
 
+impl Serialize for CollectData {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        struct RawBytes<'a>(&'a [u8]);
+        impl Serialize for RawBytes<'_> {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                serializer.serialize_bytes(self.0)
+            }
+        }
+        serializer.serialize_newtype_struct(RAW_STR, &RawBytes(&self.data))
+    }
+}
+
 impl<'de> Deserialize<'de> for CollectData {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: Deserializer<'de>,
     {
         struct _Visitor;
-        impl Visitor<'_> for _Visitor {
+        impl<'de> Visitor<'de> for _Visitor {
             type Value = CollectData;
             fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
                 formatter.write_str("a CollectData")
@@ -70,21 +105,61 @@ impl<'de> Deserialize<'de> for CollectData {
                     data: slice.to_vec(),
                 })
             }
+            fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                deserializer.deserialize_bytes(self)
+            }
         }
         let visitor = _Visitor;
-        deserializer.deserialize_bytes(visitor)
+        deserializer.deserialize_newtype_struct(RAW_STR, visitor)
     }
 }
 
 /// A structure that deserializes molecule struct into Rust values.
-pub(crate) struct MoleculeStructDeserializer {
-    data: Vec<u8>,
+pub(crate) struct MoleculeStructDeserializer<'de> {
+    data: &'de [u8],
     index: usize,
+    // the position of `data` in the whole input
+    offset: usize,
+    canonical: bool,
+    // The data is the whole value of a `struct_serde` field, which can be an
+    // `Option`: empty for `None`. Cleared once a field is deserialized.
+    pub(crate) whole: bool,
 }
 
-impl MoleculeStructDeserializer {
-    pub fn new(data: Vec<u8>) -> Self {
-        MoleculeStructDeserializer { data, index: 0 }
+impl<'de> MoleculeStructDeserializer<'de> {
+    pub fn new(data: &'de [u8], offset: usize, canonical: bool) -> Self {
+        MoleculeStructDeserializer {
+            data,
+            index: 0,
+            offset,
+            canonical,
+            whole: false,
+        }
+    }
+
+    // Deserialize the next field, errors are located by `segment`.
+    fn deserialize_field<T>(&mut self, segment: Segment, seed: T) -> Result<T::Value, Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        let offset = self.offset + self.index;
+        self.whole = false;
+        seed.deserialize(&mut *self)
+            .map_err(|e| e.at(offset, segment))
+    }
+
+    /// In canonical mode, all bytes of the struct must be consumed.
+    pub fn end(&self) -> Result<(), Error> {
+        if self.canonical && self.index != self.data.len() {
+            Err(Error::NonCanonical {
+                offset: self.offset + self.index,
+            })
+        } else {
+            Ok(())
+        }
     }
 }
 
@@ -92,7 +167,10 @@ macro_rules! read_primitive {
     ($method:ident, $type: ty, $len: expr) => {
         fn $method(&mut self) -> Result<$type, Error> {
             if self.data.len() < (self.index + $len) {
-                return Err(Error::MismatchedLength);
+                return Err(Error::MismatchedLength {
+                    expected: $len,
+                    actual: self.data.len() - self.index,
+                });
             } else {
                 let bytes: [u8; $len] = (&self.data[self.index..self.index + $len])
                     .try_into()
@@ -104,7 +182,7 @@ macro_rules! read_primitive {
     };
 }
 
-impl MoleculeStructDeserializer {
+impl MoleculeStructDeserializer<'_> {
     read_primitive!(read_u8, u8, 1);
     read_primitive!(read_u16, u16, 2);
     read_primitive!(read_u32, u32, 4);
@@ -119,12 +197,9 @@ impl MoleculeStructDeserializer {
     read_primitive!(read_f64, f64, 8);
 }
 
-impl<'de> Deserializer<'de> for &mut MoleculeStructDeserializer {
+impl<'de> Deserializer<'de> for &mut MoleculeStructDeserializer<'de> {
     type Error = Error;
 
-    fn is_human_readable(&self) -> bool {
-        false
-    }
     fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
@@ -136,10 +211,13 @@ impl<'de> Deserializer<'de> for &mut MoleculeStructDeserializer {
     where
         V: Visitor<'de>,
     {
-        if self.read_u8()? == 0 {
-            visitor.visit_bool(false)
-        } else {
-            visitor.visit_bool(true)
+        match self.read_u8()? {
+            0 => visitor.visit_bool(false),
+            1 => visitor.visit_bool(true),
+            _ if self.canonical => Err(Error::NonCanonical {
+                offset: self.offset + self.index - 1,
+            }),
+            _ => visitor.visit_bool(true),
         }
     }
     fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
@@ -237,11 +315,11 @@ impl<'de> Deserializer<'de> for &mut MoleculeStructDeserializer {
         Err(Error::InvalidStructField)
     }
 
-    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_bytes<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_bytes(&self.data[self.index..])
+        Err(Error::InvalidStructField)
     }
     fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
@@ -250,11 +328,19 @@ impl<'de> Deserializer<'de> for &mut MoleculeStructDeserializer {
         self.deserialize_bytes(visitor)
     }
 
-    fn deserialize_option<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        Err(Error::InvalidStructField)
+        if !self.whole {
+            return Err(Error::InvalidStructField);
+        }
+        self.whole = false;
+        if self.data.is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
     }
 
     fn deserialize_unit<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
@@ -276,11 +362,18 @@ impl<'de> Deserializer<'de> for &mut MoleculeStructDeserializer {
     }
 
     /// Parses a newtype struct as the underlying value.
-    fn deserialize_newtype_struct<V>(self, _name: &str, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_newtype_struct<V>(self, name: &str, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_newtype_struct(self)
+        if name == RAW_STR {
+            // from `CollectData`: the remaining data is returned without decoding.
+            let data = &self.data[self.index..];
+            self.index = self.data.len();
+            visitor.visit_borrowed_bytes(data)
+        } else {
+            visitor.visit_newtype_struct(self)
+        }
     }
 
     fn deserialize_seq<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
@@ -326,7 +419,7 @@ impl<'de> Deserializer<'de> for &mut MoleculeStructDeserializer {
     where
         V: Visitor<'de>,
     {
-        visitor.visit_map(StructAccess::new(self, fields.len()))
+        visitor.visit_map(StructAccess::new(self, fields))
     }
     fn deserialize_enum<V>(
         self,
@@ -355,30 +448,30 @@ impl<'de> Deserializer<'de> for &mut MoleculeStructDeserializer {
     }
 }
 
-struct StructAccess<'a> {
-    de: &'a mut MoleculeStructDeserializer,
+struct StructAccess<'de, 'a> {
+    de: &'a mut MoleculeStructDeserializer<'de>,
     current_index: usize,
-    count: usize,
+    fields: &'static [&'static str],
 }
 
-impl<'a> StructAccess<'a> {
-    fn new(de: &'a mut MoleculeStructDeserializer, count: usize) -> Self {
+impl<'de, 'a> StructAccess<'de, 'a> {
+    fn new(de: &'a mut MoleculeStructDeserializer<'de>, fields: &'static [&'static str]) -> Self {
         StructAccess {
             de,
             current_index: 0,
-            count,
+            fields,
         }
     }
 }
 
-impl<'de> MapAccess<'de> for StructAccess<'_> {
+impl<'de> MapAccess<'de> for StructAccess<'de, '_> {
     type Error = Error;
 
     fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
     where
         K: DeserializeSeed<'de>,
     {
-        if self.current_index < self.count {
+        if self.current_index < self.fields.len() {
             let de = U64Deserializer::<Error>::new(self.current_index as u64);
             Ok(Some(seed.deserialize(de)?))
         } else {
@@ -390,20 +483,21 @@ impl<'de> MapAccess<'de> for StructAccess<'_> {
     where
         V: DeserializeSeed<'de>,
     {
-        assert!(self.current_index < self.count);
+        assert!(self.current_index < self.fields.len());
+        let segment = Segment::Field(self.fields[self.current_index]);
         self.current_index += 1;
-        seed.deserialize(&mut *self.de)
+        self.de.deserialize_field(segment, seed)
     }
 }
 
-struct ArrayAccess<'a> {
-    de: &'a mut MoleculeStructDeserializer,
+struct ArrayAccess<'de, 'a> {
+    de: &'a mut MoleculeStructDeserializer<'de>,
     current_index: usize,
     count: usize,
 }
 
-impl<'a> ArrayAccess<'a> {
-    pub fn new(de: &'a mut MoleculeStructDeserializer, count: usize) -> Self {
+impl<'de, 'a> ArrayAccess<'de, 'a> {
+    pub fn new(de: &'a mut MoleculeStructDeserializer<'de>, count: usize) -> Self {
         ArrayAccess {
             de,
             current_index: 0,
@@ -412,7 +506,7 @@ impl<'a> ArrayAccess<'a> {
     }
 }
 
-impl<'de> SeqAccess<'de> for ArrayAccess<'_> {
+impl<'de> SeqAccess<'de> for ArrayAccess<'de, '_> {
     type Error = Error;
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
@@ -420,8 +514,9 @@ impl<'de> SeqAccess<'de> for ArrayAccess<'_> {
         T: DeserializeSeed<'de>,
     {
         if self.current_index < self.count {
+            let segment = Segment::Index(self.current_index);
             self.current_index += 1;
-            let value = seed.deserialize(&mut *self.de)?;
+            let value = self.de.deserialize_field(segment, seed)?;
             Ok(Some(value))
         } else {
             Ok(None)
@@ -1,4 +1,8 @@
-use crate::molecule::{assemble_fixvec, assemble_table, disassemble_fixvec, disassemble_table};
+use crate::error::Error;
+use crate::molecule::{
+    assemble_fixvec, assemble_table, disassemble_fixvec, disassemble_table, table_item,
+    verify_table,
+};
 
 #[test]
 fn test_disassemble_fixvec() {
@@ -141,3 +145,22 @@ fn test_assemble_table() {
     test_table(&[vec![1], vec![2], vec![3]]);
     test_table(&[vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 9]]);
 }
+
+#[test]
+fn test_table_item() {
+    let data = assemble_table(&[vec![1], vec![2, 3]]);
+    assert_eq!(table_item(&data, 1).unwrap(), &[2, 3]);
+    assert!(matches!(table_item(&data, 2), Err(Error::InvalidTable)));
+
+    // one field, the bytes after the header look like offsets
+    let data = [16u8, 0, 0, 0, 8, 0, 0, 0, 10, 0, 0, 0, 5, 0, 0, 0];
+    assert_eq!(verify_table(&data).unwrap(), 1);
+    assert_eq!(table_item(&data, 0).unwrap().len(), 8);
+    assert!(matches!(table_item(&data, 1), Err(Error::InvalidTable)));
+
+    // the offsets aren't validated
+    let data = [16u8, 0, 0, 0, 12, 0, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0];
+    assert!(matches!(table_item(&data, 0), Err(Error::InvalidTable)));
+    let data = [12u8, 0, 0, 0, 12, 0, 0, 0, 20, 0, 0, 0];
+    assert!(matches!(table_item(&data, 0), Err(Error::InvalidTable)));
+}
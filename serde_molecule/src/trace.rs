@@ -0,0 +1,799 @@
+//! Trace the molecule layout of a Rust type.
+//!
+//! The layout is found by running the `Deserialize` implementation of the type
+//! against a tracer instead of real data. The tracer returns placeholder
+//! values(0, empty string, `Some`, one element for vectors and maps) and
+//! records what is asked for. Enums are traced again until every variant is
+//! seen.
+//!
+//! ```
+//! use serde_molecule::trace::{ContainerFormat, Format, Layout};
+//!
+//! #[derive(serde::Deserialize)]
+//! struct Script {
+//!     code_hash: [u8; 2],
+//!     args: Vec<u8>,
+//! }
+//!
+//! let layout = Layout::of::<Script>().unwrap();
+//! assert_eq!(layout.root, Format::TypeName("Script"));
+//! assert_eq!(
+//!     layout.registry["Script"],
+//!     ContainerFormat::Struct(vec![
+//!         ("code_hash", Format::Tuple(vec![Format::U8, Format::U8])),
+//!         ("args", Format::Fixvec(Box::new(Format::U8))),
+//!     ])
+//! );
+//! ```
+//!
+//! Limitations:
+//! * Recursive types can't be traced, molecule has no recursive types either.
+//! * Different types with the same name, e.g. instances of a generic struct,
+//!   must have the same layout.
+//! * `Deserialize` implementations which reject the placeholder values, e.g.
+//!   `NonZeroU32`, can't be traced.
+
+use crate::de::{struct_name, union_ids, DYNVEC_STR, RAW_STR, STRUCT_STR};
+use crate::error::{Error, Result};
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+use serde::de::{self, value::U64Deserializer, DeserializeSeed, Error as _};
+
+/// The layout of a value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Format {
+    Unit,
+    Bool,
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+    I8,
+    I16,
+    I32,
+    I64,
+    I128,
+    F32,
+    F64,
+    Char,
+    /// `String` or `&str`: fixvec of UTF-8 bytes.
+    Str,
+    /// Bytes from `deserialize_bytes`, e.g. `&[u8]`: fixvec of bytes.
+    Bytes,
+    /// `Option<T>`: empty for `None`.
+    Option(Box<Format>),
+    /// `Vec<T>`: fixvec, the elements are molecule structs.
+    Fixvec(Box<Format>),
+    /// `Vec<T>` with `dynvec_serde`: dynvec.
+    Dynvec(Box<Format>),
+    /// `BTreeMap<K, V>`: dynvec of tables with key and value.
+    Map(Box<Format>, Box<Format>),
+    /// Tuple or array.
+    Tuple(Vec<Format>),
+    /// A field with `struct_serde`, or a type marked by `#[molecule(struct)]`:
+    /// the value is a molecule struct.
+    Struct(Box<Format>),
+    /// `CollectData`: the bytes aren't decoded.
+    Raw,
+    /// A named struct or enum in the [`Registry`].
+    TypeName(&'static str),
+}
+
+/// The layout of a named struct or enum.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ContainerFormat {
+    UnitStruct,
+    NewtypeStruct(Box<Format>),
+    /// Tuple struct: table.
+    TupleStruct(Vec<Format>),
+    /// Struct with named fields: table.
+    Struct(Vec<(&'static str, Format)>),
+    /// Enum: union, the union ids are the variant indexes.
+    Enum(Vec<(&'static str, VariantFormat)>),
+    /// Enum marked by `#[molecule(union)]`: union with the given union ids.
+    /// The fallback variant, if any, takes all other ids with any body.
+    Union {
+        variants: Vec<(u32, &'static str, VariantFormat)>,
+        fallback: Option<&'static str>,
+    },
+}
+
+/// The layout of an enum variant.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VariantFormat {
+    Unit,
+    Newtype(Box<Format>),
+    Tuple(Vec<Format>),
+    Struct(Vec<(&'static str, Format)>),
+}
+
+/// Named structs and enums referenced by [`Format::TypeName`].
+pub type Registry = BTreeMap<&'static str, ContainerFormat>;
+
+/// The traced layout of a type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Layout {
+    /// The layout of the type itself.
+    pub root: Format,
+    /// All named structs and enums reachable from `root`.
+    pub registry: Registry,
+}
+
+impl Layout {
+    /// Trace the layout of type `T`.
+    pub fn of<'de, T>() -> Result<Layout>
+    where
+        T: de::Deserialize<'de>,
+    {
+        let mut tracer = MoleculeDeserializerTracer::default();
+        loop {
+            tracer.progress = false;
+            let (_, root) = tracer.record(|tracer| T::deserialize(tracer).map(drop))?;
+            match tracer.incomplete_enum() {
+                None => {
+                    return Ok(Layout {
+                        root,
+                        registry: tracer.finish(),
+                    })
+                }
+                Some(name) if !tracer.progress => {
+                    return Err(Error::custom(format!(
+                        "can't trace all variants of `{}`",
+                        name
+                    )))
+                }
+                Some(_) => {}
+            }
+        }
+    }
+}
+
+enum Traced {
+    Container(ContainerFormat),
+    // variants not traced yet are `None`
+    Enum(Vec<(&'static str, Option<VariantFormat>)>),
+}
+
+// The name contains `MoleculeDeserializer`: `struct_serde`, `dynvec_serde`
+// treat it as molecule deserializer.
+#[derive(Default)]
+struct MoleculeDeserializerTracer {
+    registry: BTreeMap<&'static str, Traced>,
+    // union ids of enums marked by `#[molecule(union)]`, `None` for the
+    // fallback variant
+    union_ids: BTreeMap<&'static str, Vec<Option<u32>>>,
+    // the containers being traced, to detect recursive types
+    stack: Vec<&'static str>,
+    // the format of the last traced value
+    format: Option<Format>,
+    // whether anything new is traced in this pass
+    progress: bool,
+}
+
+impl MoleculeDeserializerTracer {
+    // Run `f`, which deserializes exactly one value, and return its format.
+    fn record<R>(&mut self, f: impl FnOnce(&mut Self) -> Result<R>) -> Result<(R, Format)> {
+        self.format = None;
+        let value = f(self)?;
+        match self.format.take() {
+            Some(format) => Ok((value, format)),
+            None => Err(Error::custom("the value isn't deserialized")),
+        }
+    }
+
+    // Trace a value in molecule struct, from `struct_serde` or `#[molecule(struct)]`.
+    fn packed<R>(&mut self, f: impl FnOnce(&mut Self) -> Result<R>) -> Result<R> {
+        let (value, format) = self.record(f)?;
+        self.format = Some(match format {
+            Format::Struct(_) => format,
+            _ => Format::Struct(Box::new(format)),
+        });
+        Ok(value)
+    }
+
+    fn enter(&mut self, name: &'static str) -> Result<()> {
+        if self.stack.contains(&name) {
+            return Err(Error::custom(format!(
+                "recursive type `{}` isn't supported",
+                name
+            )));
+        }
+        self.stack.push(name);
+        Ok(())
+    }
+
+    fn leave(&mut self, name: &'static str) {
+        self.stack.pop();
+        self.format = Some(Format::TypeName(name));
+    }
+
+    fn add_container(&mut self, name: &'static str, container: ContainerFormat) -> Result<()> {
+        match self.registry.get(name) {
+            None => {
+                self.registry.insert(name, Traced::Container(container));
+                self.progress = true;
+                Ok(())
+            }
+            Some(Traced::Container(c)) if *c == container => Ok(()),
+            Some(_) => Err(conflict(name)),
+        }
+    }
+
+    fn add_variant(
+        &mut self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        index: usize,
+        variant: VariantFormat,
+    ) -> Result<()> {
+        let traced = self
+            .registry
+            .entry(name)
+            .or_insert_with(|| Traced::Enum(variants.iter().map(|v| (*v, None)).collect()));
+        match traced {
+            Traced::Enum(traced) if traced.len() == variants.len() => match &traced[index].1 {
+                None => {
+                    traced[index].1 = Some(variant);
+                    self.progress = true;
+                    Ok(())
+                }
+                Some(v) if *v == variant => Ok(()),
+                Some(_) => Err(conflict(name)),
+            },
+            _ => Err(conflict(name)),
+        }
+    }
+
+    // The variant to trace: the first one not traced yet, or the first one
+    // leading to an enum with such variants.
+    fn pick_variant(&self, name: &'static str) -> usize {
+        match self.registry.get(name) {
+            Some(Traced::Enum(variants)) => {
+                let untraced = variants.iter().position(|(_, v)| v.is_none());
+                untraced
+                    .or_else(|| {
+                        variants.iter().position(|(_, v)| match v {
+                            Some(v) => variant_formats(v)
+                                .any(|f| self.reaches_incomplete(f, &mut BTreeSet::new())),
+                            None => false,
+                        })
+                    })
+                    .unwrap_or(0)
+            }
+            _ => 0,
+        }
+    }
+
+    fn reaches_incomplete(&self, format: &Format, visited: &mut BTreeSet<&'static str>) -> bool {
+        match format {
+            Format::Option(f) | Format::Fixvec(f) | Format::Dynvec(f) | Format::Struct(f) => {
+                self.reaches_incomplete(f, visited)
+            }
+            Format::Map(k, v) => {
+                self.reaches_incomplete(k, visited) || self.reaches_incomplete(v, visited)
+            }
+            Format::Tuple(fs) => fs.iter().any(|f| self.reaches_incomplete(f, visited)),
+            Format::TypeName(name) => {
+                if !visited.insert(name) {
+                    return false;
+                }
+                match self.registry.get(name) {
+                    Some(Traced::Container(c)) => container_formats(c)
+                        .into_iter()
+                        .any(|f| self.reaches_incomplete(f, visited)),
+                    Some(Traced::Enum(variants)) => variants.iter().any(|(_, v)| match v {
+                        Some(v) => variant_formats(v).any(|f| self.reaches_incomplete(f, visited)),
+                        None => true,
+                    }),
+                    None => false,
+                }
+            }
+            _ => false,
+        }
+    }
+
+    fn incomplete_enum(&self) -> Option<&'static str> {
+        self.registry
+            .iter()
+            .find_map(|(name, traced)| match traced {
+                Traced::Enum(variants) if variants.iter().any(|(_, v)| v.is_none()) => Some(*name),
+                _ => None,
+            })
+    }
+
+    fn finish(mut self) -> Registry {
+        self.registry
+            .into_iter()
+            .map(|(name, traced)| {
+                let container = match traced {
+                    Traced::Container(c) => c,
+                    Traced::Enum(variants) => {
+                        let variants = variants
+                            .into_iter()
+                            .map(|(n, v)| (n, v.expect("all variants are traced")));
+                        match self.union_ids.remove(name) {
+                            Some(ids) => {
+                                let mut fallback = None;
+                                let mut union = vec![];
+                                for (id, (n, v)) in ids.into_iter().zip(variants) {
+                                    match id {
+                                        Some(id) => union.push((id, n, v)),
+                                        None => fallback = Some(n),
+                                    }
+                                }
+                                ContainerFormat::Union {
+                                    variants: union,
+                                    fallback,
+                                }
+                            }
+                            None => ContainerFormat::Enum(variants.collect()),
+                        }
+                    }
+                };
+                (name, container)
+            })
+            .collect()
+    }
+}
+
+fn conflict(name: &str) -> Error {
+    Error::custom(format!("conflicting layouts of `{}`", name))
+}
+
+fn variant_formats(variant: &VariantFormat) -> impl Iterator<Item = &Format> {
+    let formats: Vec<&Format> = match variant {
+        VariantFormat::Unit => vec![],
+        VariantFormat::Newtype(f) => vec![f],
+        VariantFormat::Tuple(fs) => fs.iter().collect(),
+        VariantFormat::Struct(fs) => fs.iter().map(|(_, f)| f).collect(),
+    };
+    formats.into_iter()
+}
+
+fn container_formats(container: &ContainerFormat) -> Vec<&Format> {
+    match container {
+        ContainerFormat::UnitStruct | ContainerFormat::Enum(_) | ContainerFormat::Union { .. } => {
+            vec![]
+        }
+        ContainerFormat::NewtypeStruct(f) => vec![f],
+        ContainerFormat::TupleStruct(fs) => fs.iter().collect(),
+        ContainerFormat::Struct(fs) => fs.iter().map(|(_, f)| f).collect(),
+    }
+}
+
+macro_rules! trace_primitive {
+    ($method:ident, $visit:ident, $format:ident, $value:expr) => {
+        fn $method<V>(self, visitor: V) -> Result<V::Value>
+        where
+            V: de::Visitor<'de>,
+        {
+            self.format = Some(Format::$format);
+            visitor.$visit($value)
+        }
+    };
+}
+
+impl<'de> de::Deserializer<'de> for &mut MoleculeDeserializerTracer {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(Error::Unimplemented)
+    }
+
+    trace_primitive!(deserialize_bool, visit_bool, Bool, false);
+    trace_primitive!(deserialize_i8, visit_i8, I8, 0);
+    trace_primitive!(deserialize_i16, visit_i16, I16, 0);
+    trace_primitive!(deserialize_i32, visit_i32, I32, 0);
+    trace_primitive!(deserialize_i64, visit_i64, I64, 0);
+    trace_primitive!(deserialize_i128, visit_i128, I128, 0);
+    trace_primitive!(deserialize_u8, visit_u8, U8, 0);
+    trace_primitive!(deserialize_u16, visit_u16, U16, 0);
+    trace_primitive!(deserialize_u32, visit_u32, U32, 0);
+    trace_primitive!(deserialize_u64, visit_u64, U64, 0);
+    trace_primitive!(deserialize_u128, visit_u128, U128, 0);
+    trace_primitive!(deserialize_f32, visit_f32, F32, 0.0);
+    trace_primitive!(deserialize_f64, visit_f64, F64, 0.0);
+    trace_primitive!(deserialize_char, visit_char, Char, '\0');
+    trace_primitive!(deserialize_str, visit_borrowed_str, Str, "");
+    trace_primitive!(deserialize_string, visit_borrowed_str, Str, "");
+    trace_primitive!(deserialize_bytes, visit_borrowed_bytes, Bytes, &[]);
+    trace_primitive!(deserialize_byte_buf, visit_borrowed_bytes, Bytes, &[]);
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let (value, format) = self.record(|tracer| visitor.visit_some(tracer))?;
+        self.format = Some(Format::Option(Box::new(format)));
+        Ok(value)
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.format = Some(Format::Unit);
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.enter(name)?;
+        let value = visitor.visit_unit();
+        self.leave(name);
+        self.add_container(name, ContainerFormat::UnitStruct)?;
+        value
+    }
+
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        if name == STRUCT_STR {
+            self.packed(|tracer| visitor.visit_newtype_struct(tracer))
+        } else if let Some(name) = struct_name(name) {
+            self.packed(|tracer| tracer.deserialize_newtype_struct(name, visitor))
+        } else if name == RAW_STR {
+            self.format = Some(Format::Raw);
+            visitor.visit_borrowed_bytes(&[])
+        } else {
+            self.enter(name)?;
+            let result = self.record(|tracer| visitor.visit_newtype_struct(tracer));
+            self.leave(name);
+            let (value, format) = result?;
+            self.add_container(name, ContainerFormat::NewtypeStruct(Box::new(format)))?;
+            Ok(value)
+        }
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let mut formats = vec![];
+        let value = visitor.visit_seq(SeqTracer::new(self, 1, &mut formats))?;
+        let format = single(formats)?;
+        self.format = Some(Format::Fixvec(Box::new(format)));
+        Ok(value)
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let mut formats = vec![];
+        let value = visitor.visit_seq(SeqTracer::new(self, len, &mut formats))?;
+        if formats.len() != len {
+            return Err(Error::custom("the tuple isn't fully deserialized"));
+        }
+        self.format = Some(Format::Tuple(formats));
+        Ok(value)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        if let Some(name) = struct_name(name) {
+            return self.packed(|tracer| tracer.deserialize_tuple_struct(name, len, visitor));
+        }
+        self.enter(name)?;
+        let mut formats = vec![];
+        let value = visitor.visit_seq(SeqTracer::new(self, len, &mut formats));
+        self.leave(name);
+        let value = value?;
+        self.add_container(name, ContainerFormat::TupleStruct(formats))?;
+        Ok(value)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let mut formats = vec![];
+        let value = visitor.visit_map(MapTracer::new(self, None, 1, &mut formats))?;
+        match (formats.pop(), formats.pop()) {
+            (Some(value_format), Some(key_format)) => {
+                self.format = Some(Format::Map(Box::new(key_format), Box::new(value_format)));
+                Ok(value)
+            }
+            _ => Err(Error::custom("the map isn't fully deserialized")),
+        }
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        if name == DYNVEC_STR {
+            let mut formats = vec![];
+            let value = visitor.visit_map(MapTracer::new(self, Some(0), 1, &mut formats))?;
+            let format = single(formats)?;
+            self.format = Some(Format::Dynvec(Box::new(format)));
+            Ok(value)
+        } else if let Some(name) = struct_name(name) {
+            self.packed(|tracer| tracer.deserialize_struct(name, fields, visitor))
+        } else {
+            self.enter(name)?;
+            let mut formats = vec![];
+            let value =
+                visitor.visit_map(MapTracer::new(self, Some(0), fields.len(), &mut formats));
+            self.leave(name);
+            let value = value?;
+            let fields = named(fields, formats)?;
+            self.add_container(name, ContainerFormat::Struct(fields))?;
+            Ok(value)
+        }
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let name = match union_ids(name) {
+            Some((name, ids)) => {
+                let ids: Vec<_> = ids.iter().collect();
+                if ids.len() != variants.len() {
+                    return Err(Error::custom(format!("invalid union ids of `{}`", name)));
+                }
+                if self
+                    .union_ids
+                    .insert(name, ids.clone())
+                    .is_some_and(|i| i != ids)
+                {
+                    return Err(conflict(name));
+                }
+                name
+            }
+            None => name,
+        };
+        if variants.is_empty() {
+            return Err(Error::custom(format!("enum `{}` has no variant", name)));
+        }
+        let index = self.pick_variant(name);
+        self.enter(name)?;
+        let mut format = None;
+        let value = visitor.visit_enum(EnumTracer {
+            tracer: self,
+            index,
+            format: &mut format,
+        });
+        self.leave(name);
+        let value = value?;
+        match format {
+            Some(format) => self.add_variant(name, variants, index, format)?,
+            None => return Err(Error::custom("the variant isn't deserialized")),
+        }
+        Ok(value)
+    }
+
+    fn deserialize_identifier<V>(self, _visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(Error::Unimplemented)
+    }
+
+    fn deserialize_ignored_any<V>(self, _visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(Error::Unimplemented)
+    }
+}
+
+fn single(mut formats: Vec<Format>) -> Result<Format> {
+    match formats.pop() {
+        Some(format) => Ok(format),
+        None => Err(Error::custom("the element isn't deserialized")),
+    }
+}
+
+fn named(
+    fields: &'static [&'static str],
+    formats: Vec<Format>,
+) -> Result<Vec<(&'static str, Format)>> {
+    if fields.len() != formats.len() {
+        return Err(Error::custom("the struct isn't fully deserialized"));
+    }
+    Ok(fields.iter().copied().zip(formats).collect())
+}
+
+// Gives `len` elements and records their formats.
+struct SeqTracer<'a> {
+    tracer: &'a mut MoleculeDeserializerTracer,
+    len: usize,
+    formats: &'a mut Vec<Format>,
+}
+
+impl<'a> SeqTracer<'a> {
+    fn new(
+        tracer: &'a mut MoleculeDeserializerTracer,
+        len: usize,
+        formats: &'a mut Vec<Format>,
+    ) -> Self {
+        SeqTracer {
+            tracer,
+            len,
+            formats,
+        }
+    }
+}
+
+impl<'de> de::SeqAccess<'de> for SeqTracer<'_> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        if self.formats.len() < self.len {
+            let (value, format) = self.tracer.record(|tracer| seed.deserialize(tracer))?;
+            self.formats.push(format);
+            Ok(Some(value))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len - self.formats.len())
+    }
+}
+
+// Gives `len` entries and records the formats of their keys and values. With
+// `index`, keys are field indexes like `TableAccess` and only the values are
+// recorded.
+struct MapTracer<'a> {
+    tracer: &'a mut MoleculeDeserializerTracer,
+    index: Option<usize>,
+    len: usize,
+    formats: &'a mut Vec<Format>,
+}
+
+impl<'a> MapTracer<'a> {
+    fn new(
+        tracer: &'a mut MoleculeDeserializerTracer,
+        index: Option<usize>,
+        len: usize,
+        formats: &'a mut Vec<Format>,
+    ) -> Self {
+        MapTracer {
+            tracer,
+            index,
+            len,
+            formats,
+        }
+    }
+
+    fn entries(&self) -> usize {
+        match self.index {
+            Some(_) => self.formats.len(),
+            None => self.formats.len() / 2,
+        }
+    }
+}
+
+impl<'de> de::MapAccess<'de> for MapTracer<'_> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        if self.entries() >= self.len {
+            return Ok(None);
+        }
+        match &mut self.index {
+            Some(index) => {
+                let de = U64Deserializer::<Error>::new(*index as u64);
+                *index += 1;
+                Ok(Some(seed.deserialize(de)?))
+            }
+            None => {
+                let (value, format) = self.tracer.record(|tracer| seed.deserialize(tracer))?;
+                self.formats.push(format);
+                Ok(Some(value))
+            }
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let (value, format) = self.tracer.record(|tracer| seed.deserialize(tracer))?;
+        self.formats.push(format);
+        Ok(value)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len - self.entries())
+    }
+}
+
+struct EnumTracer<'a> {
+    tracer: &'a mut MoleculeDeserializerTracer,
+    index: usize,
+    format: &'a mut Option<VariantFormat>,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumTracer<'_> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self)>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let de = U64Deserializer::<Error>::new(self.index as u64);
+        Ok((seed.deserialize(de)?, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for EnumTracer<'_> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        *self.format = Some(VariantFormat::Unit);
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        let (value, format) = self.tracer.record(|tracer| seed.deserialize(tracer))?;
+        *self.format = Some(VariantFormat::Newtype(Box::new(format)));
+        Ok(value)
+    }
+
+    fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let mut formats = vec![];
+        let value = visitor.visit_seq(SeqTracer::new(self.tracer, len, &mut formats))?;
+        *self.format = Some(VariantFormat::Tuple(formats));
+        Ok(value)
+    }
+
+    fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let mut formats = vec![];
+        let value = visitor.visit_map(MapTracer::new(
+            self.tracer,
+            Some(0),
+            fields.len(),
+            &mut formats,
+        ))?;
+        *self.format = Some(VariantFormat::Struct(named(fields, formats)?));
+        Ok(value)
+    }
+}
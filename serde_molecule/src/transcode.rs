@@ -0,0 +1,300 @@
+//! Transcode molecule data to and from JSON following a schema.
+//!
+//! [`to_json`] decodes molecule data with [`from_slice_with_schema`] and
+//! converts it to a `serde_json::Value`, [`from_json`] does the opposite and
+//! returns the canonical molecule encoding. The JSON looks like:
+//! * `byte`: a number.
+//! * Arrays and vectors of `byte`: a hex string with `0x` prefix, in
+//!   lowercase, e.g. `"0x0102"`. Upper case digits are accepted too.
+//! * Other arrays and vectors: arrays.
+//! * Structs and tables: objects with the declared fields. Unknown or missing
+//!   fields are rejected.
+//! * Options: `null` for none, otherwise the value.
+//! * Unions: `{"type": "Item", "value": ...}`, where `Item` is the item type.
+//!
+//! ```
+//! use serde_json::json;
+//! use serde_molecule::schema::Schema;
+//! use serde_molecule::transcode::{from_json, to_json};
+//!
+//! let schema = Schema::parse(
+//!     "
+//!     array Byte4 [byte; 4];
+//!     vector Bytes <byte>;
+//!     option BytesOpt (Bytes);
+//!     table Script { hash_type: byte, args: BytesOpt }
+//!     union Lock { Byte4, Script }
+//!     ",
+//! )
+//! .unwrap();
+//! let json = json!({
+//!     "type": "Script",
+//!     "value": { "hash_type": 1, "args": "0x0102" },
+//! });
+//! let bytes = from_json(&json, &schema, "Lock").unwrap();
+//! assert_eq!(to_json(&bytes, &schema, "Lock").unwrap(), json);
+//!
+//! let json = json!({ "type": "Script", "value": { "hash_type": 1 } });
+//! let error = from_json(&json, &schema, "Lock").unwrap_err();
+//! assert_eq!(error.to_string(), "missing field `args` of `Script` at `Script`");
+//! ```
+use crate::error::{Error, Result};
+use crate::schema::{DeclKind, Field, Schema};
+use crate::value::{
+    from_compatible_slice_with_schema, from_slice_with_schema, to_vec_with_schema, Value,
+};
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use serde::de::Error as _;
+use serde_json::{Map, Value as Json};
+
+/// Decode `v` as type `name` of `schema` and convert it to JSON.
+pub fn to_json(v: &[u8], schema: &Schema, name: &str) -> Result<Json> {
+    let value = from_slice_with_schema(v, schema, name)?;
+    Ok(json_of(schema, name, &value))
+}
+
+/// Same as [`to_json`], but tables can have extra fields, see
+/// [`from_compatible_slice_with_schema`].
+pub fn to_json_compatible(v: &[u8], schema: &Schema, name: &str) -> Result<Json> {
+    let value = from_compatible_slice_with_schema(v, schema, name)?;
+    Ok(json_of(schema, name, &value))
+}
+
+/// Convert `json` to type `name` of `schema` and encode it. Errors give the
+/// path of the offending JSON value, like `Error::Context` does for molecule
+/// data.
+pub fn from_json(json: &Json, schema: &Schema, name: &str) -> Result<Vec<u8>> {
+    let value = Parser {
+        schema,
+        path: String::new(),
+    }
+    .value(name, json)?;
+    to_vec_with_schema(&value, schema, name)
+}
+
+// `value` is decoded from `name`, so they always match.
+fn json_of(schema: &Schema, name: &str, value: &Value) -> Json {
+    let kind = schema.get(name).map(|decl| &decl.kind);
+    match (kind, value) {
+        (_, Value::Byte(b)) => Json::from(*b),
+        (Some(DeclKind::Array { item, .. }), Value::Array(items))
+        | (Some(DeclKind::Vector(item)), Value::FixVec(items) | Value::DynVec(items)) => {
+            if item == "byte" {
+                Json::String(to_hex(items))
+            } else {
+                Json::Array(items.iter().map(|v| json_of(schema, item, v)).collect())
+            }
+        }
+        (
+            Some(DeclKind::Struct(fields) | DeclKind::Table(fields)),
+            Value::Struct(values) | Value::Table(values),
+        ) => {
+            let map = fields
+                .iter()
+                .zip(values)
+                .map(|(field, (_, v))| (field.name.clone(), json_of(schema, &field.ty, v)))
+                .collect();
+            Json::Object(map)
+        }
+        (Some(DeclKind::Option(item)), Value::Option(v)) => match v {
+            Some(v) => json_of(schema, item, v),
+            None => Json::Null,
+        },
+        (Some(DeclKind::Union(items)), Value::Union { id, value }) => {
+            let item = items.iter().find(|item| item.id == *id);
+            let ty = item.map(|item| item.ty.as_str()).unwrap_or_default();
+            let mut map = Map::new();
+            map.insert("type".to_string(), Json::from(ty));
+            map.insert("value".to_string(), json_of(schema, ty, value));
+            Json::Object(map)
+        }
+        _ => unreachable!("the value doesn't match `{}`", name),
+    }
+}
+
+fn to_hex(items: &[Value]) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    let mut hex = String::from("0x");
+    for item in items {
+        if let Value::Byte(b) = item {
+            hex.push(DIGITS[(b >> 4) as usize] as char);
+            hex.push(DIGITS[(b & 0xf) as usize] as char);
+        }
+    }
+    hex
+}
+
+fn from_hex(hex: &str) -> Option<Vec<Value>> {
+    let hex = hex.strip_prefix("0x")?.as_bytes();
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    let digit = |c: u8| (c as char).to_digit(16).map(|d| d as u8);
+    hex.chunks(2)
+        .map(|pair| Some(Value::Byte((digit(pair[0])? << 4) | digit(pair[1])?)))
+        .collect()
+}
+
+// The JSON type of `json`, or the number itself.
+fn describe(json: &Json) -> String {
+    match json {
+        Json::Null => "null".into(),
+        Json::Bool(_) => "boolean".into(),
+        Json::Number(n) => n.to_string(),
+        Json::String(_) => "string".into(),
+        Json::Array(_) => "array".into(),
+        Json::Object(_) => "object".into(),
+    }
+}
+
+struct Parser<'a> {
+    schema: &'a Schema,
+    // the path to the current JSON value, e.g. `raw.outputs[3].lock`
+    path: String,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: String) -> Error {
+        if self.path.is_empty() {
+            Error::custom(message)
+        } else {
+            Error::custom(format!("{} at `{}`", message, self.path))
+        }
+    }
+
+    // Convert `json` with `segment` appended to the path.
+    fn nested(&mut self, segment: &str, name: &str, json: &Json) -> Result<Value> {
+        let len = self.path.len();
+        if !self.path.is_empty() && !segment.starts_with('[') {
+            self.path.push('.');
+        }
+        self.path.push_str(segment);
+        let result = self.value(name, json);
+        self.path.truncate(len);
+        result
+    }
+
+    fn items(&mut self, name: &str, item: &str, json: &Json) -> Result<Vec<Value>> {
+        if item == "byte" {
+            return match json {
+                Json::String(hex) => from_hex(hex)
+                    .ok_or_else(|| self.error(format!("invalid hex string for `{}`", name))),
+                _ => Err(self.error(format!(
+                    "expected hex string for `{}`, found {}",
+                    name,
+                    describe(json)
+                ))),
+            };
+        }
+        match json {
+            Json::Array(items) => items
+                .iter()
+                .enumerate()
+                .map(|(i, v)| self.nested(&format!("[{}]", i), item, v))
+                .collect(),
+            _ => Err(self.error(format!(
+                "expected array for `{}`, found {}",
+                name,
+                describe(json)
+            ))),
+        }
+    }
+
+    fn fields(
+        &mut self,
+        name: &str,
+        fields: &[Field],
+        json: &Json,
+    ) -> Result<Vec<(String, Value)>> {
+        let map = match json {
+            Json::Object(map) => map,
+            _ => {
+                return Err(self.error(format!(
+                    "expected object for `{}`, found {}",
+                    name,
+                    describe(json)
+                )))
+            }
+        };
+        if let Some(key) = map.keys().find(|k| fields.iter().all(|f| f.name != **k)) {
+            return Err(self.error(format!("unknown field `{}` of `{}`", key, name)));
+        }
+        fields
+            .iter()
+            .map(|field| match map.get(&field.name) {
+                Some(v) => Ok((field.name.clone(), self.nested(&field.name, &field.ty, v)?)),
+                None => Err(self.error(format!("missing field `{}` of `{}`", field.name, name))),
+            })
+            .collect()
+    }
+
+    fn value(&mut self, name: &str, json: &Json) -> Result<Value> {
+        if name == "byte" {
+            return match json.as_u64().filter(|n| *n <= u8::MAX as u64) {
+                Some(n) => Ok(Value::Byte(n as u8)),
+                None => Err(self.error(format!("expected byte, found {}", describe(json)))),
+            };
+        }
+        let decl = self
+            .schema
+            .get(name)
+            .ok_or_else(|| Error::custom(format!("undefined type `{}`", name)))?;
+        match &decl.kind {
+            DeclKind::Array { item, count } => {
+                let items = self.items(name, item, json)?;
+                if items.len() != *count {
+                    return Err(self.error(format!(
+                        "array `{}` has {} items, found {}",
+                        name,
+                        count,
+                        items.len()
+                    )));
+                }
+                Ok(Value::Array(items))
+            }
+            DeclKind::Struct(fields) => Ok(Value::Struct(self.fields(name, fields, json)?)),
+            DeclKind::Vector(item) => {
+                let items = self.items(name, item, json)?;
+                if self.schema.fixed_size(item).is_some() {
+                    Ok(Value::FixVec(items))
+                } else {
+                    Ok(Value::DynVec(items))
+                }
+            }
+            DeclKind::Option(item) => match json {
+                Json::Null => Ok(Value::Option(None)),
+                _ => Ok(Value::Option(Some(Box::new(self.value(item, json)?)))),
+            },
+            DeclKind::Union(items) => {
+                let (ty, value) = match json {
+                    Json::Object(map) if map.len() == 2 => {
+                        match (map.get("type").and_then(Json::as_str), map.get("value")) {
+                            (Some(ty), Some(value)) => (ty, value),
+                            _ => return Err(self.union_error(name, json)),
+                        }
+                    }
+                    _ => return Err(self.union_error(name, json)),
+                };
+                let item = items.iter().find(|item| item.ty == ty).ok_or_else(|| {
+                    self.error(format!("`{}` isn't an item of union `{}`", ty, name))
+                })?;
+                Ok(Value::Union {
+                    id: item.id,
+                    value: Box::new(self.nested(ty, ty, value)?),
+                })
+            }
+            DeclKind::Table(fields) => Ok(Value::Table(self.fields(name, fields, json)?)),
+        }
+    }
+
+    fn union_error(&self, name: &str, json: &Json) -> Error {
+        self.error(format!(
+            "expected object with `type` and `value` for union `{}`, found {}",
+            name,
+            describe(json)
+        ))
+    }
+}
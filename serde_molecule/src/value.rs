@@ -0,0 +1,370 @@
+//! Decode and encode molecule data described by a schema at runtime.
+//!
+//! Molecule isn't self-describing, so data can't be decoded without knowing
+//! its type. When the type is only known at runtime, e.g. in explorers or
+//! debuggers, [`from_slice_with_schema`] decodes the data into a [`Value`]
+//! following a [`Schema`], and [`to_vec_with_schema`] encodes it back.
+//!
+//! ```
+//! use serde_molecule::schema::Schema;
+//! use serde_molecule::{from_slice_with_schema, to_vec_with_schema, Value};
+//!
+//! let schema = Schema::parse(
+//!     "
+//!     array Uint16 [byte; 2];
+//!     vector Bytes <byte>;
+//!     table Cell { capacity: Uint16, data: Bytes }
+//!     ",
+//! )
+//! .unwrap();
+//! let bytes = [18, 0, 0, 0, 12, 0, 0, 0, 14, 0, 0, 0, 1, 2, 0, 0, 0, 0];
+//! let value = from_slice_with_schema(&bytes, &schema, "Cell").unwrap();
+//! assert_eq!(
+//!     value,
+//!     Value::Table(vec![
+//!         ("capacity".into(), Value::Array(vec![Value::Byte(1), Value::Byte(2)])),
+//!         ("data".into(), Value::FixVec(vec![])),
+//!     ])
+//! );
+//! assert_eq!(to_vec_with_schema(&value, &schema, "Cell").unwrap(), bytes);
+//! ```
+use crate::error::{Error, Result, Segment};
+use crate::molecule::{fixvec_item_count, table_item, unpack_number, verify_table};
+use crate::schema::{Decl, DeclKind, Field, Schema};
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use serde::de::Error as _;
+
+/// A molecule value of any type, see the [module documentation](self).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Value {
+    Byte(u8),
+    Array(Vec<Value>),
+    /// The fields in the declared order.
+    Struct(Vec<(String, Value)>),
+    /// A vector of fixed size items.
+    FixVec(Vec<Value>),
+    /// A vector of dynamic size items.
+    DynVec(Vec<Value>),
+    /// The fields in the declared order.
+    Table(Vec<(String, Value)>),
+    Option(Option<Box<Value>>),
+    Union {
+        id: u32,
+        value: Box<Value>,
+    },
+}
+
+impl Value {
+    fn kind(&self) -> &'static str {
+        match self {
+            Value::Byte(_) => "byte",
+            Value::Array(_) => "array",
+            Value::Struct(_) => "struct",
+            Value::FixVec(_) => "fixvec",
+            Value::DynVec(_) => "dynvec",
+            Value::Table(_) => "table",
+            Value::Option(_) => "option",
+            Value::Union { .. } => "union",
+        }
+    }
+}
+
+/// Decode `v` as type `name` of `schema`.
+///
+/// Like the `from_slice` of readers generated by molecule, tables must have
+/// exactly the declared fields, and the ids of unions must be declared.
+/// Errors are wrapped in `Error::Context` with the offset and the path, where
+/// union bodies are named by the item type.
+pub fn from_slice_with_schema(v: &[u8], schema: &Schema, name: &str) -> Result<Value> {
+    Codec {
+        schema,
+        compatible: false,
+    }
+    .decode(name, v, 0)
+}
+
+/// Same as [`from_slice_with_schema`], but like the `from_compatible_slice` of
+/// readers generated by molecule, tables can have extra fields appended by
+/// newer versions of the schema. The extra fields are dropped.
+pub fn from_compatible_slice_with_schema(v: &[u8], schema: &Schema, name: &str) -> Result<Value> {
+    Codec {
+        schema,
+        compatible: true,
+    }
+    .decode(name, v, 0)
+}
+
+/// Encode `value` as type `name` of `schema`. The value must match the
+/// declaration: e.g. the vectors of fixed size items must be `Value::FixVec`,
+/// and the fields of structs and tables must be in the declared order.
+pub fn to_vec_with_schema(value: &Value, schema: &Schema, name: &str) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    Codec {
+        schema,
+        compatible: false,
+    }
+    .encode(name, value, &mut out)?;
+    Ok(out)
+}
+
+struct Codec<'a> {
+    schema: &'a Schema,
+    // extra fields of tables are allowed
+    compatible: bool,
+}
+
+impl<'a> Codec<'a> {
+    fn decl(&self, name: &str) -> Result<&'a Decl> {
+        self.schema
+            .get(name)
+            .ok_or_else(|| Error::custom(format!("undefined type `{}`", name)))
+    }
+
+    fn decode(&self, name: &str, data: &[u8], offset: usize) -> Result<Value> {
+        if let Some(size) = self.schema.fixed_size(name) {
+            if data.len() != size {
+                return Err(Error::MismatchedLength {
+                    expected: size,
+                    actual: data.len(),
+                });
+            }
+        }
+        if name == "byte" {
+            return Ok(Value::Byte(data[0]));
+        }
+        match &self.decl(name)?.kind {
+            DeclKind::Array { item, count } => {
+                let size = data.len() / count;
+                let items = data.chunks(size).enumerate().map(|(i, part)| {
+                    let offset = offset + i * size;
+                    self.decode(item, part, offset)
+                        .map_err(|e| e.at(offset, Segment::Index(i)))
+                });
+                Ok(Value::Array(items.collect::<Result<_>>()?))
+            }
+            DeclKind::Struct(fields) => {
+                let mut start = 0;
+                let mut values = Vec::with_capacity(fields.len());
+                for field in fields {
+                    let size = self.schema.fixed_size(&field.ty).unwrap_or_default();
+                    let offset = offset + start;
+                    let value = self
+                        .decode(&field.ty, &data[start..start + size], offset)
+                        .map_err(|e| e.at_field(offset, &field.name))?;
+                    values.push((field.name.clone(), value));
+                    start += size;
+                }
+                Ok(Value::Struct(values))
+            }
+            DeclKind::Vector(item) => match self.schema.fixed_size(item) {
+                Some(size) => {
+                    let count = fixvec_item_count(data)?;
+                    let expected = count
+                        .checked_mul(size)
+                        .and_then(|n| n.checked_add(4))
+                        .ok_or(Error::Overflow)?;
+                    if data.len() != expected {
+                        return Err(Error::InvalidFixvec);
+                    }
+                    let items = data[4..].chunks(size).enumerate().map(|(i, part)| {
+                        let offset = offset + 4 + i * size;
+                        self.decode(item, part, offset)
+                            .map_err(|e| e.at(offset, Segment::Index(i)))
+                    });
+                    Ok(Value::FixVec(items.collect::<Result<_>>()?))
+                }
+                None => {
+                    let count = verify_table(data)?;
+                    let mut items = Vec::with_capacity(count);
+                    for i in 0..count {
+                        let part = table_item(data, i)?;
+                        let offset = offset_of(data, offset, part);
+                        let value = self
+                            .decode(item, part, offset)
+                            .map_err(|e| e.at(offset, Segment::Index(i)))?;
+                        items.push(value);
+                    }
+                    Ok(Value::DynVec(items))
+                }
+            },
+            DeclKind::Option(item) => {
+                if data.is_empty() {
+                    Ok(Value::Option(None))
+                } else {
+                    let value = self.decode(item, data, offset)?;
+                    Ok(Value::Option(Some(Box::new(value))))
+                }
+            }
+            DeclKind::Union(items) => {
+                let id = unpack_number(data, 0)?;
+                let item = items
+                    .iter()
+                    .find(|item| item.id as usize == id)
+                    .ok_or(Error::UnknownUnionId { id })?;
+                let value = self
+                    .decode(&item.ty, &data[4..], offset + 4)
+                    .map_err(|e| e.at_field(offset + 4, &item.ty))?;
+                Ok(Value::Union {
+                    id: item.id,
+                    value: Box::new(value),
+                })
+            }
+            DeclKind::Table(fields) => {
+                let count = verify_table(data)?;
+                if count < fields.len() || (!self.compatible && count != fields.len()) {
+                    return Err(Error::MismatchedTableFieldCount);
+                }
+                let mut values = Vec::with_capacity(fields.len());
+                for (i, field) in fields.iter().enumerate() {
+                    let part = table_item(data, i)?;
+                    let offset = offset_of(data, offset, part);
+                    let value = self
+                        .decode(&field.ty, part, offset)
+                        .map_err(|e| e.at_field(offset, &field.name))?;
+                    values.push((field.name.clone(), value));
+                }
+                Ok(Value::Table(values))
+            }
+        }
+    }
+
+    fn encode(&self, name: &str, value: &Value, out: &mut Vec<u8>) -> Result<()> {
+        if name == "byte" {
+            return match value {
+                Value::Byte(b) => {
+                    out.push(*b);
+                    Ok(())
+                }
+                _ => Err(mismatched("byte", name, value)),
+            };
+        }
+        match (&self.decl(name)?.kind, value) {
+            (DeclKind::Array { item, count }, Value::Array(items)) => {
+                if items.len() != *count {
+                    return Err(Error::custom(format!(
+                        "array `{}` has {} items, found {}",
+                        name,
+                        count,
+                        items.len()
+                    )));
+                }
+                items.iter().try_for_each(|v| self.encode(item, v, out))
+            }
+            (DeclKind::Struct(fields), Value::Struct(values)) => {
+                check_fields(name, fields, values)?;
+                for (field, (_, v)) in fields.iter().zip(values) {
+                    self.encode(&field.ty, v, out)?;
+                }
+                Ok(())
+            }
+            (DeclKind::Vector(item), Value::FixVec(items))
+                if self.schema.fixed_size(item).is_some() =>
+            {
+                out.extend(number(items.len())?.to_le_bytes());
+                items.iter().try_for_each(|v| self.encode(item, v, out))
+            }
+            (DeclKind::Vector(item), Value::DynVec(items))
+                if self.schema.fixed_size(item).is_none() =>
+            {
+                self.encode_table(items.iter().map(|v| (item.as_str(), v)), out)
+            }
+            (DeclKind::Option(item), Value::Option(v)) => match v {
+                Some(v) => self.encode(item, v, out),
+                None => Ok(()),
+            },
+            (DeclKind::Union(items), Value::Union { id, value }) => {
+                let item = items
+                    .iter()
+                    .find(|item| item.id == *id)
+                    .ok_or(Error::UnknownUnionId { id: *id as usize })?;
+                out.extend(id.to_le_bytes());
+                self.encode(&item.ty, value, out)
+            }
+            (DeclKind::Table(fields), Value::Table(values)) => {
+                check_fields(name, fields, values)?;
+                let parts = fields
+                    .iter()
+                    .zip(values)
+                    .map(|(field, (_, v))| (field.ty.as_str(), v));
+                self.encode_table(parts, out)
+            }
+            (DeclKind::Vector(item), _) if self.schema.fixed_size(item).is_some() => {
+                Err(mismatched("fixvec", name, value))
+            }
+            (DeclKind::Vector(_), _) => Err(mismatched("dynvec", name, value)),
+            (DeclKind::Array { .. }, _) => Err(mismatched("array", name, value)),
+            (DeclKind::Struct(_), _) => Err(mismatched("struct", name, value)),
+            (DeclKind::Option(_), _) => Err(mismatched("option", name, value)),
+            (DeclKind::Union(_), _) => Err(mismatched("union", name, value)),
+            (DeclKind::Table(_), _) => Err(mismatched("table", name, value)),
+        }
+    }
+
+    // Encode the parts of a table or dynvec into `out` after the header, which
+    // is reserved first and patched when the sizes are known.
+    fn encode_table<'v>(
+        &self,
+        parts: impl ExactSizeIterator<Item = (&'v str, &'v Value)>,
+        out: &mut Vec<u8>,
+    ) -> Result<()> {
+        let start = out.len();
+        let header_size = parts
+            .len()
+            .checked_add(1)
+            .and_then(|n| n.checked_mul(NUMBER_SIZE))
+            .ok_or(Error::Overflow)?;
+        out.resize(start + header_size, 0);
+        let patch = |out: &mut Vec<u8>, index: usize| -> Result<()> {
+            let value = number(out.len() - start)?;
+            let at = start + index * NUMBER_SIZE;
+            out[at..at + NUMBER_SIZE].copy_from_slice(&value.to_le_bytes());
+            Ok(())
+        };
+        for (i, (name, value)) in parts.enumerate() {
+            patch(out, i + 1)?;
+            self.encode(name, value, out)?;
+        }
+        patch(out, 0)
+    }
+}
+
+const NUMBER_SIZE: usize = 4;
+
+fn number(n: usize) -> Result<u32> {
+    u32::try_from(n).map_err(|_| Error::Overflow)
+}
+
+// The offset of `part`, a sub-slice of `data` at `offset`.
+fn offset_of(data: &[u8], offset: usize, part: &[u8]) -> usize {
+    offset + (part.as_ptr() as usize - data.as_ptr() as usize)
+}
+
+fn mismatched(kind: &str, name: &str, value: &Value) -> Error {
+    Error::custom(format!(
+        "expected {} `{}`, found {}",
+        kind,
+        name,
+        value.kind()
+    ))
+}
+
+fn check_fields(name: &str, fields: &[Field], values: &[(String, Value)]) -> Result<()> {
+    if fields.len() != values.len() {
+        return Err(Error::custom(format!(
+            "`{}` has {} fields, found {}",
+            name,
+            fields.len(),
+            values.len()
+        )));
+    }
+    match fields.iter().zip(values).find(|(f, (n, _))| f.name != *n) {
+        Some((field, (found, _))) => Err(Error::custom(format!(
+            "expected field `{}` of `{}`, found `{}`",
+            field.name, name, found
+        ))),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,443 @@
+//! Validate molecule data for a type without deserializing it.
+//!
+//! It's the same as `verify(compatible)` of the readers generated by molecule:
+//! the bytes are walked following the [`Layout`] of the type, like
+//! [`from_slice_with`](crate::from_slice_with) does, but no value is built.
+use crate::de::DeserializerOptions;
+use crate::error::{Error, Result, Segment};
+use crate::molecule::{
+    fixvec_item_count, table_item, table_item_count, unpack_number, verify_table,
+};
+use crate::trace::{ContainerFormat, Format, Layout, Registry, VariantFormat};
+use alloc::format;
+use alloc::vec::Vec;
+use serde::de::{self, Error as _, Unexpected};
+
+/// Check that `v` can be deserialized into type `T` with `options`, without
+/// building the value.
+///
+/// The layout of `T` is traced on every call, see [`Layout::of`]. To verify
+/// a lot of data, trace it once and use [`Layout::verify`] instead.
+///
+/// ```
+/// use serde_molecule::{to_vec, verify, DeserializerOptions};
+///
+/// let bytes = to_vec(&vec![1u32, 2, 3], false).unwrap();
+/// let options = DeserializerOptions::new();
+/// assert!(verify::<Vec<u32>>(&bytes, &options).is_ok());
+/// assert!(verify::<Vec<u32>>(&bytes[..10], &options).is_err());
+/// ```
+pub fn verify<'de, T>(v: &[u8], options: &DeserializerOptions) -> Result<()>
+where
+    T: de::Deserialize<'de>,
+{
+    Layout::of::<T>()?.verify(v, options)
+}
+
+impl Layout {
+    /// Check that `v` can be deserialized into the traced type with
+    /// `options`, without building the value. The depth and item limits of
+    /// `options` are checked, the allocation budget isn't used.
+    ///
+    /// Values are only checked by their molecule encoding: e.g. a `NonZeroU32`
+    /// of 0, or an unsorted `BTreeMap` in canonical mode, passes the check
+    /// but fails in deserialization.
+    pub fn verify(&self, v: &[u8], options: &DeserializerOptions) -> Result<()> {
+        let mut verifier = Verifier {
+            registry: &self.registry,
+            options,
+            depth: 0,
+        };
+        if options.is_struct {
+            let mut index = 0;
+            verifier.verify_packed(&self.root, v, 0, &mut index)?;
+            verifier.end_packed(v, 0, index)
+        } else {
+            verifier.verify_value(&self.root, v, 0)
+        }
+    }
+}
+
+struct Verifier<'a> {
+    registry: &'a Registry,
+    options: &'a DeserializerOptions,
+    depth: usize,
+}
+
+impl<'a> Verifier<'a> {
+    // Same as `MoleculeDeserializer::nested`.
+    fn nested(&mut self, f: impl FnOnce(&mut Self) -> Result<()>) -> Result<()> {
+        if self.depth >= self.options.max_depth {
+            return Err(Error::DepthLimitExceeded);
+        }
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn check_items(&self, count: usize) -> Result<()> {
+        if count > self.options.max_items {
+            Err(Error::ItemLimitExceeded)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn container(&self, name: &str) -> Result<&'a ContainerFormat> {
+        self.registry
+            .get(name)
+            .ok_or_else(|| Error::custom(format!("`{}` isn't in the registry", name)))
+    }
+
+    // Verify `part`, a sub-slice of `data` at `offset`.
+    fn verify_part(
+        &mut self,
+        format: &Format,
+        data: &[u8],
+        offset: usize,
+        part: &[u8],
+        segment: Segment,
+    ) -> Result<()> {
+        let offset = offset + (part.as_ptr() as usize - data.as_ptr() as usize);
+        self.verify_value(format, part, offset)
+            .map_err(|e| e.at(offset, segment))
+    }
+
+    // Verify data in table mode, like `MoleculeDeserializer`.
+    fn verify_value(&mut self, format: &Format, data: &[u8], offset: usize) -> Result<()> {
+        match format {
+            Format::Unit => self.verify_unit(data, offset),
+            Format::Bool => {
+                exact(data, 1)?;
+                if self.options.canonical && data[0] > 1 {
+                    return Err(Error::NonCanonical { offset });
+                }
+                Ok(())
+            }
+            Format::U8 | Format::I8 => exact(data, 1),
+            Format::U16 | Format::I16 => exact(data, 2),
+            Format::U32 | Format::I32 | Format::F32 => exact(data, 4),
+            Format::U64 | Format::I64 | Format::F64 => exact(data, 8),
+            Format::U128 | Format::I128 => exact(data, 16),
+            Format::Char => {
+                exact(data, 4)?;
+                verify_char(data)
+            }
+            Format::Str => {
+                let s = bytes(data)?;
+                match core::str::from_utf8(s) {
+                    Ok(_) => Ok(()),
+                    Err(_) if self.options.lossy_utf8 && !self.options.canonical => Ok(()),
+                    Err(e) => Err(Error::InvalidUtf8 {
+                        offset: offset + 4 + e.valid_up_to(),
+                    }),
+                }
+            }
+            Format::Bytes => bytes(data).map(drop),
+            Format::Option(f) => {
+                if data.is_empty() {
+                    Ok(())
+                } else {
+                    self.nested(|v| v.verify_value(f, data, offset))
+                }
+            }
+            Format::Fixvec(f) => self.nested(|v| v.verify_fixvec(f, data, offset)),
+            Format::Dynvec(f) => self.nested(|v| {
+                v.check_items(table_item_count(data)?)?;
+                let count = verify_table(data)?;
+                for i in 0..count {
+                    v.verify_part(f, data, offset, table_item(data, i)?, Segment::Index(i))?;
+                }
+                Ok(())
+            }),
+            Format::Map(key, value) => self.nested(|v| v.verify_map(key, value, data, offset)),
+            Format::Tuple(fs) => self.nested(|v| {
+                if fs.is_empty() || data.len() % fs.len() != 0 {
+                    return Err(Error::InvalidArray);
+                }
+                let size = data.len() / fs.len();
+                for (i, f) in fs.iter().enumerate() {
+                    let part = &data[i * size..(i + 1) * size];
+                    v.verify_part(f, data, offset, part, Segment::Index(i))?;
+                }
+                Ok(())
+            }),
+            // `struct_serde` on `Option`: empty for `None`
+            Format::Struct(f) => {
+                let f = match &**f {
+                    Format::Option(_) if data.is_empty() => return Ok(()),
+                    Format::Option(f) => f,
+                    f => f,
+                };
+                let mut index = 0;
+                self.verify_packed(f, data, offset, &mut index)?;
+                self.end_packed(data, offset, index)
+            }
+            Format::Raw => Ok(()),
+            Format::TypeName(name) => match self.container(name)? {
+                ContainerFormat::UnitStruct => self.verify_unit(data, offset),
+                ContainerFormat::NewtypeStruct(f) => {
+                    self.nested(|v| v.verify_value(f, data, offset))
+                }
+                ContainerFormat::TupleStruct(fs) => self.nested(|v| {
+                    let fields = fs.iter().enumerate().map(|(i, f)| (Segment::Index(i), f));
+                    v.verify_table(fields, data, offset)
+                }),
+                ContainerFormat::Struct(fs) => self.nested(|v| {
+                    let fields = fs.iter().map(|(name, f)| (Segment::Field(name), f));
+                    v.verify_table(fields, data, offset)
+                }),
+                ContainerFormat::Enum(variants) => self.nested(|v| {
+                    let id = unpack_number(data, 0)?;
+                    let (name, variant) = variants.get(id).ok_or_else(|| {
+                        let expected = format!("variant index 0 <= i < {}", variants.len());
+                        Error::invalid_value(Unexpected::Unsigned(id as u64), &expected.as_str())
+                    })?;
+                    v.verify_variant(name, variant, data, offset)
+                }),
+                ContainerFormat::Union { variants, fallback } => self.nested(|v| {
+                    let id = unpack_number(data, 0)?;
+                    match variants.iter().find(|(i, _, _)| *i as usize == id) {
+                        Some((_, name, variant)) => v.verify_variant(name, variant, data, offset),
+                        // the body is kept undecoded
+                        None if fallback.is_some() => Ok(()),
+                        None => Err(Error::UnknownUnionId { id }),
+                    }
+                }),
+            },
+        }
+    }
+
+    fn verify_unit(&self, data: &[u8], offset: usize) -> Result<()> {
+        if self.options.canonical && !data.is_empty() {
+            return Err(Error::NonCanonical { offset });
+        }
+        Ok(())
+    }
+
+    // Same checks as `disassemble_fixvec`, elements are molecule structs.
+    fn verify_fixvec(&mut self, format: &Format, data: &[u8], offset: usize) -> Result<()> {
+        let count = fixvec_item_count(data)?;
+        self.check_items(count)?;
+        if count == 0 {
+            if self.options.canonical && data.len() != 4 {
+                return Err(Error::NonCanonical { offset: offset + 4 });
+            }
+            return Ok(());
+        }
+        let remaining = data.len() - 4;
+        if remaining == 0 || remaining % count != 0 {
+            return Err(Error::InvalidFixvec);
+        }
+        let size = remaining / count;
+        for i in 0..count {
+            let start = 4 + i * size;
+            let part = &data[start..start + size];
+            let mut index = 0;
+            self.verify_packed(format, part, offset + start, &mut index)
+                .and_then(|_| self.end_packed(part, offset + start, index))
+                .map_err(|e| e.at(offset + start, Segment::Index(i)))?;
+        }
+        Ok(())
+    }
+
+    fn verify_map(
+        &mut self,
+        key: &Format,
+        value: &Format,
+        data: &[u8],
+        offset: usize,
+    ) -> Result<()> {
+        self.check_items(table_item_count(data)?)?;
+        let count = verify_table(data)?;
+        let mut entries = Vec::new();
+        for i in 0..count {
+            let entry = table_item(data, i)?;
+            if verify_table(entry)? != 2 {
+                return Err(Error::InvalidMap);
+            }
+            entries.push((table_item(entry, 0)?, table_item(entry, 1)?));
+        }
+        if self.options.canonical {
+            let mut keys: Vec<&[u8]> = entries.iter().map(|(k, _)| *k).collect();
+            keys.sort_unstable();
+            if let Some(w) = keys.windows(2).find(|w| w[0] == w[1]) {
+                return Err(Error::NonCanonical {
+                    offset: offset + (w[1].as_ptr() as usize - data.as_ptr() as usize),
+                });
+            }
+        }
+        for (i, (k, v)) in entries.into_iter().enumerate() {
+            self.verify_part(key, data, offset, k, Segment::Index(i))?;
+            self.verify_part(value, data, offset, v, Segment::Index(i))?;
+        }
+        Ok(())
+    }
+
+    // Same checks as `TableAccess`.
+    fn verify_table<'f>(
+        &mut self,
+        fields: impl ExactSizeIterator<Item = (Segment, &'f Format)>,
+        data: &[u8],
+        offset: usize,
+    ) -> Result<()> {
+        self.check_items(table_item_count(data)?)?;
+        let count = verify_table(data)?;
+        let exact = !self.options.compatible || self.options.canonical;
+        if count < fields.len() || (exact && count != fields.len()) {
+            return Err(Error::MismatchedTableFieldCount);
+        }
+        for (i, (segment, f)) in fields.enumerate() {
+            self.verify_part(f, data, offset, table_item(data, i)?, segment)?;
+        }
+        Ok(())
+    }
+
+    // Verify the body of union `data` with the variant of its id.
+    fn verify_variant(
+        &mut self,
+        name: &'static str,
+        variant: &VariantFormat,
+        data: &[u8],
+        offset: usize,
+    ) -> Result<()> {
+        let body = &data[4..];
+        let offset = offset + 4;
+        let result = match variant {
+            VariantFormat::Unit => self.verify_unit(body, offset),
+            VariantFormat::Newtype(f) => self.verify_value(f, body, offset),
+            VariantFormat::Tuple(fs) => self.nested(|v| {
+                let fields = fs.iter().enumerate().map(|(i, f)| (Segment::Index(i), f));
+                v.verify_table(fields, body, offset)
+            }),
+            VariantFormat::Struct(fs) => self.nested(|v| {
+                let fields = fs.iter().map(|(name, f)| (Segment::Field(name), f));
+                v.verify_table(fields, body, offset)
+            }),
+        };
+        result.map_err(|e| e.at(offset, Segment::Field(name)))
+    }
+
+    // Verify data in struct mode from `index`, like `MoleculeStructDeserializer`.
+    fn verify_packed(
+        &mut self,
+        format: &Format,
+        data: &[u8],
+        offset: usize,
+        index: &mut usize,
+    ) -> Result<()> {
+        match format {
+            Format::Bool => {
+                let value = take(data, index, 1)?[0];
+                if self.options.canonical && value > 1 {
+                    return Err(Error::NonCanonical {
+                        offset: offset + *index - 1,
+                    });
+                }
+                Ok(())
+            }
+            Format::U8 | Format::I8 => take(data, index, 1).map(drop),
+            Format::U16 | Format::I16 => take(data, index, 2).map(drop),
+            Format::U32 | Format::I32 | Format::F32 => take(data, index, 4).map(drop),
+            Format::U64 | Format::I64 | Format::F64 => take(data, index, 8).map(drop),
+            Format::U128 | Format::I128 => take(data, index, 16).map(drop),
+            Format::Char => verify_char(take(data, index, 4)?),
+            Format::Tuple(fs) => {
+                let fields = fs.iter().enumerate().map(|(i, f)| (Segment::Index(i), f));
+                self.verify_packed_fields(fields, data, offset, index)
+            }
+            Format::Struct(f) => self.verify_packed(f, data, offset, index),
+            Format::Raw => {
+                *index = data.len();
+                Ok(())
+            }
+            Format::TypeName(name) => match self.container(name)? {
+                ContainerFormat::NewtypeStruct(f) => self.verify_packed(f, data, offset, index),
+                ContainerFormat::TupleStruct(fs) => {
+                    let fields = fs.iter().enumerate().map(|(i, f)| (Segment::Index(i), f));
+                    self.verify_packed_fields(fields, data, offset, index)
+                }
+                ContainerFormat::Struct(fs) => {
+                    let fields = fs.iter().map(|(name, f)| (Segment::Field(name), f));
+                    self.verify_packed_fields(fields, data, offset, index)
+                }
+                ContainerFormat::UnitStruct
+                | ContainerFormat::Enum(_)
+                | ContainerFormat::Union { .. } => Err(Error::InvalidStructField),
+            },
+            Format::Unit
+            | Format::Str
+            | Format::Bytes
+            | Format::Option(_)
+            | Format::Fixvec(_)
+            | Format::Dynvec(_)
+            | Format::Map(_, _) => Err(Error::InvalidStructField),
+        }
+    }
+
+    fn verify_packed_fields<'f>(
+        &mut self,
+        fields: impl Iterator<Item = (Segment, &'f Format)>,
+        data: &[u8],
+        offset: usize,
+        index: &mut usize,
+    ) -> Result<()> {
+        for (segment, f) in fields {
+            let start = offset + *index;
+            self.verify_packed(f, data, offset, index)
+                .map_err(|e| e.at(start, segment))?;
+        }
+        Ok(())
+    }
+
+    // Same as `MoleculeStructDeserializer::end`.
+    fn end_packed(&self, data: &[u8], offset: usize, index: usize) -> Result<()> {
+        if self.options.canonical && index != data.len() {
+            return Err(Error::NonCanonical {
+                offset: offset + index,
+            });
+        }
+        Ok(())
+    }
+}
+
+fn exact(data: &[u8], len: usize) -> Result<()> {
+    if data.len() != len {
+        return Err(Error::MismatchedLength {
+            expected: len,
+            actual: data.len(),
+        });
+    }
+    Ok(())
+}
+
+fn take<'a>(data: &'a [u8], index: &mut usize, len: usize) -> Result<&'a [u8]> {
+    if data.len() < *index + len {
+        return Err(Error::MismatchedLength {
+            expected: len,
+            actual: data.len() - *index,
+        });
+    }
+    let bytes = &data[*index..*index + len];
+    *index += len;
+    Ok(bytes)
+}
+
+fn verify_char(data: &[u8]) -> Result<()> {
+    let value = u32::from_le_bytes(data.try_into().unwrap());
+    match char::from_u32(value) {
+        Some(_) => Ok(()),
+        None => Err(Error::InvalidChar),
+    }
+}
+
+// fixvec with element size = 1
+fn bytes(data: &[u8]) -> Result<&[u8]> {
+    let item_count = unpack_number(data, 0)?;
+    if item_count != data.len() - 4 {
+        Err(Error::InvalidFixvec)
+    } else {
+        Ok(&data[4..])
+    }
+}
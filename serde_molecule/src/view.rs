@@ -0,0 +1,251 @@
+//! Lazy views of molecule tables, dynvecs and fixvecs.
+//!
+//! Deserialization builds the whole value, and a `Vec` of the parts of every
+//! table and vector on the way. A view validates its header once when it's
+//! created, then reads the offsets of an item only when the item is accessed:
+//! as a sub-slice of the data, as a nested view, or deserialized into `T`.
+//! Nothing is allocated, which suits memory-limited scenarios like on-chain
+//! scripts.
+//!
+//! Only the header of a view is validated, an item is checked when it's
+//! viewed or deserialized. Since newer schemas may append fields to tables,
+//! a table view accepts any field count, see [`TableView::with_count`]. The
+//! offsets in errors are relative to the viewed data.
+//!
+//! ```
+//! use serde_molecule::to_vec;
+//! use serde_molecule::view::TableView;
+//!
+//! #[derive(serde::Serialize)]
+//! struct Script {
+//!     code_hash: [u8; 32],
+//!     hash_type: u8,
+//!     args: Vec<u8>,
+//! }
+//!
+//! #[derive(serde::Serialize)]
+//! struct CellOutput {
+//!     capacity: u64,
+//!     lock: Script,
+//!     type_: Option<Script>,
+//! }
+//!
+//! let output = CellOutput {
+//!     capacity: 100,
+//!     lock: Script {
+//!         code_hash: [1; 32],
+//!         hash_type: 1,
+//!         args: vec![2, 3],
+//!     },
+//!     type_: None,
+//! };
+//! let bytes = to_vec(&output, false).unwrap();
+//! let view = TableView::with_count(&bytes, 3).unwrap();
+//! assert_eq!(view.get::<u64>(0, false).unwrap(), 100);
+//! let lock = view.table(1).unwrap();
+//! assert_eq!(lock.item(0).unwrap(), &[1; 32]);
+//! assert_eq!(lock.fixvec(2, 1).unwrap().body(), &[2, 3]);
+//! assert!(view.item(2).unwrap().is_empty());
+//! assert!(view.item(3).is_err());
+//! ```
+use crate::de::from_slice;
+use crate::error::{Error, Result, Segment};
+use crate::molecule::{unpack_number, verify_table};
+use core::ops::Range;
+use serde::de;
+
+macro_rules! table_view {
+    ($(#[$attr:meta])* $name:ident) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy, Debug)]
+        pub struct $name<'a> {
+            data: &'a [u8],
+            len: usize,
+        }
+
+        impl<'a> $name<'a> {
+            /// Validate the header of `data`: the full size and the offsets.
+            pub fn new(data: &'a [u8]) -> Result<Self> {
+                let len = verify_table(data)?;
+                Ok(Self { data, len })
+            }
+
+            /// The number of items.
+            pub fn len(&self) -> usize {
+                self.len
+            }
+
+            pub fn is_empty(&self) -> bool {
+                self.len == 0
+            }
+
+            /// The viewed data, with the header.
+            pub fn as_slice(&self) -> &'a [u8] {
+                self.data
+            }
+
+            /// The bytes of the `index`-th item.
+            pub fn item(&self, index: usize) -> Result<&'a [u8]> {
+                Ok(&self.data[self.range(index)?])
+            }
+
+            /// Deserialize the `index`-th item into `T`, `is_struct` is the
+            /// same as [`from_slice`].
+            pub fn get<T>(&self, index: usize, is_struct: bool) -> Result<T>
+            where
+                T: de::Deserialize<'a>,
+            {
+                let range = self.range(index)?;
+                from_slice(&self.data[range.clone()], is_struct)
+                    .map_err(|e| e.at(range.start, Segment::Index(index)))
+            }
+
+            /// View the `index`-th item as a table.
+            pub fn table(&self, index: usize) -> Result<TableView<'a>> {
+                let range = self.range(index)?;
+                TableView::new(&self.data[range.clone()])
+                    .map_err(|e| e.at(range.start, Segment::Index(index)))
+            }
+
+            /// View the `index`-th item as a dynvec.
+            pub fn dynvec(&self, index: usize) -> Result<DynvecView<'a>> {
+                let range = self.range(index)?;
+                DynvecView::new(&self.data[range.clone()])
+                    .map_err(|e| e.at(range.start, Segment::Index(index)))
+            }
+
+            /// View the `index`-th item as a fixvec of `item_size` bytes items.
+            pub fn fixvec(&self, index: usize, item_size: usize) -> Result<FixvecView<'a>> {
+                let range = self.range(index)?;
+                FixvecView::new(&self.data[range.clone()], item_size)
+                    .map_err(|e| e.at(range.start, Segment::Index(index)))
+            }
+
+            /// The bytes of the items.
+            pub fn iter(&self) -> impl Iterator<Item = &'a [u8]> + 'a {
+                let view = *self;
+                (0..self.len).map(move |index| &view.data[view.bounds(index)])
+            }
+
+            fn range(&self, index: usize) -> Result<Range<usize>> {
+                if index >= self.len {
+                    return Err(Error::IndexOutOfBounds {
+                        index,
+                        len: self.len,
+                    });
+                }
+                Ok(self.bounds(index))
+            }
+
+            // The offsets are in the header validated by `new`.
+            fn bounds(&self, index: usize) -> Range<usize> {
+                let offset = |i: usize| {
+                    let bytes = self.data[i * 4..i * 4 + 4].try_into().unwrap();
+                    u32::from_le_bytes(bytes) as usize
+                };
+                let end = if index + 1 == self.len {
+                    self.data.len()
+                } else {
+                    offset(index + 2)
+                };
+                offset(index + 1)..end
+            }
+        }
+    };
+}
+
+table_view!(
+    /// A view of molecule table, the items are the fields.
+    TableView
+);
+
+table_view!(
+    /// A view of molecule dynvec.
+    DynvecView
+);
+
+impl<'a> TableView<'a> {
+    /// Validate the header of `data` with at least `count` fields. More fields
+    /// are allowed, they are appended by newer schemas.
+    pub fn with_count(data: &'a [u8], count: usize) -> Result<Self> {
+        let view = Self::new(data)?;
+        if view.len < count {
+            return Err(Error::MismatchedTableFieldCount);
+        }
+        Ok(view)
+    }
+}
+
+/// A view of molecule fixvec, with items of the same size.
+#[derive(Clone, Copy, Debug)]
+pub struct FixvecView<'a> {
+    data: &'a [u8],
+    len: usize,
+    item_size: usize,
+}
+
+impl<'a> FixvecView<'a> {
+    /// Validate the header of `data`: the item count must match the length.
+    pub fn new(data: &'a [u8], item_size: usize) -> Result<Self> {
+        let len = unpack_number(data, 0)?;
+        let size = len
+            .checked_mul(item_size)
+            .and_then(|size| size.checked_add(4))
+            .ok_or(Error::Overflow)?;
+        if size != data.len() {
+            return Err(Error::InvalidFixvec);
+        }
+        Ok(FixvecView {
+            data,
+            len,
+            item_size,
+        })
+    }
+
+    /// The number of items.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The viewed data, with the item count.
+    pub fn as_slice(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// The items without the item count, e.g. the content of `Bytes`.
+    pub fn body(&self) -> &'a [u8] {
+        &self.data[4..]
+    }
+
+    /// The bytes of the `index`-th item.
+    pub fn item(&self, index: usize) -> Result<&'a [u8]> {
+        if index >= self.len {
+            return Err(Error::IndexOutOfBounds {
+                index,
+                len: self.len,
+            });
+        }
+        let start = 4 + index * self.item_size;
+        Ok(&self.data[start..start + self.item_size])
+    }
+
+    /// Deserialize the `index`-th item into `T`, as molecule struct.
+    pub fn get<T>(&self, index: usize) -> Result<T>
+    where
+        T: de::Deserialize<'a>,
+    {
+        let item = self.item(index)?;
+        let offset = 4 + index * self.item_size;
+        from_slice(item, true).map_err(|e| e.at(offset, Segment::Index(index)))
+    }
+
+    /// The bytes of the items.
+    pub fn iter(&self) -> impl Iterator<Item = &'a [u8]> + 'a {
+        let (data, size) = (self.data, self.item_size);
+        (0..self.len).map(move |index| &data[4 + index * size..4 + (index + 1) * size])
+    }
+}
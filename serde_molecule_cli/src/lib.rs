@@ -0,0 +1,260 @@
+//! The `serde-molecule` command: decode, encode and verify molecule data with
+//! a `.mol` schema, without writing Rust types.
+//!
+//! ```text
+//! serde-molecule decode -s blockchain.mol -t WitnessArgs --hex 0x10000000...
+//! serde-molecule encode -s blockchain.mol -t WitnessArgs --json '{"lock": null, ...}'
+//! serde-molecule verify -s blockchain.mol -t Transaction --file tx.bin --compatible
+//! serde-molecule layout -s blockchain.mol -t Transaction --file tx.bin
+//! serde-molecule guess --hex 0x55000000...
+//! ```
+//!
+//! The input is read from standard input if neither `--hex` nor `--file` is
+//! given: hex for `decode`, `verify`, `layout` and `guess`, JSON for `encode`.
+use clap::{Args, Parser, Subcommand};
+use serde::de::Error as _;
+use serde_molecule::guess::guess;
+use serde_molecule::inspect::inspect_with_schema;
+use serde_molecule::schema::Schema;
+use serde_molecule::transcode::{from_json, to_json, to_json_compatible};
+use serde_molecule::{from_compatible_slice_with_schema, from_slice_with_schema, Error, Result};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(name = "serde-molecule", version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Decode molecule data to JSON.
+    Decode {
+        #[command(flatten)]
+        target: Target,
+        #[command(flatten)]
+        input: Input,
+        /// Allow extra fields in tables, appended by newer schemas.
+        #[arg(long)]
+        compatible: bool,
+    },
+    /// Encode JSON to molecule data, printed in hex.
+    Encode {
+        #[command(flatten)]
+        target: Target,
+        /// The JSON text.
+        #[arg(long, conflicts_with = "file")]
+        json: Option<String>,
+        /// Read the JSON text from a file.
+        #[arg(long)]
+        file: Option<PathBuf>,
+    },
+    /// Check that molecule data is valid for the type.
+    Verify {
+        #[command(flatten)]
+        target: Target,
+        #[command(flatten)]
+        input: Input,
+        /// Allow extra fields in tables, appended by newer schemas.
+        #[arg(long)]
+        compatible: bool,
+    },
+    /// Print the offset and size of every part of molecule data, with the
+    /// headers of tables and vectors.
+    Layout {
+        #[command(flatten)]
+        target: Target,
+        #[command(flatten)]
+        input: Input,
+        /// Print a hexdump annotated with the parts.
+        #[arg(long)]
+        hexdump: bool,
+    },
+    /// Guess the structure of molecule data without a schema.
+    Guess {
+        #[command(flatten)]
+        input: Input,
+        /// The number of guesses to print, the most plausible first.
+        #[arg(long, default_value_t = 3)]
+        limit: usize,
+        /// Print hexdumps annotated with the parts.
+        #[arg(long)]
+        hexdump: bool,
+    },
+}
+
+#[derive(Args, Debug)]
+pub struct Target {
+    /// The `.mol` schema file, imports are loaded relative to it.
+    #[arg(short, long)]
+    pub schema: PathBuf,
+    /// The type name in the schema.
+    #[arg(short = 't', long = "type")]
+    pub ty: String,
+}
+
+#[derive(Args, Debug)]
+pub struct Input {
+    /// The data in hex, with or without `0x` prefix.
+    #[arg(long, conflicts_with = "file")]
+    pub hex: Option<String>,
+    /// Read the raw data from a file.
+    #[arg(long)]
+    pub file: Option<PathBuf>,
+}
+
+impl Target {
+    fn load(&self) -> Result<Schema> {
+        let schema = Schema::load(&self.schema)?;
+        if schema.get(&self.ty).is_none() && self.ty != "byte" {
+            return Err(Error::custom(format!(
+                "`{}` isn't defined in `{}`",
+                self.ty,
+                self.schema.display()
+            )));
+        }
+        Ok(schema)
+    }
+}
+
+impl Input {
+    fn read(&self, stdin: &mut dyn Read) -> Result<Vec<u8>> {
+        match (&self.hex, &self.file) {
+            (Some(hex), _) => parse_hex(hex),
+            (None, Some(file)) => std::fs::read(file).map_err(Error::Io),
+            (None, None) => parse_hex(&read_string(stdin)?),
+        }
+    }
+}
+
+/// Run the command, `stdin` is used if the input isn't given by arguments.
+pub fn run(cli: &Cli, stdin: &mut dyn Read, stdout: &mut dyn Write) -> Result<()> {
+    match &cli.command {
+        Command::Decode {
+            target,
+            input,
+            compatible,
+        } => {
+            let schema = target.load()?;
+            let data = input.read(stdin)?;
+            let json = if *compatible {
+                to_json_compatible(&data, &schema, &target.ty)?
+            } else {
+                to_json(&data, &schema, &target.ty)?
+            };
+            let text = serde_json::to_string_pretty(&json).map_err(Error::custom)?;
+            writeln!(stdout, "{}", text).map_err(Error::Io)
+        }
+        Command::Encode { target, json, file } => {
+            let schema = target.load()?;
+            let text = match (json, file) {
+                (Some(json), _) => json.clone(),
+                (None, Some(file)) => std::fs::read_to_string(file).map_err(Error::Io)?,
+                (None, None) => read_string(stdin)?,
+            };
+            let json = serde_json::from_str(&text)
+                .map_err(|e| Error::custom(format!("invalid JSON: {}", e)))?;
+            let data = from_json(&json, &schema, &target.ty)?;
+            writeln!(stdout, "0x{}", to_hex(&data)).map_err(Error::Io)
+        }
+        Command::Verify {
+            target,
+            input,
+            compatible,
+        } => {
+            let schema = target.load()?;
+            let data = input.read(stdin)?;
+            if *compatible {
+                from_compatible_slice_with_schema(&data, &schema, &target.ty)?;
+            } else {
+                from_slice_with_schema(&data, &schema, &target.ty)?;
+            }
+            writeln!(stdout, "ok").map_err(Error::Io)
+        }
+        Command::Layout {
+            target,
+            input,
+            hexdump,
+        } => {
+            let schema = target.load()?;
+            let data = input.read(stdin)?;
+            let node = inspect_with_schema(&data, &schema, &target.ty)?;
+            if *hexdump {
+                write!(stdout, "{}", node.hexdump(&data)).map_err(Error::Io)?;
+            } else {
+                writeln!(stdout, "offset   size  type").map_err(Error::Io)?;
+                write!(stdout, "{}", node).map_err(Error::Io)?;
+            }
+            match node.first_error() {
+                Some(node) => Err(Error::custom(format!(
+                    "`{}` at offset {} is malformed",
+                    node.label, node.range.start
+                ))),
+                None => Ok(()),
+            }
+        }
+        Command::Guess {
+            input,
+            limit,
+            hexdump,
+        } => {
+            let data = input.read(stdin)?;
+            for (i, guess) in guess(&data, *limit).iter().enumerate() {
+                if i > 0 {
+                    writeln!(stdout).map_err(Error::Io)?;
+                }
+                writeln!(
+                    stdout,
+                    "guess {}: confidence {:.1}%, score {} bits",
+                    i + 1,
+                    guess.confidence * 100.0,
+                    guess.score
+                )
+                .map_err(Error::Io)?;
+                if *hexdump {
+                    write!(stdout, "{}", guess.node.hexdump(&data)).map_err(Error::Io)?;
+                } else {
+                    writeln!(stdout, "offset   size  type").map_err(Error::Io)?;
+                    write!(stdout, "{}", guess.node).map_err(Error::Io)?;
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+fn read_string(stdin: &mut dyn Read) -> Result<String> {
+    let mut text = String::new();
+    stdin.read_to_string(&mut text).map_err(Error::Io)?;
+    Ok(text)
+}
+
+fn parse_hex(text: &str) -> Result<Vec<u8>> {
+    let text: String = text.split_whitespace().collect();
+    let hex = text.strip_prefix("0x").unwrap_or(&text).as_bytes();
+    if hex.len() % 2 != 0 {
+        return Err(Error::custom("odd number of hex digits"));
+    }
+    let digit = |c: u8| {
+        (c as char)
+            .to_digit(16)
+            .ok_or_else(|| Error::custom(format!("invalid hex digit `{}`", c as char)))
+    };
+    hex.chunks(2)
+        .map(|pair| Ok(((digit(pair[0])? << 4) | digit(pair[1])?) as u8))
+        .collect()
+}
+
+fn to_hex(data: &[u8]) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    data.iter()
+        .flat_map(|b| {
+            [
+                DIGITS[(b >> 4) as usize] as char,
+                DIGITS[(b & 0xf) as usize] as char,
+            ]
+        })
+        .collect()
+}
@@ -0,0 +1,10 @@
+use clap::Parser;
+use serde_molecule_cli::{run, Cli};
+
+fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = run(&cli, &mut std::io::stdin(), &mut std::io::stdout()) {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,657 @@
+//! Derive macros for [serde_molecule](https://docs.rs/serde_molecule).
+//!
+//! Use them through `serde_molecule` with the `derive` feature, e.g.
+//! `serde_molecule::molecule`.
+use proc_macro::TokenStream;
+use proc_macro2::{Literal, TokenTree};
+use quote::{format_ident, quote, quote_spanned};
+use syn::parse::{Parse, ParseStream};
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, parse_quote, parse_quote_spanned, Attribute, Data, DataEnum, DeriveInput,
+    Error, Field, Fields, GenericArgument, Ident, Lit, LitInt, PathArguments, Token, Type,
+};
+
+// Same as `STRUCT_PREFIX` in serde_molecule: the serializer and deserializer
+// switch to molecule struct for types with this prefix in their names.
+const STRUCT_PREFIX: &str = "$serde_molecule::Struct::";
+// Same as `UNION_PREFIX` in serde_molecule: followed by the enum name and the
+// union ids of the variants.
+const UNION_PREFIX: &str = "$serde_molecule::Union::";
+
+const EXPECTED: &str = "expected `struct`, `table` or `union`";
+
+enum Kind {
+    Struct,
+    Table,
+    Union,
+}
+
+impl Parse for Kind {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let kind = if input.peek(Token![struct]) {
+            input.parse::<Token![struct]>()?;
+            Kind::Struct
+        } else {
+            let ident: Ident = input.parse()?;
+            if ident == "table" {
+                Kind::Table
+            } else if ident == "union" {
+                Kind::Union
+            } else {
+                return Err(Error::new(ident.span(), EXPECTED));
+            }
+        };
+        if !input.is_empty() {
+            return Err(input.error(EXPECTED));
+        }
+        Ok(kind)
+    }
+}
+
+/// Declare that a type is a molecule struct, table or union, and implement
+/// `MoleculeKind` for it.
+///
+/// `#[molecule(struct)]` renames the type for serde with a name the molecule
+/// serializer and deserializer recognize, so the type is always encoded as a
+/// molecule struct: as the root value, as a field of tables, or in vectors.
+/// It must be placed before `#[derive(Serialize, Deserialize)]`.
+///
+/// `#[molecule(struct)]` also implements `FixedSize`, the sum of the sizes of
+/// the fields. Every field, except the ones with `#[serde(skip)]`, must
+/// implement `FixedSize`, otherwise it fails to compile.
+///
+/// `#[molecule(table)]` keeps the default encoding, a molecule table.
+///
+/// `#[molecule(union)]` is for enums. The union id of a variant is given by
+/// `#[molecule(union_id = N)]` on the variant, or it's the id of the previous
+/// variant plus one, starting from 0. Like `#[molecule(struct)]`, the ids are
+/// passed in the serde name of the enum, so it must be placed before
+/// `#[derive(Serialize, Deserialize)]`.
+///
+/// A variant with `#[molecule(fallback)]` takes all unknown union ids. It must
+/// have two fields, the `u32` union id and the body, e.g.
+/// `Unknown { id: u32, body: Vec<u8> }`. The body is kept undecoded and
+/// serialized as it is.
+#[proc_macro_attribute]
+pub fn molecule(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let kind = parse_macro_input!(attr as Kind);
+    let mut input = parse_macro_input!(item as DeriveInput);
+    expand(kind, &mut input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// Derive `MoleculeReader`: generate a zero-copy reader of the type, named
+/// after it with a `Reader` suffix, e.g. `ScriptReader<'a>`.
+///
+/// * For a struct with named fields, the reader has an accessor method for
+///   every field, except the ones with `#[serde(skip)]`. It's a molecule
+///   struct if the type is marked by `#[molecule(struct)]`, otherwise a
+///   molecule table. `Vec` fields with `dynvec_serde` are read as dynvec,
+///   fields with `struct_serde` are rejected: mark their types instead.
+/// * For a newtype struct, the reader is the one of the inner type.
+/// * For an enum, the reader is an enum with the readers of the newtype
+///   variants, the unit variants, and the union id and the body for the
+///   fallback variant. Union ids are given by `#[molecule(union)]`, or they
+///   are the variant indexes.
+///
+/// `#[molecule]` must be placed before the derive.
+#[proc_macro_derive(MoleculeReader)]
+pub fn derive_molecule_reader(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_reader(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(kind: Kind, input: &mut DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let (is_struct, fixed_size) = match kind {
+        Kind::Struct => {
+            let fields = struct_fields(input)?;
+            if let Fields::Unit = fields {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "a molecule struct must have fields",
+                ));
+            }
+            let fixed_size = fixed_size(input, fields);
+            let name = format!("{}{}", STRUCT_PREFIX, input.ident);
+            rename(input, &name)?;
+            (true, Some(fixed_size))
+        }
+        Kind::Table => {
+            struct_fields(input)?;
+            (false, None)
+        }
+        Kind::Union => {
+            let ids = match &mut input.data {
+                Data::Enum(data) => union_ids(data)?,
+                _ => {
+                    return Err(Error::new_spanned(
+                        &input.ident,
+                        "`#[molecule(union)]` only supports enums",
+                    ))
+                }
+            };
+            let name = format!("{}{}::{}", UNION_PREFIX, input.ident, ids.join(","));
+            rename(input, &name)?;
+            (false, None)
+        }
+    };
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        #input
+
+        impl #impl_generics ::serde_molecule::MoleculeKind for #ident #ty_generics #where_clause {
+            const IS_STRUCT: bool = #is_struct;
+        }
+
+        #fixed_size
+    })
+}
+
+fn struct_fields(input: &DeriveInput) -> syn::Result<&Fields> {
+    match &input.data {
+        Data::Struct(data) => Ok(&data.fields),
+        _ => Err(Error::new_spanned(
+            &input.ident,
+            "`#[molecule(struct)]` and `#[molecule(table)]` only support structs",
+        )),
+    }
+}
+
+// Give the type a serde name recognized by the molecule serializer and
+// deserializer.
+fn rename(input: &mut DeriveInput, name: &str) -> syn::Result<()> {
+    check_derive_attrs(input)?;
+    check_serde_attrs(input)?;
+    input.attrs.push(parse_quote!(#[serde(rename = #name)]));
+    Ok(())
+}
+
+// Collect the union ids of variants, `*` for the fallback variant, and
+// remove their `#[molecule]` attributes.
+fn union_ids(data: &mut DataEnum) -> syn::Result<Vec<String>> {
+    let mut ids: Vec<u32> = vec![];
+    let mut result = vec![];
+    let mut has_fallback = false;
+    for variant in &mut data.variants {
+        let mut id = match ids.last() {
+            None => Some(0),
+            Some(last) => last.checked_add(1),
+        };
+        let mut fallback = false;
+        let mut parsed = Ok(());
+        variant.attrs.retain(|attr| {
+            if !attr.path().is_ident("molecule") {
+                return true;
+            }
+            let r = attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("union_id") {
+                    let lit: LitInt = meta.value()?.parse()?;
+                    id = Some(lit.base10_parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("fallback") {
+                    fallback = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `union_id = N` or `fallback`"))
+                }
+            });
+            if parsed.is_ok() {
+                parsed = r;
+            }
+            false
+        });
+        parsed?;
+        if fallback {
+            if has_fallback {
+                return Err(Error::new_spanned(
+                    &variant.ident,
+                    "only one variant can be the fallback",
+                ));
+            }
+            check_fallback(&variant.fields)
+                .map_err(|msg| Error::new_spanned(&variant.ident, msg))?;
+            has_fallback = true;
+            result.push("*".to_string());
+            continue;
+        }
+        let id = id.ok_or_else(|| Error::new_spanned(&variant.ident, "union id overflows"))?;
+        if ids.contains(&id) {
+            return Err(Error::new_spanned(
+                &variant.ident,
+                format!("duplicated union id {}", id),
+            ));
+        }
+        ids.push(id);
+        result.push(id.to_string());
+    }
+    Ok(result)
+}
+
+// The fallback variant has two fields: the union id and the body, e.g.
+// `Unknown { id: u32, body: Vec<u8> }`.
+fn check_fallback(fields: &Fields) -> Result<(), &'static str> {
+    let mut fields = fields.iter();
+    match (fields.next(), fields.next(), fields.next()) {
+        (Some(id), Some(_), None) => match &id.ty {
+            Type::Path(path) if path.path.is_ident("u32") => Ok(()),
+            _ => Err("the union id of the fallback variant must be `u32`"),
+        },
+        _ => Err("the fallback variant must have two fields: the union id and the body"),
+    }
+}
+
+// The `FixedSize` impl of a molecule struct. Fields which aren't fixed size
+// fail the bounds on their own types.
+fn fixed_size(input: &DeriveInput, fields: &Fields) -> proc_macro2::TokenStream {
+    let ident = &input.ident;
+    let mut generics = input.generics.clone();
+    let mut sizes = vec![];
+    let where_clause = generics.make_where_clause();
+    for field in fields.iter().filter(|f| !is_skipped(&f.attrs)) {
+        let ty = &field.ty;
+        where_clause
+            .predicates
+            .push(parse_quote_spanned!(ty.span()=> #ty: ::serde_molecule::FixedSize));
+        sizes.push(quote_spanned! {ty.span()=>
+            <#ty as ::serde_molecule::FixedSize>::SIZE
+        });
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote! {
+        impl #impl_generics ::serde_molecule::FixedSize for #ident #ty_generics #where_clause {
+            const SIZE: usize = 0 #(+ #sizes)*;
+        }
+    }
+}
+
+fn is_skipped(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        attr.path().is_ident("serde")
+            && attr.meta.require_list().is_ok_and(|list| {
+                list.tokens
+                    .clone()
+                    .into_iter()
+                    .any(|token| matches!(token, TokenTree::Ident(ident) if ident == "skip"))
+            })
+    })
+}
+
+// The serde derives must come after `#[molecule]` to see the new name. Once
+// they are expanded, their attribute is gone.
+fn check_derive_attrs(input: &DeriveInput) -> syn::Result<()> {
+    for attr in &input.attrs {
+        if !attr.path().is_ident("derive") {
+            continue;
+        }
+        let list = attr.meta.require_list()?;
+        for token in list.tokens.clone() {
+            if let TokenTree::Ident(ident) = token {
+                if ident == "Serialize" || ident == "Deserialize" {
+                    return Ok(());
+                }
+            }
+        }
+    }
+    Err(Error::new_spanned(
+        &input.ident,
+        "`#[molecule]` must be placed before `#[derive(Serialize, Deserialize)]`",
+    ))
+}
+
+// The marker is carried by the serde name of the type: it can't be renamed
+// again, and must not be transparent.
+fn check_serde_attrs(input: &DeriveInput) -> syn::Result<()> {
+    for attr in &input.attrs {
+        if !attr.path().is_ident("serde") {
+            continue;
+        }
+        let list = attr.meta.require_list()?;
+        for token in list.tokens.clone() {
+            if let TokenTree::Ident(ident) = token {
+                if ident == "rename" || ident == "transparent" {
+                    return Err(Error::new_spanned(
+                        ident,
+                        "`#[molecule]` can't be used with this serde attribute",
+                    ));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn expand_reader(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "`MoleculeReader` doesn't support generic types",
+        ));
+    }
+    let name = serde_value(&input.attrs, "rename");
+    match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => {
+                let fields: Vec<_> = fields
+                    .named
+                    .iter()
+                    .filter(|f| !is_skipped(&f.attrs))
+                    .collect();
+                if name.is_some_and(|name| name.starts_with(STRUCT_PREFIX)) {
+                    struct_reader(input, &fields)
+                } else {
+                    table_reader(input, &fields)
+                }
+            }
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                newtype_reader(input, &fields.unnamed[0])
+            }
+            _ => Err(Error::new_spanned(
+                &input.ident,
+                "`MoleculeReader` supports structs with named fields and newtype structs",
+            )),
+        },
+        Data::Enum(data) => {
+            let ids: Vec<Option<usize>> = match name
+                .as_deref()
+                .and_then(|name| name.strip_prefix(UNION_PREFIX)?.rsplit_once("::"))
+            {
+                Some((_, ids)) => ids.split(',').map(|id| id.parse().ok()).collect(),
+                None => (0..data.variants.len()).map(Some).collect(),
+            };
+            union_reader(input, data, &ids)
+        }
+        Data::Union(_) => Err(Error::new_spanned(
+            &input.ident,
+            "`MoleculeReader` doesn't support unions",
+        )),
+    }
+}
+
+// The reader of a molecule table, with an accessor for every field.
+fn table_reader(input: &DeriveInput, fields: &[&Field]) -> syn::Result<proc_macro2::TokenStream> {
+    let (ident, vis) = (&input.ident, &input.vis);
+    let reader = format_ident!("{}Reader", ident);
+    let doc = format!("The reader of molecule table [`{}`].", ident);
+    let count = fields.len();
+    let mut accessors = vec![];
+    for (index, field) in fields.iter().enumerate() {
+        let name = field.ident.as_ref().unwrap();
+        let ty = reader_type(field)?;
+        let doc = format!("Read the `{}` field.", name);
+        let name_str = name.to_string();
+        accessors.push(quote! {
+            #[doc = #doc]
+            pub fn #name(&self) -> ::serde_molecule::Result<<#ty as ::serde_molecule::MoleculeReader>::Reader<'a>> {
+                ::serde_molecule::reader::read_field::<#ty>(&self.view, #index, #name_str)
+            }
+        });
+    }
+    Ok(quote! {
+        #[doc = #doc]
+        #[derive(Clone, Copy, Debug)]
+        #vis struct #reader<'a> {
+            view: ::serde_molecule::view::TableView<'a>,
+        }
+
+        impl<'a> #reader<'a> {
+            /// Validate the header of `data`, with at least the fields of the
+            /// type.
+            pub fn new(data: &'a [u8]) -> ::serde_molecule::Result<Self> {
+                let view = ::serde_molecule::view::TableView::with_count(data, #count)?;
+                Ok(#reader { view })
+            }
+
+            /// The read data.
+            pub fn as_slice(&self) -> &'a [u8] {
+                self.view.as_slice()
+            }
+
+            /// Whether there are fields appended by newer schemas.
+            pub fn has_extra_fields(&self) -> bool {
+                self.view.len() > #count
+            }
+
+            #(#accessors)*
+        }
+
+        impl ::serde_molecule::MoleculeReader for #ident {
+            type Reader<'a> = #reader<'a>;
+
+            fn read(data: &[u8]) -> ::serde_molecule::Result<#reader<'_>> {
+                #reader::new(data)
+            }
+        }
+    })
+}
+
+// The reader of a molecule struct: the fields are at the offsets summed from
+// the sizes of the previous fields.
+fn struct_reader(input: &DeriveInput, fields: &[&Field]) -> syn::Result<proc_macro2::TokenStream> {
+    let (ident, vis) = (&input.ident, &input.vis);
+    let reader = format_ident!("{}Reader", ident);
+    let doc = format!("The reader of molecule struct [`{}`].", ident);
+    let mut accessors = vec![];
+    let mut sizes = vec![];
+    for field in fields {
+        let name = field.ident.as_ref().unwrap();
+        let ty = reader_type(field)?;
+        let doc = format!("Read the `{}` field.", name);
+        let name_str = name.to_string();
+        accessors.push(quote! {
+            #[doc = #doc]
+            pub fn #name(&self) -> ::serde_molecule::Result<<#ty as ::serde_molecule::MoleculeReader>::Reader<'a>> {
+                let offset = 0 #(+ #sizes)*;
+                ::serde_molecule::reader::read_struct_field::<#ty>(self.data, offset, #name_str)
+            }
+        });
+        sizes.push(quote!(<#ty as ::serde_molecule::FixedSize>::SIZE));
+    }
+    Ok(quote! {
+        #[doc = #doc]
+        #[derive(Clone, Copy, Debug)]
+        #vis struct #reader<'a> {
+            data: &'a [u8],
+        }
+
+        impl<'a> #reader<'a> {
+            /// Check the length of `data`.
+            pub fn new(data: &'a [u8]) -> ::serde_molecule::Result<Self> {
+                ::serde_molecule::reader::check_struct::<#ident>(data)?;
+                Ok(#reader { data })
+            }
+
+            /// The read data.
+            pub fn as_slice(&self) -> &'a [u8] {
+                self.data
+            }
+
+            #(#accessors)*
+        }
+
+        impl ::serde_molecule::MoleculeReader for #ident {
+            type Reader<'a> = #reader<'a>;
+
+            fn read(data: &[u8]) -> ::serde_molecule::Result<#reader<'_>> {
+                #reader::new(data)
+            }
+        }
+    })
+}
+
+// A newtype struct is encoded as the inner type.
+fn newtype_reader(input: &DeriveInput, field: &Field) -> syn::Result<proc_macro2::TokenStream> {
+    let (ident, vis) = (&input.ident, &input.vis);
+    let reader = format_ident!("{}Reader", ident);
+    let doc = format!("The reader of [`{}`], the one of the inner type.", ident);
+    let ty = reader_type(field)?;
+    Ok(quote! {
+        #[doc = #doc]
+        #vis type #reader<'a> = <#ty as ::serde_molecule::MoleculeReader>::Reader<'a>;
+
+        impl ::serde_molecule::MoleculeReader for #ident {
+            type Reader<'a> = #reader<'a>;
+
+            fn read(data: &[u8]) -> ::serde_molecule::Result<#reader<'_>> {
+                <#ty as ::serde_molecule::MoleculeReader>::read(data)
+            }
+        }
+    })
+}
+
+// The reader of a molecule union, `ids` are the union ids of the variants,
+// `None` for the fallback variant.
+fn union_reader(
+    input: &DeriveInput,
+    data: &DataEnum,
+    ids: &[Option<usize>],
+) -> syn::Result<proc_macro2::TokenStream> {
+    let (ident, vis) = (&input.ident, &input.vis);
+    let reader = format_ident!("{}Reader", ident);
+    let doc = format!("The reader of molecule union [`{}`].", ident);
+    let mut variants = vec![];
+    let mut arms = vec![];
+    let mut fallback = None;
+    for (variant, id) in data.variants.iter().zip(ids) {
+        let name = &variant.ident;
+        let name_str = name.to_string();
+        let Some(id) = id else {
+            fallback = Some(quote!(id => Ok(#reader::#name(id as u32, &data[4..]))));
+            variants.push(quote! {
+                /// The union id and the undecoded body.
+                #name(u32, &'a [u8])
+            });
+            continue;
+        };
+        let id = Literal::usize_unsuffixed(*id);
+        match &variant.fields {
+            Fields::Unit => {
+                variants.push(quote!(#name));
+                arms.push(quote!(#id => Ok(#reader::#name)));
+            }
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                let ty = reader_type(&fields.unnamed[0])?;
+                variants.push(quote! {
+                    #name(<#ty as ::serde_molecule::MoleculeReader>::Reader<'a>)
+                });
+                arms.push(quote! {
+                    #id => Ok(#reader::#name(::serde_molecule::reader::read_variant::<#ty>(
+                        &data[4..],
+                        #name_str,
+                    )?))
+                });
+            }
+            _ => {
+                return Err(Error::new_spanned(
+                    name,
+                    "`MoleculeReader` supports unit and newtype variants",
+                ))
+            }
+        }
+    }
+    let fallback = fallback
+        .unwrap_or_else(|| quote!(id => Err(::serde_molecule::Error::UnknownUnionId { id })));
+    // without borrowed variants, the reader has no lifetime
+    let borrowed = data
+        .variants
+        .iter()
+        .zip(ids)
+        .any(|(variant, id)| id.is_none() || !matches!(variant.fields, Fields::Unit));
+    let lifetime = if borrowed { quote!(<'a>) } else { quote!() };
+    Ok(quote! {
+        #[doc = #doc]
+        #[derive(Clone, Copy, Debug)]
+        #vis enum #reader #lifetime {
+            #(#variants,)*
+        }
+
+        impl<'a> #reader #lifetime {
+            /// Read the union id of `data`, and the body of the variant.
+            pub fn new(data: &'a [u8]) -> ::serde_molecule::Result<Self> {
+                match ::serde_molecule::molecule::unpack_number(data, 0)? {
+                    #(#arms,)*
+                    #fallback,
+                }
+            }
+        }
+
+        impl ::serde_molecule::MoleculeReader for #ident {
+            type Reader<'a> = #reader #lifetime;
+
+            fn read(data: &[u8]) -> ::serde_molecule::Result<Self::Reader<'_>> {
+                #reader::new(data)
+            }
+        }
+    })
+}
+
+// The type whose `MoleculeReader` reads the field: `Dynvec<T>` for `Vec<T>`
+// with `dynvec_serde`.
+fn reader_type(field: &Field) -> syn::Result<Type> {
+    let with = serde_value(&field.attrs, "with");
+    let with = with
+        .as_deref()
+        .map(|with| with.rsplit("::").next().unwrap_or(with));
+    match with {
+        Some("struct_serde") => Err(Error::new_spanned(
+            field,
+            "`MoleculeReader` doesn't support `struct_serde`, mark the type by `#[molecule(struct)]` instead",
+        )),
+        Some("dynvec_serde") => match vec_item(&field.ty) {
+            Some(item) => Ok(parse_quote!(::serde_molecule::reader::Dynvec<#item>)),
+            None => Err(Error::new_spanned(&field.ty, "expected `Vec<T>` for `dynvec_serde`")),
+        },
+        _ => Ok(field.ty.clone()),
+    }
+}
+
+// `T` of `Vec<T>`.
+fn vec_item(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Vec" {
+        return None;
+    }
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) => match args.args.first()? {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// The string value of `key` in `#[serde(key = "value")]`.
+fn serde_value(attrs: &[Attribute], key: &str) -> Option<String> {
+    attrs.iter().find_map(|attr| {
+        if !attr.path().is_ident("serde") {
+            return None;
+        }
+        let tokens: Vec<_> = attr
+            .meta
+            .require_list()
+            .ok()?
+            .tokens
+            .clone()
+            .into_iter()
+            .collect();
+        tokens.windows(3).find_map(|window| match window {
+            [TokenTree::Ident(ident), TokenTree::Punct(punct), TokenTree::Literal(literal)]
+                if ident == key && punct.as_char() == '=' =>
+            {
+                match Lit::new(literal.clone()) {
+                    Lit::Str(value) => Some(value.value()),
+                    _ => None,
+                }
+            }
+            _ => None,
+        })
+    })
+}
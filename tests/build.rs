@@ -0,0 +1,12 @@
+// Generate Rust types from the schemas for `test_codegen`.
+fn main() {
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    println!("cargo:rerun-if-changed=schemas");
+    for (mol, out) in [
+        ("schemas/test1.mol", "test1.rs"),
+        ("schemas/codegen.mol", "codegen.rs"),
+        ("schemas/ckb/protocols.mol", "protocols.rs"),
+    ] {
+        serde_molecule::codegen::generate_file(mol, format!("{}/{}", out_dir, out)).unwrap();
+    }
+}
@@ -0,0 +1,11 @@
+// Generate Rust types from the schemas of the tests crate.
+fn main() {
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    println!("cargo:rerun-if-changed=../schemas");
+    for (mol, out) in [
+        ("../schemas/test1.mol", "test1.rs"),
+        ("../schemas/codegen.mol", "codegen.rs"),
+    ] {
+        serde_molecule::codegen::generate_file(mol, format!("{}/{}", out_dir, out)).unwrap();
+    }
+}
@@ -0,0 +1,12 @@
+//! The generated code in a `no_std` crate, without `String`, `Vec` and
+//! `BTreeMap` in the prelude.
+#![no_std]
+#![allow(clippy::all)]
+
+pub mod test1 {
+    include!(concat!(env!("OUT_DIR"), "/test1.rs"));
+}
+
+pub mod codegen {
+    include!(concat!(env!("OUT_DIR"), "/codegen.rs"));
+}
@@ -4,19 +4,43 @@
 #![allow(dead_code)]
 
 use serde::{de::DeserializeOwned, Serialize};
-use serde_molecule::{from_slice, to_vec};
+use serde_molecule::{from_slice, serialized_size, to_vec, to_writer, verify, DeserializerOptions};
 use std::fmt::Debug;
 pub mod ckb_types;
 pub mod new;
 pub mod old;
 pub mod simple;
+#[cfg(test)]
+mod test_alloc;
 pub mod test_big_array;
+pub mod test_borrow;
+pub mod test_canonical;
 pub mod test_ckb_types;
+pub mod test_cli;
+pub mod test_codegen;
+pub mod test_derive;
+pub mod test_error;
 pub mod test_fuzzing;
+pub mod test_guess;
+pub mod test_inspect;
+pub mod test_mol;
+pub mod test_reader;
+pub mod test_schema;
 pub mod test_serde;
+pub mod test_transcode;
+pub mod test_value;
+pub mod test_verify;
+pub mod test_view;
+pub mod test_writer;
 
 pub fn test_once<V: Serialize + DeserializeOwned>(value: &V) {
     let bytes = to_vec(value, false).expect("Failed to serialize value");
+    let mut streamed = vec![];
+    to_writer(&mut streamed, value, false).expect("Failed to serialize value to writer");
+    assert_eq!(bytes, streamed, "Streamed bytes do not match to_vec");
+    let size = serialized_size(value, false).expect("Failed to compute serialized size");
+    assert_eq!(bytes.len(), size, "Serialized size does not match to_vec");
+    verify::<V>(&bytes, &DeserializerOptions::new()).expect("Failed to verify value");
     let value2: V = from_slice(&bytes, false).expect("Failed to deserialize value");
     let bytes2 = to_vec(&value2, false).expect("Failed to re-serialize value");
     assert_eq!(bytes, bytes2, "Re-serialized bytes do not match original");
@@ -0,0 +1,68 @@
+// count heap allocations made by the current thread while decoding
+use crate::ckb_types::{CellInput, OutPoint};
+use serde_molecule::{from_slice, to_vec};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|c| c.set(c.get() + 1));
+        System.alloc(layout)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn count_allocations<R>(f: impl FnOnce() -> R) -> (R, usize) {
+    let before = ALLOCATIONS.with(|c| c.get());
+    let result = f();
+    let after = ALLOCATIONS.with(|c| c.get());
+    (result, after - before)
+}
+
+#[test]
+fn test_fixvec_of_structs_allocation() {
+    let inputs: Vec<CellInput> = (0..10_000)
+        .map(|i| CellInput {
+            since: i,
+            previous_output: OutPoint {
+                tx_hash: [i as u8; 32],
+                index: i as u32,
+            },
+        })
+        .collect();
+    let out_points: Vec<OutPoint> = inputs.iter().map(|i| i.previous_output.clone()).collect();
+
+    let bytes = to_vec(&out_points, false).unwrap();
+    let (decoded, count) = count_allocations(|| from_slice::<Vec<OutPoint>>(&bytes, false));
+    assert_eq!(decoded.unwrap(), out_points);
+    assert!(count < 10, "{} allocations", count);
+
+    // `CellInput` contains a molecule struct field annotated with `struct_serde`
+    let bytes = to_vec(&inputs, false).unwrap();
+    let (decoded, count) = count_allocations(|| from_slice::<Vec<CellInput>>(&bytes, false));
+    assert_eq!(decoded.unwrap(), inputs);
+    assert!(count < 10, "{} allocations", count);
+}
+
+#[test]
+fn test_struct_allocation() {
+    let out_point = OutPoint {
+        tx_hash: [3u8; 32],
+        index: 7,
+    };
+    let bytes = to_vec(&out_point, true).unwrap();
+    let (decoded, count) = count_allocations(|| from_slice::<OutPoint>(&bytes, true));
+    assert_eq!(decoded.unwrap(), out_point);
+    assert_eq!(count, 0);
+}
@@ -0,0 +1,106 @@
+use serde::{Deserialize, Serialize, Serializer};
+use serde_molecule::{from_slice, to_vec};
+use std::borrow::Cow;
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct BorrowedTable<'a> {
+    f1: u8,
+    name: &'a str,
+    args: &'a [u8],
+    #[serde(borrow)]
+    cow: Cow<'a, str>,
+    owned: String,
+}
+
+fn is_borrowed_from(part: &[u8], input: &[u8]) -> bool {
+    let range = input.as_ptr_range();
+    range.contains(&part.as_ptr())
+}
+
+#[test]
+fn test_borrowed_fields() {
+    let value = BorrowedTable {
+        f1: 1,
+        name: "hello",
+        args: &[1, 2, 3, 4],
+        cow: Cow::Borrowed("world"),
+        owned: "owned".into(),
+    };
+    let bytes = to_vec(&value, false).unwrap();
+    let value2: BorrowedTable = from_slice(&bytes, false).unwrap();
+    assert_eq!(value, value2);
+    assert!(is_borrowed_from(value2.name.as_bytes(), &bytes));
+    assert!(is_borrowed_from(value2.args, &bytes));
+    match &value2.cow {
+        Cow::Borrowed(s) => assert!(is_borrowed_from(s.as_bytes(), &bytes)),
+        Cow::Owned(_) => panic!("Cow<str> should borrow from input"),
+    }
+}
+
+#[test]
+fn test_borrowed_same_as_owned() {
+    #[derive(Serialize)]
+    struct OwnedTable {
+        f1: u8,
+        name: String,
+        args: Vec<u8>,
+        cow: String,
+        owned: String,
+    }
+    let owned = OwnedTable {
+        f1: 1,
+        name: "hello".into(),
+        args: vec![1, 2, 3],
+        cow: "".into(),
+        owned: "owned".into(),
+    };
+    let bytes = to_vec(&owned, false).unwrap();
+    let value: BorrowedTable = from_slice(&bytes, false).unwrap();
+    assert_eq!(value.name, "hello");
+    assert_eq!(value.args, &[1, 2, 3]);
+    assert_eq!(value.cow, "");
+}
+
+// serialized by `serialize_bytes`, e.g. serde_bytes
+struct Bytes<'a>(&'a [u8]);
+
+impl Serialize for Bytes<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+#[test]
+fn test_serialize_bytes() {
+    let bytes = to_vec(&Bytes(&[1, 2, 3]), false).unwrap();
+    assert_eq!(bytes, to_vec(&vec![1u8, 2, 3], false).unwrap());
+    let value: &[u8] = from_slice(&bytes, false).unwrap();
+    assert_eq!(value, &[1, 2, 3]);
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct Blob {
+    #[serde(with = "serde_bytes")]
+    data: Vec<u8>,
+    buf: serde_bytes::ByteBuf,
+}
+
+#[test]
+fn test_serde_bytes() {
+    let blob = Blob {
+        data: vec![1, 2],
+        buf: serde_bytes::ByteBuf::from(vec![3]),
+    };
+    let bytes = to_vec(&blob, false).unwrap();
+    // fixvecs of byte like `Vec<u8>`, the bytes were written as they are in 1.x
+    assert_eq!(
+        bytes,
+        [
+            23, 0, 0, 0, 12, 0, 0, 0, 18, 0, 0, 0, // header
+            2, 0, 0, 0, 1, 2, // data
+            1, 0, 0, 0, 3, // buf
+        ]
+    );
+    let value: Blob = from_slice(&bytes, false).unwrap();
+    assert_eq!(value, blob);
+}
@@ -0,0 +1,225 @@
+use crate::ckb_types::{CellInput, OutPoint, Transaction};
+use serde::{Deserialize, Serialize};
+use serde_molecule::{
+    dynvec_serde, from_slice, from_slice_canonical, from_slice_with, to_vec, verify,
+    DeserializerOptions, Error,
+};
+use std::collections::BTreeMap;
+
+fn canonical() -> DeserializerOptions {
+    DeserializerOptions::new().canonical(true)
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct Flags {
+    f1: bool,
+    f2: bool,
+}
+
+fn flags() -> Flags {
+    Flags {
+        f1: true,
+        f2: false,
+    }
+}
+
+#[test]
+fn test_bool() {
+    let mut bytes = to_vec(&flags(), false).unwrap();
+    let value: Flags = from_slice_with(&bytes, &canonical()).unwrap();
+    assert_eq!(value, flags());
+
+    // header: size + 2 offsets, then f1 at offset 12
+    bytes[12] = 2;
+    let value: Flags = from_slice(&bytes, false).unwrap();
+    assert!(value.f1);
+    let result: Result<Flags, _> = from_slice_with(&bytes, &canonical());
+    assert!(matches!(
+        result.unwrap_err().inner(),
+        Error::NonCanonical { offset: 12 }
+    ));
+}
+
+#[test]
+fn test_bool_in_struct() {
+    let bytes = [1u8, 3];
+    let value: (bool, bool) = from_slice(&bytes, true).unwrap();
+    assert_eq!(value, (true, true));
+    let result: Result<(bool, bool), _> = from_slice_with(&bytes, &canonical().is_struct(true));
+    assert!(matches!(
+        result.unwrap_err().inner(),
+        Error::NonCanonical { offset: 1 }
+    ));
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+enum Union {
+    A,
+    B(u8),
+}
+
+#[test]
+fn test_unit_variant_with_body() {
+    let mut bytes = to_vec(&Union::A, false).unwrap();
+    bytes.push(1);
+    let value: Union = from_slice(&bytes, false).unwrap();
+    assert_eq!(value, Union::A);
+    let result: Result<Union, _> = from_slice_with(&bytes, &canonical());
+    assert!(matches!(
+        result.unwrap_err().inner(),
+        Error::NonCanonical { offset: 4 }
+    ));
+}
+
+#[test]
+fn test_struct_trailing_bytes() {
+    let bytes = [1u8, 2, 3];
+    let value: [u8; 2] = from_slice(&bytes, true).unwrap();
+    assert_eq!(value, [1, 2]);
+    let result: Result<[u8; 2], _> = from_slice_with(&bytes, &canonical().is_struct(true));
+    assert!(matches!(
+        result.unwrap_err().inner(),
+        Error::NonCanonical { offset: 2 }
+    ));
+
+    // fixvec with 2 items of 3 bytes, decoded as items of 2 bytes
+    let bytes = [2u8, 0, 0, 0, 1, 2, 0, 3, 4, 0];
+    let value: Vec<[u8; 2]> = from_slice(&bytes, false).unwrap();
+    assert_eq!(value, vec![[1, 2], [3, 4]]);
+    let result: Result<Vec<[u8; 2]>, _> = from_slice_with(&bytes, &canonical());
+    assert!(matches!(
+        result.unwrap_err().inner(),
+        Error::NonCanonical { offset: 6 }
+    ));
+
+    // empty fixvec with trailing bytes
+    let bytes = [0u8, 0, 0, 0, 0xff, 0xff];
+    let value: Vec<u16> = from_slice(&bytes, false).unwrap();
+    assert!(value.is_empty());
+    let result: Result<Vec<u16>, _> = from_slice_with(&bytes, &canonical());
+    assert!(matches!(
+        result.unwrap_err().inner(),
+        Error::NonCanonical { offset: 4 }
+    ));
+    assert!(verify::<Vec<u16>>(&bytes, &DeserializerOptions::new()).is_ok());
+    let result = verify::<Vec<u16>>(&bytes, &canonical());
+    assert!(matches!(
+        result.unwrap_err().inner(),
+        Error::NonCanonical { offset: 4 }
+    ));
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct TableV1 {
+    f1: u8,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct TableV2 {
+    f1: u8,
+    f2: u8,
+}
+
+#[test]
+fn test_extra_fields() {
+    let bytes = to_vec(&TableV2 { f1: 1, f2: 2 }, false).unwrap();
+    let value: TableV1 = from_slice(&bytes, false).unwrap();
+    assert_eq!(value, TableV1 { f1: 1 });
+    let result: Result<TableV1, _> = from_slice_with(&bytes, &canonical().compatible(true));
+    assert!(matches!(
+        result.unwrap_err().inner(),
+        Error::MismatchedTableFieldCount
+    ));
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct Map {
+    f1: BTreeMap<u8, u8>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct Entry {
+    key: u8,
+    value: u8,
+}
+
+// Same encoding as `Map`, with entries in any order.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct Entries {
+    #[serde(with = "dynvec_serde")]
+    f1: Vec<Entry>,
+}
+
+fn entries(pairs: &[(u8, u8)]) -> Vec<u8> {
+    let f1 = pairs
+        .iter()
+        .map(|&(key, value)| Entry { key, value })
+        .collect();
+    to_vec(&Entries { f1 }, false).unwrap()
+}
+
+#[test]
+fn test_map() {
+    let map = Map {
+        f1: [(1, 10), (2, 20)].into_iter().collect(),
+    };
+    let bytes = to_vec(&map, false).unwrap();
+    assert_eq!(bytes, entries(&[(1, 10), (2, 20)]));
+    let value: Map = from_slice_canonical(&bytes, &DeserializerOptions::new()).unwrap();
+    assert_eq!(value, map);
+
+    // duplicated keys: the last one wins
+    let bytes = entries(&[(1, 10), (1, 20)]);
+    let value: Map = from_slice(&bytes, false).unwrap();
+    assert_eq!(value.f1.len(), 1);
+    let result: Result<Map, _> = from_slice_with(&bytes, &canonical());
+    assert!(matches!(
+        result.unwrap_err().inner(),
+        Error::NonCanonical { .. }
+    ));
+
+    // unsorted keys are only found by re-encoding
+    let bytes = entries(&[(2, 20), (1, 10)]);
+    let value: Map = from_slice_with(&bytes, &canonical()).unwrap();
+    assert_eq!(value, map);
+    let result: Result<Map, _> = from_slice_canonical(&bytes, &DeserializerOptions::new());
+    assert!(matches!(
+        result.unwrap_err().inner(),
+        Error::NonCanonical { .. }
+    ));
+}
+
+#[test]
+fn test_lossy_utf8_ignored() {
+    let bytes = to_vec(&vec![0xffu8], false).unwrap();
+    let options = canonical().lossy_utf8(true);
+    let result: Result<String, _> = from_slice_with(&bytes, &options);
+    assert!(matches!(
+        result.unwrap_err().inner(),
+        Error::InvalidUtf8 { offset: 4 }
+    ));
+}
+
+#[test]
+fn test_from_slice_canonical() {
+    let mut tx = Transaction::default();
+    tx.raw.inputs = vec![CellInput {
+        since: 42,
+        previous_output: OutPoint {
+            tx_hash: [1u8; 32],
+            index: 1,
+        },
+    }];
+    tx.witnesses = vec![vec![1, 2, 3]];
+    let bytes = to_vec(&tx, false).unwrap();
+    let value: Transaction = from_slice_canonical(&bytes, &DeserializerOptions::new()).unwrap();
+    assert_eq!(value, tx);
+
+    let mut bytes = to_vec(&flags(), false).unwrap();
+    bytes[13] = 5;
+    let result: Result<Flags, _> = from_slice_canonical(&bytes, &DeserializerOptions::new());
+    assert!(matches!(
+        result.unwrap_err().inner(),
+        Error::NonCanonical { offset: 13 }
+    ));
+}
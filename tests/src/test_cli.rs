@@ -0,0 +1,191 @@
+use clap::Parser;
+use serde_molecule_cli::{run, Cli};
+
+const BLOCKCHAIN: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/schemas/ckb/blockchain.mol");
+
+const SCRIPT: &str = "0x3700000010000000300000003100000002020202020202020202020202020202020202020202020202020202020202020102000000aabb";
+
+// Run the command line `args` with `stdin`, return the output.
+fn cli(args: &[&str], stdin: &str) -> serde_molecule::Result<String> {
+    let args = ["serde-molecule"].iter().chain(args);
+    let cli = Cli::try_parse_from(args).unwrap();
+    let mut stdout = vec![];
+    run(&cli, &mut stdin.as_bytes(), &mut stdout)?;
+    Ok(String::from_utf8(stdout).unwrap())
+}
+
+#[test]
+fn test_cli_decode_encode() {
+    let json = cli(
+        &["decode", "-s", BLOCKCHAIN, "-t", "Script", "--hex", SCRIPT],
+        "",
+    )
+    .unwrap();
+    let expected = format!(
+        r#"{{
+  "args": "0xaabb",
+  "code_hash": "0x{}",
+  "hash_type": 1
+}}
+"#,
+        "02".repeat(32)
+    );
+    assert_eq!(json, expected);
+    // from stdin, with whitespace and without prefix
+    let hex = format!("  {}\n", &SCRIPT[2..]);
+    assert_eq!(
+        cli(&["decode", "-s", BLOCKCHAIN, "-t", "Script"], &hex).unwrap(),
+        expected
+    );
+
+    let hex = cli(&["encode", "-s", BLOCKCHAIN, "-t", "Script"], &json).unwrap();
+    assert_eq!(hex, format!("{}\n", SCRIPT));
+    let hex = cli(
+        &[
+            "encode",
+            "-s",
+            BLOCKCHAIN,
+            "-t",
+            "ScriptOpt",
+            "--json",
+            "null",
+        ],
+        "",
+    )
+    .unwrap();
+    assert_eq!(hex, "0x\n");
+}
+
+#[test]
+fn test_cli_verify() {
+    let verify = |ty: &str, hex: &str, compatible: bool| {
+        let mut args = vec!["verify", "-s", BLOCKCHAIN, "-t", ty, "--hex", hex];
+        if compatible {
+            args.push("--compatible");
+        }
+        cli(&args, "")
+    };
+    assert_eq!(verify("Script", SCRIPT, false).unwrap(), "ok\n");
+    // WitnessArgs with an extra field
+    let witness = "0x1400000014000000140000001400000014000000";
+    assert_eq!(verify("WitnessArgs", witness, true).unwrap(), "ok\n");
+    let error = verify("WitnessArgs", witness, false).unwrap_err();
+    assert!(matches!(
+        error,
+        serde_molecule::Error::MismatchedTableFieldCount
+    ));
+    let error = verify("Script", &SCRIPT[..SCRIPT.len() - 2], false).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "InvalidTableLength { expected: 55, actual: 54 }"
+    );
+    let error = verify("Script", "0x123", false).unwrap_err();
+    assert_eq!(error.to_string(), "odd number of hex digits");
+    let error = verify("Unknown", SCRIPT, false).unwrap_err();
+    assert!(error.to_string().starts_with("`Unknown` isn't defined in"));
+}
+
+#[test]
+fn test_cli_layout() {
+    let output = cli(
+        &[
+            "layout",
+            "-s",
+            BLOCKCHAIN,
+            "-t",
+            "ScriptOpt",
+            "--hex",
+            SCRIPT,
+        ],
+        "",
+    )
+    .unwrap();
+    let expected = format!(
+        "offset   size  type
+0           55  ScriptOpt option
+0           55    Script table, header [55, 16, 48, 49]
+16          32      code_hash: Byte32 array = 0x{}
+48           1      hash_type: byte = 0x01
+49           6      args: Bytes fixvec, count 2 = 0xaabb
+",
+        "02".repeat(32)
+    );
+    assert_eq!(output, expected);
+
+    let output = cli(
+        &[
+            "layout",
+            "-s",
+            BLOCKCHAIN,
+            "-t",
+            "BytesVec",
+            "--hex",
+            "0x150000000c00000011000000010000000100000000",
+        ],
+        "",
+    )
+    .unwrap();
+    assert_eq!(
+        output,
+        "offset   size  type
+0           21  BytesVec dynvec, header [21, 12, 17]
+12           5    [0]: Bytes fixvec, count 1 = 0x01
+17           4    [1]: Bytes fixvec, count 0 = 0x
+"
+    );
+    let hexdump = cli(
+        &[
+            "layout",
+            "-s",
+            BLOCKCHAIN,
+            "-t",
+            "BytesVec",
+            "--hex",
+            "0x150000000c00000011000000010000000100000000",
+            "--hexdump",
+        ],
+        "",
+    )
+    .unwrap();
+    assert_eq!(
+        hexdump,
+        "\
+00000000  15 00 00 00 0c 00 00 00 11 00 00 00              BytesVec dynvec, header [21, 12, 17]
+0000000c  01 00 00 00 01                                     [0]: Bytes fixvec, count 1 = 0x01
+00000011  00 00 00 00                                        [1]: Bytes fixvec, count 0 = 0x
+"
+    );
+
+    // the count of `args` is 3
+    let mut hex = SCRIPT.to_string();
+    hex.replace_range(100..102, "03");
+    let error = cli(
+        &["layout", "-s", BLOCKCHAIN, "-t", "Script", "--hex", &hex],
+        "",
+    )
+    .unwrap_err();
+    assert_eq!(error.to_string(), "`args` at offset 49 is malformed");
+}
+
+#[test]
+fn test_cli_guess() {
+    let output = cli(&["guess", "--limit", "2"], SCRIPT).unwrap();
+    let expected = format!(
+        "guess 1: confidence 94.1%, score 18 bits
+offset   size  type
+0           55  unknown table, header [55, 16, 48, 49]
+16          32    [0]: unknown raw = 0x{0}
+48           1    [1]: unknown raw = 0x01
+49           6    [2]: unknown fixvec, count 2 = 0xaabb
+
+guess 2: confidence 5.9%, score 14 bits
+offset   size  type
+0           55  unknown table, header [55, 16, 48, 49]
+16          32    [0]: unknown raw = 0x{0}
+48           1    [1]: unknown raw = 0x01
+49           6    [2]: unknown raw = 0x02000000aabb
+",
+        "02".repeat(32)
+    );
+    assert_eq!(output, expected);
+}
@@ -0,0 +1,216 @@
+use crate::old::test1::Struct1Opt;
+use crate::old::test1::Table1 as OldTable1;
+use crate::old::test1_default as old_default;
+use crate::{test_eq_once, test_once};
+use ckb_gen_types::packed;
+use ckb_gen_types::prelude::*;
+use molecule::prelude::*;
+use serde_molecule::codegen::generate;
+use serde_molecule::schema::Schema;
+use serde_molecule::{from_slice, to_vec};
+
+// generated by build.rs
+mod test1 {
+    include!(concat!(env!("OUT_DIR"), "/test1.rs"));
+}
+
+mod protocols {
+    include!(concat!(env!("OUT_DIR"), "/protocols.rs"));
+}
+
+mod codegen {
+    include!(concat!(env!("OUT_DIR"), "/codegen.rs"));
+}
+
+fn new_table1() -> test1::Table1 {
+    test1::Table1 {
+        f1: 0xcd,
+        f2: 0xcd,
+        f3: 0xcd,
+        f4: 0xcd,
+        f5: 0xcd,
+        fixvec: vec![0xcd; 3],
+        dynvec: vec![vec![0xcd; 3]; 3],
+        struct1: test1::Struct1 { f1: 0xcd, f2: 0xcd },
+        option: None,
+        array3: [0xcd; 3],
+        string: "cd".into(),
+        struct1_opt: Some(test1::Struct1 { f1: 0xcd, f2: 0xcd }),
+        map: [(0xcd, vec![0xcd; 3])].into(),
+    }
+}
+
+#[test]
+fn test_codegen_test1() {
+    let old_value = OldTable1::new_builder()
+        .f1(old_default::DEFAULT_BYTE.clone())
+        .f2(old_default::DEFAULT_U16.clone())
+        .f3(old_default::DEFAULT_U32.clone())
+        .f4(old_default::DEFAULT_U64.clone())
+        .f5(old_default::DEFAULT_U128.clone())
+        .fixvec(old_default::DEFAULT_FIXVEC.clone())
+        .dynvec(old_default::DEFAULT_DYNVEC.clone())
+        .struct1(old_default::DEFAULT_STRUCT1.clone())
+        .array3(old_default::DEFAULT_ARRAY3.clone())
+        .string(old_default::DEFAULT_STRING.clone())
+        .struct1_opt(Struct1Opt::from_slice(old_default::DEFAULT_STRUCT1.as_slice()).unwrap())
+        .map(old_default::DEFAULT_MAP.clone())
+        .build();
+    let value = new_table1();
+    assert_eq!(old_value.as_slice(), to_vec(&value, false).unwrap());
+    test_once(&value);
+    test_eq_once(&value);
+    test_eq_once(&test1::Enum1::U32(0xcd));
+}
+
+#[test]
+fn test_codegen_ckb() {
+    let tx = protocols::Transaction {
+        raw: protocols::RawTransaction {
+            version: 0,
+            cell_deps: vec![],
+            header_deps: vec![[1; 32]],
+            inputs: vec![],
+            outputs: vec![protocols::CellOutput {
+                capacity: 42,
+                lock: protocols::Script {
+                    code_hash: [2; 32],
+                    hash_type: 1,
+                    args: vec![1, 2, 3],
+                },
+                type_: None,
+            }],
+            outputs_data: vec![vec![]],
+        },
+        witnesses: vec![vec![4, 5]],
+    };
+    let old_tx = packed::Transaction::default();
+    let output = packed::CellOutput::new_builder()
+        .capacity(42u64.pack())
+        .lock(
+            packed::Script::new_builder()
+                .code_hash([2; 32].pack())
+                .hash_type(1.into())
+                .args(vec![1, 2, 3].pack())
+                .build(),
+        )
+        .build();
+    let raw = old_tx
+        .raw()
+        .as_builder()
+        .header_deps(vec![[1; 32].pack()].pack())
+        .outputs(vec![output].pack())
+        .outputs_data(vec![Vec::<u8>::new().pack()].pack())
+        .build();
+    let old_tx = old_tx
+        .as_builder()
+        .raw(raw)
+        .witnesses(vec![vec![4u8, 5].pack()].pack())
+        .build();
+    assert_eq!(old_tx.as_slice(), to_vec(&tx, false).unwrap());
+    test_eq_once(&tx);
+
+    let header: protocols::Header = from_slice(packed::Header::default().as_slice(), true).unwrap();
+    assert_eq!(header.nonce, 0);
+
+    // union with customized ids
+    let message = protocols::SyncMessage::InIBD(protocols::InIBD {});
+    let bytes = to_vec(&message, false).unwrap();
+    assert_eq!(&bytes[..4], &8u32.to_le_bytes());
+    test_eq_once(&message);
+}
+
+#[test]
+fn test_codegen_annotations() {
+    let point = codegen::Point {
+        x: 1,
+        y: 2,
+        tag: [3; 64],
+    };
+    let shape = codegen::Shape {
+        r#type: 1,
+        points: codegen::PointArray2([point.clone(), point.clone()]),
+        center: codegen::PointOpt(Some(point.clone())),
+        corners: vec![
+            codegen::PointOpt(None),
+            codegen::PointOpt(Some(point.clone())),
+        ],
+        bounds: None,
+        names: Some(codegen::BytesVec(vec![b"a".to_vec(), b"bc".to_vec()])),
+        signature: [4; 64],
+        weights: [5; 40],
+    };
+    test_once(&shape);
+    test_eq_once(&shape);
+    for message in [
+        codegen::Message::Point(point),
+        codegen::Message::Shape(shape),
+        codegen::Message::Bytes(vec![1]),
+    ] {
+        test_eq_once(&message);
+    }
+    let bytes = to_vec(&codegen::Message::Bytes(vec![1]), false).unwrap();
+    assert_eq!(bytes, [17, 0, 0, 0, 1, 0, 0, 0, 1]);
+}
+
+#[test]
+fn test_codegen_reserved_names() {
+    let module = codegen::Module {
+        crate_: vec![1, 2],
+        self_: 3,
+    };
+    test_eq_once(&module);
+    // the molecule names are kept
+    let json = serde_json::to_value(&module).unwrap();
+    assert_eq!(json["crate"], serde_json::json!([1, 2]));
+    assert_eq!(json["self"], 3);
+}
+
+#[test]
+fn test_codegen_source() {
+    let schema = Schema::parse(
+        "
+        array Uint64 [byte; 8];
+        vector String <byte>;
+        struct Point { x: Uint64, y: Uint64 }
+        vector PointVec <Point>;
+        table Named { name: String, points: PointVec, origin: Point }
+        vector NamedVec <Named>;
+        ",
+    )
+    .unwrap();
+    assert_eq!(
+        generate(&schema).unwrap(),
+        r#"// Generated by serde_molecule from a molecule schema, don't edit.
+
+#[allow(unused_imports)]
+use serde_molecule::codegen::prelude::*;
+
+pub type Uint64 = u64;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Debug)]
+pub struct Point {
+    pub x: Uint64,
+    pub y: Uint64,
+}
+
+pub type PointVec = Vec<Point>;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Debug)]
+pub struct Named {
+    pub name: String,
+    pub points: PointVec,
+    #[serde(with = "serde_molecule::struct_serde")]
+    pub origin: Point,
+}
+
+pub type NamedVec = Vec<Named>;
+"#
+    );
+
+    let schema = Schema::parse("struct S { f: byte }\narray A [S; 33];").unwrap();
+    assert_eq!(
+        generate(&schema).unwrap_err().to_string(),
+        "array `A` of more than 32 items must be bytes or numbers"
+    );
+}
@@ -0,0 +1,411 @@
+use crate::ckb_types;
+use serde::{Deserialize, Serialize};
+use serde_molecule::trace::{ContainerFormat, Format, Layout, VariantFormat};
+use serde_molecule::{
+    from_slice, from_slice_with, molecule, serialized_size, to_slice, to_vec, verify,
+    DeserializerOptions, Error, FixedSize, MoleculeKind,
+};
+
+#[molecule(struct)]
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+pub struct OutPoint {
+    pub tx_hash: [u8; 32],
+    pub index: u32,
+}
+
+#[molecule(table)]
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+pub struct CellInput {
+    pub since: u64,
+    // no `struct_serde` is needed
+    pub previous_output: OutPoint,
+}
+
+#[molecule(table)]
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+pub struct Inputs {
+    pub inputs: Vec<CellInput>,
+    pub out_points: Vec<OutPoint>,
+}
+
+#[molecule(struct)]
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+pub struct Pair(u8, u32);
+
+#[molecule(struct)]
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+pub struct Wrapper(Pair);
+
+#[molecule(table)]
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+pub struct Fields {
+    pub pair: Pair,
+    pub wrapper: Wrapper,
+    pub optional: Option<Pair>,
+}
+
+#[molecule(struct)]
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+pub struct Generic<T> {
+    pub f1: T,
+    #[serde(skip)]
+    pub cache: Vec<u8>,
+    pub f2: [Pair; 2],
+}
+
+fn out_point() -> OutPoint {
+    OutPoint {
+        tx_hash: [1u8; 32],
+        index: 2,
+    }
+}
+
+fn ckb_out_point() -> ckb_types::OutPoint {
+    ckb_types::OutPoint {
+        tx_hash: [1u8; 32],
+        index: 2,
+    }
+}
+
+#[test]
+fn test_kind() {
+    assert!(OutPoint::IS_STRUCT);
+    assert!(Pair::IS_STRUCT);
+    assert!(!CellInput::IS_STRUCT);
+    assert!(!Inputs::IS_STRUCT);
+}
+
+#[test]
+fn test_root() {
+    let expected = to_vec(&ckb_out_point(), true).unwrap();
+    // `is_struct` doesn't matter for a molecule struct
+    for is_struct in [false, true] {
+        let bytes = to_vec(&out_point(), is_struct).unwrap();
+        assert_eq!(bytes, expected);
+        let value: OutPoint = from_slice(&bytes, is_struct).unwrap();
+        assert_eq!(value, out_point());
+    }
+}
+
+#[test]
+fn test_field() {
+    let input = CellInput {
+        since: 3,
+        previous_output: out_point(),
+    };
+    let ckb_input = ckb_types::CellInput {
+        since: 3,
+        previous_output: ckb_out_point(),
+    };
+    let bytes = to_vec(&input, false).unwrap();
+    assert_eq!(bytes, to_vec(&ckb_input, false).unwrap());
+    let value: CellInput = from_slice(&bytes, false).unwrap();
+    assert_eq!(value, input);
+
+    let inputs = Inputs {
+        inputs: vec![input.clone(), input],
+        out_points: vec![out_point()],
+    };
+    let bytes = to_vec(&inputs, false).unwrap();
+    let value: Inputs = from_slice(&bytes, false).unwrap();
+    assert_eq!(value, inputs);
+    verify::<Inputs>(&bytes, &DeserializerOptions::new()).unwrap();
+}
+
+#[test]
+fn test_tuple_struct() {
+    let fields = Fields {
+        pair: Pair(1, 2),
+        wrapper: Wrapper(Pair(3, 4)),
+        optional: Some(Pair(5, 6)),
+    };
+    let bytes = to_vec(&fields, false).unwrap();
+    // header: size + 3 offsets, then 3 structs of 5 bytes
+    assert_eq!(bytes.len(), 16 + 5 * 3);
+    assert_eq!(&bytes[16..21], &[1, 2, 0, 0, 0]);
+    let value: Fields = from_slice(&bytes, false).unwrap();
+    assert_eq!(value, fields);
+
+    let options = DeserializerOptions::new().canonical(true);
+    let mut bytes = to_vec(&Pair(1, 2), false).unwrap();
+    bytes.push(0);
+    let value: Pair = from_slice(&bytes, false).unwrap();
+    assert_eq!(value, Pair(1, 2));
+    assert!(from_slice_with::<Pair>(&bytes, &options).is_err());
+}
+
+#[test]
+fn test_trace() {
+    let layout = Layout::of::<CellInput>().unwrap();
+    assert_eq!(
+        layout.registry["CellInput"],
+        ContainerFormat::Struct(vec![
+            ("since", Format::U64),
+            (
+                "previous_output",
+                Format::Struct(Box::new(Format::TypeName("OutPoint")))
+            ),
+        ])
+    );
+    let layout = Layout::of::<OutPoint>().unwrap();
+    assert_eq!(
+        layout.root,
+        Format::Struct(Box::new(Format::TypeName("OutPoint")))
+    );
+}
+
+#[test]
+fn test_json() {
+    let json = serde_json::to_string(&out_point()).unwrap();
+    let value: OutPoint = serde_json::from_str(&json).unwrap();
+    assert_eq!(value, out_point());
+}
+
+#[test]
+fn test_fixed_size() {
+    assert_eq!(<[u16; 3]>::SIZE, 6);
+    assert_eq!(OutPoint::SIZE, 36);
+    assert_eq!(Pair::SIZE, 5);
+    assert_eq!(Wrapper::SIZE, 5);
+    assert_eq!(Generic::<OutPoint>::SIZE, 46);
+
+    let value = Generic {
+        f1: out_point(),
+        cache: vec![1, 2, 3],
+        f2: [Pair(1, 2), Pair(3, 4)],
+    };
+    assert_eq!(serialized_size(&value, false).unwrap(), 46);
+    assert_eq!(
+        serialized_size(&out_point(), false).unwrap(),
+        OutPoint::SIZE
+    );
+}
+
+#[test]
+fn test_to_slice() {
+    let mut buf = [0u8; OutPoint::SIZE];
+    let len = to_slice(&out_point(), &mut buf, false).unwrap();
+    assert_eq!(len, OutPoint::SIZE);
+    assert_eq!(buf.as_slice(), to_vec(&out_point(), false).unwrap());
+
+    let mut buf = [0u8; OutPoint::SIZE - 1];
+    let error = to_slice(&out_point(), &mut buf, false).unwrap_err();
+    assert!(matches!(error, Error::BufferTooSmall));
+
+    // tables fit too, the header is patched in place
+    let input = CellInput {
+        since: 3,
+        previous_output: out_point(),
+    };
+    let expected = to_vec(&input, false).unwrap();
+    let mut buf = [0xffu8; 64];
+    let len = to_slice(&input, &mut buf, false).unwrap();
+    assert_eq!(&buf[..len], expected);
+    let error = to_slice(&input, &mut buf[..8], false).unwrap_err();
+    assert!(matches!(error, Error::BufferTooSmall));
+}
+
+#[molecule(union)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum Union {
+    #[molecule(union_id = 4278190081)]
+    A,
+    B(OutPoint),
+    #[molecule(union_id = 7)]
+    C(u8, Vec<u8>),
+    D {
+        f1: Pair,
+    },
+}
+
+#[molecule(table)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Unions {
+    pub f1: Union,
+    pub f2: Option<Union>,
+}
+
+#[test]
+fn test_union_id() {
+    assert!(!Union::IS_STRUCT);
+    for (value, id) in [
+        (Union::A, 4278190081u32),
+        (Union::B(out_point()), 4278190082),
+        (Union::C(1, vec![2, 3]), 7),
+        (Union::D { f1: Pair(1, 2) }, 8),
+    ] {
+        let bytes = to_vec(&value, false).unwrap();
+        assert_eq!(bytes[..4], id.to_le_bytes());
+        let result: Union = from_slice(&bytes, false).unwrap();
+        assert_eq!(result, value);
+        verify::<Union>(&bytes, &DeserializerOptions::new()).unwrap();
+    }
+    let bytes = to_vec(&Union::B(out_point()), false).unwrap();
+    assert_eq!(&bytes[4..], to_vec(&out_point(), false).unwrap());
+
+    let value = Unions {
+        f1: Union::A,
+        f2: Some(Union::C(1, vec![])),
+    };
+    let bytes = to_vec(&value, false).unwrap();
+    let result: Unions = from_slice(&bytes, false).unwrap();
+    assert_eq!(result, value);
+}
+
+#[test]
+fn test_unknown_union_id() {
+    let mut bytes = to_vec(&Union::A, false).unwrap();
+    // the variant index is not a valid id
+    bytes[..4].copy_from_slice(&1u32.to_le_bytes());
+    let error = from_slice::<Union>(&bytes, false).unwrap_err();
+    assert!(matches!(error, Error::UnknownUnionId { id: 1 }));
+    let options = DeserializerOptions::new();
+    let error = verify::<Union>(&bytes, &options).unwrap_err();
+    assert!(matches!(error, Error::UnknownUnionId { id: 1 }));
+
+    let value = Unions {
+        f1: Union::A,
+        f2: Some(Union::A),
+    };
+    let mut bytes = to_vec(&value, false).unwrap();
+    let len = bytes.len();
+    bytes[len - 4..].copy_from_slice(&2u32.to_le_bytes());
+    let error = from_slice::<Unions>(&bytes, false).unwrap_err();
+    assert_eq!(error.path(), Some("f2"));
+    assert!(matches!(error.inner(), Error::UnknownUnionId { id: 2 }));
+    let error2 = verify::<Unions>(&bytes, &options).unwrap_err();
+    assert_eq!(error.to_string(), error2.to_string());
+}
+
+#[test]
+fn test_union_trace() {
+    let layout = Layout::of::<Union>().unwrap();
+    assert_eq!(layout.root, Format::TypeName("Union"));
+    assert_eq!(
+        layout.registry["Union"],
+        ContainerFormat::Union {
+            variants: vec![
+                (4278190081, "A", VariantFormat::Unit),
+                (
+                    4278190082,
+                    "B",
+                    VariantFormat::Newtype(Box::new(Format::Struct(Box::new(Format::TypeName(
+                        "OutPoint"
+                    )))))
+                ),
+                (
+                    7,
+                    "C",
+                    VariantFormat::Tuple(vec![Format::U8, Format::Fixvec(Box::new(Format::U8))])
+                ),
+                (
+                    8,
+                    "D",
+                    VariantFormat::Struct(vec![(
+                        "f1",
+                        Format::Struct(Box::new(Format::TypeName("Pair")))
+                    )])
+                ),
+            ],
+            fallback: None,
+        }
+    );
+}
+
+#[test]
+fn test_union_json() {
+    let json = serde_json::to_string(&Union::C(1, vec![2])).unwrap();
+    assert_eq!(json, r#"{"C":[1,[2]]}"#);
+    let value: Union = serde_json::from_str(&json).unwrap();
+    assert_eq!(value, Union::C(1, vec![2]));
+}
+
+// A newer version of `UnionV1`
+#[molecule(union)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum UnionV2 {
+    #[molecule(union_id = 10)]
+    A(u8),
+    B(Vec<u8>),
+    C(OutPoint),
+}
+
+#[molecule(union)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum UnionV1 {
+    #[molecule(union_id = 10)]
+    A(u8),
+    #[molecule(fallback)]
+    Unknown { id: u32, body: Vec<u8> },
+}
+
+#[molecule(union)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum BorrowedUnion<'a> {
+    #[molecule(fallback)]
+    Unknown(u32, &'a [u8]),
+    #[molecule(union_id = 10)]
+    A(u8),
+}
+
+#[molecule(table)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Indexed {
+    pub f1: UnionV1,
+    pub f2: Vec<u8>,
+}
+
+#[test]
+fn test_union_fallback() {
+    let bytes = to_vec(&UnionV2::A(1), false).unwrap();
+    let value: UnionV1 = from_slice(&bytes, false).unwrap();
+    assert_eq!(value, UnionV1::A(1));
+
+    for v2 in [UnionV2::B(vec![1, 2, 3]), UnionV2::C(out_point())] {
+        let bytes = to_vec(&v2, false).unwrap();
+        let value: UnionV1 = from_slice(&bytes, false).unwrap();
+        assert!(matches!(&value, UnionV1::Unknown { body, .. } if *body == bytes[4..]));
+        // re-serialized verbatim
+        assert_eq!(to_vec(&value, false).unwrap(), bytes);
+        verify::<UnionV1>(&bytes, &DeserializerOptions::new()).unwrap();
+
+        let value: BorrowedUnion = from_slice(&bytes, false).unwrap();
+        assert!(matches!(value, BorrowedUnion::Unknown(_, body) if body == &bytes[4..]));
+        assert_eq!(to_vec(&value, false).unwrap(), bytes);
+    }
+    let value: UnionV1 = from_slice(&to_vec(&UnionV2::B(vec![]), false).unwrap(), false).unwrap();
+    assert_eq!(
+        value,
+        UnionV1::Unknown {
+            id: 11,
+            body: vec![0, 0, 0, 0]
+        }
+    );
+
+    let indexed = Indexed {
+        f1: UnionV1::Unknown {
+            id: 100,
+            body: vec![1, 2],
+        },
+        f2: vec![3],
+    };
+    let bytes = to_vec(&indexed, false).unwrap();
+    let value: Indexed = from_slice(&bytes, false).unwrap();
+    assert_eq!(value, indexed);
+    let options = DeserializerOptions::new().canonical(true);
+    let value: Indexed = from_slice_with(&bytes, &options).unwrap();
+    assert_eq!(value, indexed);
+    verify::<Indexed>(&bytes, &options).unwrap();
+}
+
+#[test]
+fn test_union_fallback_trace() {
+    let layout = Layout::of::<UnionV1>().unwrap();
+    assert_eq!(
+        layout.registry["UnionV1"],
+        ContainerFormat::Union {
+            variants: vec![(10, "A", VariantFormat::Newtype(Box::new(Format::U8)))],
+            fallback: Some("Unknown"),
+        }
+    );
+}
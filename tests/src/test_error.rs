@@ -0,0 +1,107 @@
+use crate::ckb_types::{CellOutput, Script, Transaction};
+use serde::{Deserialize, Serialize};
+use serde_molecule::{from_slice, to_vec, Error};
+
+fn find(bytes: &[u8], pattern: &[u8]) -> usize {
+    bytes
+        .windows(pattern.len())
+        .position(|w| w == pattern)
+        .unwrap()
+}
+
+#[test]
+fn test_table_path() {
+    let script = Script {
+        code_hash: [5u8; 32],
+        hash_type: 1,
+        args: vec![0xaa, 0xbb, 0xcc],
+    };
+    let mut tx = Transaction::default();
+    tx.raw.outputs = vec![
+        CellOutput::default(),
+        CellOutput {
+            capacity: 100,
+            lock: Script::default(),
+            type_: Some(script),
+        },
+    ];
+    let mut bytes = to_vec(&tx, false).unwrap();
+    // args: fixvec with 4 items but only 3 bytes
+    let offset = find(&bytes, &[3, 0, 0, 0, 0xaa, 0xbb, 0xcc]);
+    bytes[offset] = 4;
+
+    let error = from_slice::<Transaction>(&bytes, false).unwrap_err();
+    assert!(matches!(error.inner(), Error::InvalidFixvec));
+    assert_eq!(error.offset(), Some(offset));
+    assert_eq!(error.path(), Some("raw.outputs[1].type_.args"));
+    assert_eq!(
+        error.to_string(),
+        format!(
+            "InvalidFixvec at offset {}, path `raw.outputs[1].type_.args`",
+            offset
+        )
+    );
+}
+
+#[test]
+fn test_struct_path() {
+    let error = from_slice::<(u8, u32)>(&[1, 2, 3], true).unwrap_err();
+    assert!(matches!(
+        error.inner(),
+        Error::MismatchedLength {
+            expected: 4,
+            actual: 2
+        }
+    ));
+    assert_eq!(error.offset(), Some(1));
+    assert_eq!(error.path(), Some("[1]"));
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+enum Union {
+    A(u32),
+    B { f1: u8, f2: Vec<u16> },
+}
+
+#[test]
+fn test_union_path() {
+    let mut bytes = to_vec(&Union::B { f1: 1, f2: vec![1] }, false).unwrap();
+    // f2: 1 item of 2 bytes, truncate it
+    bytes.pop();
+    let error = from_slice::<Union>(&bytes, false).unwrap_err();
+    assert_eq!(error.path(), Some("B"));
+    assert!(matches!(
+        error.inner(),
+        Error::InvalidTableLength {
+            expected: 19,
+            actual: 18
+        }
+    ));
+
+    let mut bytes = to_vec(&Union::A(1), false).unwrap();
+    bytes.pop();
+    let error = from_slice::<Union>(&bytes, false).unwrap_err();
+    assert_eq!(error.offset(), Some(4));
+    assert_eq!(error.path(), Some("A"));
+    assert!(matches!(
+        error.inner(),
+        Error::MismatchedLength {
+            expected: 4,
+            actual: 3
+        }
+    ));
+}
+
+#[test]
+fn test_top_level_error() {
+    let error = from_slice::<Transaction>(&[1, 2, 3], false).unwrap_err();
+    assert!(matches!(
+        error,
+        Error::LengthNotEnough {
+            expected: 4,
+            actual: 3
+        }
+    ));
+    assert_eq!(error.offset(), None);
+    assert_eq!(error.path(), None);
+}
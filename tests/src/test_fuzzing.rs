@@ -1,8 +1,9 @@
 // test cases about bugs found in fuzzing tests
 
 use serde::{Deserialize, Serialize};
-use serde_molecule::{dynvec_serde, error::Result, struct_serde};
-use serde_molecule::{from_slice, to_vec};
+use serde_molecule::{dynvec_serde, error::Error, error::Result, struct_serde};
+use serde_molecule::{from_slice, from_slice_with, to_vec, DeserializerOptions};
+use std::collections::BTreeMap;
 
 #[derive(Serialize, Deserialize)]
 struct Struct0 {
@@ -25,7 +26,105 @@ struct Struct1 {
     pub f10: Struct0,
 }
 
+#[test]
 fn test_oom() {
     let bytes = include_bytes!("../test-data/oom-dump");
     let _: Result<Struct1> = from_slice(bytes, false);
 }
+
+#[test]
+fn test_oom_with_limits() {
+    let bytes = include_bytes!("../test-data/oom-dump");
+    let options = DeserializerOptions::new()
+        .max_items(1024)
+        .max_alloc(1 << 20);
+    let _: Result<Struct1> = from_slice_with(bytes, &options);
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+enum Nested {
+    Leaf(u8),
+    Node(Box<Nested>),
+}
+
+fn nested(depth: usize) -> Nested {
+    let mut value = Nested::Leaf(0);
+    for _ in 0..depth {
+        value = Nested::Node(Box::new(value));
+    }
+    value
+}
+
+#[test]
+fn test_depth_limit() {
+    let bytes = to_vec(&nested(100), false).unwrap();
+    let value: Nested = from_slice(&bytes, false).unwrap();
+    assert_eq!(value, nested(100));
+
+    let options = DeserializerOptions::new().max_depth(50);
+    let result: Result<Nested> = from_slice_with(&bytes, &options);
+    assert!(matches!(
+        result.unwrap_err().inner(),
+        Error::DepthLimitExceeded
+    ));
+
+    // deeper than the default limit
+    let bytes = to_vec(&nested(1000), false).unwrap();
+    let result: Result<Nested> = from_slice(&bytes, false);
+    assert!(matches!(
+        result.unwrap_err().inner(),
+        Error::DepthLimitExceeded
+    ));
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Limited {
+    f0: Vec<u32>,
+    #[serde(with = "dynvec_serde")]
+    f1: Vec<String>,
+    f2: BTreeMap<u8, u8>,
+}
+
+#[test]
+fn test_item_limit() {
+    let value = Limited {
+        f0: vec![1, 2, 3],
+        f1: vec!["a".into(), "b".into()],
+        f2: [(1, 1), (2, 2)].into_iter().collect(),
+    };
+    let bytes = to_vec(&value, false).unwrap();
+    let options = DeserializerOptions::new().max_items(3);
+    let decoded: Limited = from_slice_with(&bytes, &options).unwrap();
+    assert_eq!(decoded, value);
+
+    for (f0, f1, f2) in [(4, 2, 2), (3, 4, 2), (3, 2, 4)] {
+        let value = Limited {
+            f0: vec![0; f0],
+            f1: vec!["a".into(); f1],
+            f2: (0..f2).map(|i| (i, i)).collect(),
+        };
+        let bytes = to_vec(&value, false).unwrap();
+        let result: Result<Limited> = from_slice_with(&bytes, &options);
+        assert!(matches!(
+            result.unwrap_err().inner(),
+            Error::ItemLimitExceeded
+        ));
+    }
+}
+
+#[test]
+fn test_alloc_limit() {
+    let value = vec![String::from("hello"); 100];
+    #[derive(Serialize, Deserialize, Debug)]
+    struct Strings(#[serde(with = "dynvec_serde")] Vec<String>);
+    let bytes = to_vec(&Strings(value), false).unwrap();
+    let result: Result<Strings> =
+        from_slice_with(&bytes, &DeserializerOptions::new().max_alloc(10000));
+    assert!(result.is_ok());
+    let result: Result<Strings> =
+        from_slice_with(&bytes, &DeserializerOptions::new().max_alloc(1000));
+    assert!(matches!(
+        result.unwrap_err().inner(),
+        Error::AllocLimitExceeded
+    ));
+}
@@ -0,0 +1,175 @@
+use serde::Serialize;
+use serde_molecule::guess::guess;
+use serde_molecule::inspect::Kind;
+use serde_molecule::to_vec;
+
+// The witness of a third-party lock script.
+#[derive(Serialize)]
+struct LockWitness {
+    pubkey: Vec<u8>,
+    #[serde(with = "serde_molecule::dynvec_serde")]
+    proofs: Vec<Vec<u8>>,
+}
+
+#[derive(Serialize)]
+struct WitnessArgs {
+    lock: Option<Vec<u8>>,
+    input_type: Option<Vec<u8>>,
+    output_type: Option<Vec<u8>>,
+}
+
+fn witness() -> Vec<u8> {
+    let lock = LockWitness {
+        pubkey: vec![0xaa; 5],
+        proofs: vec![vec![1, 2], vec![], vec![3, 4, 5]],
+    };
+    let witness = WitnessArgs {
+        lock: Some(to_vec(&lock, false).unwrap()),
+        input_type: None,
+        output_type: Some(vec![9]),
+    };
+    to_vec(&witness, false).unwrap()
+}
+
+#[test]
+fn test_guess_witness() {
+    let guesses = guess(&witness(), 4);
+    assert_eq!(guesses.len(), 4);
+    assert_eq!(
+        guesses[0].node.to_string(),
+        "\
+0           79  unknown table, header [79, 16, 74, 74]
+16          58    [0]: unknown fixvec, count 54
+20          54      unknown table, header [54, 12, 21]
+32           9        [0]: unknown fixvec, count 5 = 0xaaaaaaaaaa
+41          33        [1]: unknown dynvec, header [33, 16, 22, 26]
+57           6          [0]: unknown fixvec, count 2 = 0x0102
+63           4          [1]: unknown fixvec, count 0 = 0x
+67           7          [2]: unknown fixvec, count 3 = 0x030405
+74           0    [1]: unknown raw = 0x
+74           5    [2]: unknown fixvec, count 1 = 0x09
+"
+    );
+    assert_eq!(guesses[0].score, 64);
+    // the alternatives read one fixvec as raw bytes, 4 bits less
+    assert!(guesses[1..].iter().all(|guess| guess.score == 60));
+    assert_eq!(guesses[0].confidence, 16.0 / 19.0);
+    let total: f64 = guesses.iter().map(|guess| guess.confidence).sum();
+    assert!((total - 1.0).abs() < 1e-9);
+    assert_eq!(
+        guesses[1].node.children[2].value.as_deref(),
+        Some("0x0100000009")
+    );
+}
+
+#[test]
+fn test_guess_raw() {
+    // neither a table nor a fixvec
+    let guesses = guess(&[0xff; 10], 3);
+    assert_eq!(guesses.len(), 1);
+    assert_eq!(guesses[0].node.kind, Kind::Raw);
+    assert_eq!(guesses[0].score, 0);
+    assert_eq!(guesses[0].confidence, 1.0);
+
+    let guesses = guess(&[], 3);
+    assert_eq!(
+        guesses[0].node.to_string(),
+        "0            0  unknown raw = 0x\n"
+    );
+
+    // the data as raw bytes is the last resort
+    let guesses = guess(&witness(), 100);
+    let last = guesses.last().unwrap();
+    assert_eq!(last.node.kind, Kind::Raw);
+    assert!(last.confidence < 1e-15);
+    let guesses = guess(&witness(), 0);
+    assert_eq!(guesses.len(), 1);
+    assert_eq!(guesses[0].score, 64);
+}
+
+#[test]
+fn test_guess_dynvec() {
+    #[derive(Serialize)]
+    struct Item {
+        id: u32,
+        name: Vec<u8>,
+    }
+    #[derive(Serialize)]
+    struct Items(#[serde(with = "serde_molecule::dynvec_serde")] Vec<Item>);
+    let items = Items(vec![
+        Item {
+            id: 1,
+            name: vec![],
+        },
+        Item {
+            id: 2,
+            name: b"ab".to_vec(),
+        },
+    ]);
+    let bytes = to_vec(&items, false).unwrap();
+    let node = &guess(&bytes, 1)[0].node;
+    assert_eq!(node.kind, Kind::Dynvec);
+    assert_eq!(node.children[1].kind, Kind::Table);
+    assert_eq!(
+        node.children[1].children[0].value.as_deref(),
+        Some("0x02000000")
+    );
+
+    // the items of different structure
+    #[derive(Serialize)]
+    struct Pair {
+        a: Vec<u8>,
+        b: u32,
+    }
+    let bytes = to_vec(&Pair { a: vec![1], b: 2 }, false).unwrap();
+    let node = &guess(&bytes, 1)[0].node;
+    assert_eq!(node.kind, Kind::Table);
+    assert_eq!(node.children[0].kind, Kind::Fixvec);
+    assert_eq!(node.children[1].kind, Kind::Raw);
+}
+
+#[test]
+fn test_guess_deep() {
+    // byte fixvecs nested in each other
+    let mut blob = vec![];
+    for _ in 0..3000 {
+        let mut outer = (blob.len() as u32).to_le_bytes().to_vec();
+        outer.extend(blob);
+        blob = outer;
+    }
+    let guesses = guess(&blob, 3);
+    // the content is guessed down to a fixed depth, then it's the value
+    let mut node = &guesses[0].node;
+    let mut depth = 0;
+    while let [child] = &node.children[..] {
+        assert_eq!(node.kind, Kind::Fixvec);
+        node = child;
+        depth += 1;
+    }
+    assert_eq!(depth, 63);
+    assert_eq!(node.kind, Kind::Fixvec);
+    assert_eq!(node.range, 252..blob.len());
+    assert_eq!(
+        node.value.as_ref().unwrap().len(),
+        2 + 2 * (blob.len() - 256)
+    );
+    assert_eq!(guesses[0].score, 64 * 4);
+}
+
+#[test]
+fn test_guess_wide() {
+    // a dynvec of many empty byte fixvecs
+    #[derive(Serialize)]
+    struct Blobs(#[serde(with = "serde_molecule::dynvec_serde")] Vec<Vec<u8>>);
+
+    let bytes = to_vec(&Blobs(vec![vec![]; 32768]), false).unwrap();
+    let guesses = guess(&bytes, 4);
+    assert_eq!(guesses.len(), 4);
+    let node = &guesses[0].node;
+    assert_eq!(node.kind, Kind::Dynvec);
+    assert_eq!(node.children.len(), 32768);
+    assert!(node.children.iter().all(|child| child.kind == Kind::Fixvec));
+    // the alternatives read one item as raw bytes
+    assert_eq!(guesses[1].score, guesses[0].score - 4);
+    assert_eq!(guesses[1].node.children[32767].kind, Kind::Raw);
+}
@@ -0,0 +1,211 @@
+use serde::{Deserialize, Serialize};
+use serde_molecule::inspect::{inspect, inspect_with_schema, Kind};
+use serde_molecule::schema::Schema;
+use serde_molecule::to_vec;
+use std::collections::BTreeMap;
+
+#[derive(Serialize, Deserialize)]
+struct Point {
+    x: u16,
+    y: i8,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Shape {
+    Dot(u32),
+    Label { text: String },
+}
+
+#[derive(Serialize, Deserialize)]
+struct Canvas {
+    #[serde(with = "serde_molecule::struct_serde")]
+    origin: Point,
+    points: Vec<Point>,
+    #[serde(with = "serde_molecule::dynvec_serde")]
+    shapes: Vec<Shape>,
+    tags: BTreeMap<u8, bool>,
+    id: Option<[u8; 2]>,
+}
+
+fn canvas() -> Canvas {
+    Canvas {
+        origin: Point { x: 1, y: -1 },
+        points: vec![Point { x: 2, y: 3 }],
+        shapes: vec![Shape::Dot(4), Shape::Label { text: "ab".into() }],
+        tags: [(5, true)].into(),
+        id: None,
+    }
+}
+
+#[test]
+fn test_inspect_type() {
+    let bytes = to_vec(&canvas(), false).unwrap();
+    let node = inspect::<Canvas>(&bytes, false).unwrap();
+    assert!(node.first_error().is_none());
+    assert_eq!(
+        node.to_string(),
+        "\
+0           94  Canvas table, header [94, 24, 27, 34, 72, 94]
+24           3    origin: Point struct
+24           2      x: u16 = 1
+26           1      y: i8 = -1
+27           7    points: Vec<Point> fixvec, count 1
+31           3      [0]: Point struct
+31           2        x: u16 = 2
+33           1        y: i8 = 3
+34          38    shapes: Vec<Shape> dynvec, header [38, 12, 20]
+46           8      [0]: Shape union, id 0
+50           4        Dot: u32 = 4
+54          18      [1]: Shape union, id 1
+58          14        Label table, header [14, 8]
+66           6          text: String fixvec, count 2 = \"ab\"
+72          22    tags: BTreeMap<u8, bool> dynvec, header [22, 8]
+80          14      [0]: entry table, header [14, 12, 13]
+92           1        key: u8 = 5
+93           1        value: bool = true
+94           0    id: Option<[u8; 2]> option, none
+"
+    );
+    assert_eq!(
+        node.hexdump(&bytes),
+        "\
+00000000  5e 00 00 00 18 00 00 00 1b 00 00 00 22 00 00 00  Canvas table, header [94, 24, 27, 34, 72, 94]
+00000010  48 00 00 00 5e 00 00 00
+00000018                                                     origin: Point struct
+00000018  01 00                                                x: u16 = 1
+0000001a  ff                                                   y: i8 = -1
+0000001b  01 00 00 00                                        points: Vec<Point> fixvec, count 1
+0000001f                                                       [0]: Point struct
+0000001f  02 00                                                  x: u16 = 2
+00000021  03                                                     y: i8 = 3
+00000022  26 00 00 00 0c 00 00 00 14 00 00 00                shapes: Vec<Shape> dynvec, header [38, 12, 20]
+0000002e  00 00 00 00                                          [0]: Shape union, id 0
+00000032  04 00 00 00                                            Dot: u32 = 4
+00000036  01 00 00 00                                          [1]: Shape union, id 1
+0000003a  0e 00 00 00 08 00 00 00                                Label table, header [14, 8]
+00000042  02 00 00 00 61 62                                        text: String fixvec, count 2 = \"ab\"
+00000048  16 00 00 00 08 00 00 00                            tags: BTreeMap<u8, bool> dynvec, header [22, 8]
+00000050  0e 00 00 00 0c 00 00 00 0d 00 00 00                  [0]: entry table, header [14, 12, 13]
+0000005c  05                                                     key: u8 = 5
+0000005d  01                                                     value: bool = true
+0000005e                                                     id: Option<[u8; 2]> option, none
+"
+    );
+
+    // packed
+    let bytes = to_vec(&Point { x: 1, y: 2 }, true).unwrap();
+    let node = inspect::<Point>(&bytes, true).unwrap();
+    assert_eq!(node.kind, Kind::Struct);
+    assert_eq!(node.children[1].value.as_deref(), Some("2"));
+    let node = inspect::<Point>(&[1, 0, 2, 3], true).unwrap();
+    assert_eq!(
+        node.error.as_deref(),
+        Some("MismatchedLength { expected: 3, actual: 4 }")
+    );
+}
+
+#[test]
+fn test_inspect_errors() {
+    let mut bytes = to_vec(&canvas(), false).unwrap();
+    // the union id of `shapes[1]`
+    bytes[54] = 7;
+    let node = inspect::<Canvas>(&bytes, false).unwrap();
+    let error = node.first_error().unwrap();
+    assert_eq!(error.label, "[1]");
+    assert_eq!(error.range, 54..72);
+    assert_eq!(error.error.as_deref(), Some("UnknownUnionId { id: 7 }"));
+    // the other parts are still inspected
+    assert_eq!(
+        node.children[3].children[0].children[1].value.as_deref(),
+        Some("true")
+    );
+
+    // the offset of `points`
+    let mut bytes = to_vec(&canvas(), false).unwrap();
+    bytes[8] = 28;
+    let node = inspect::<Canvas>(&bytes, false).unwrap();
+    assert_eq!(node.first_error().unwrap().label, "origin");
+    let node = inspect::<Canvas>(&bytes[..90], false).unwrap();
+    assert_eq!(
+        node.error.as_deref(),
+        Some("InvalidTableLength { expected: 94, actual: 90 }")
+    );
+    assert!(node.children.is_empty());
+}
+
+#[test]
+fn test_inspect_schema() {
+    let schema = Schema::parse(
+        "
+        array Uint16 [byte; 2];
+        struct Point { x: Uint16, y: byte }
+        vector PointVec <Point>;
+        vector Bytes <byte>;
+        option BytesOpt (Bytes);
+        union Shape { Point, Bytes: 5 }
+        table Canvas { points: PointVec, label: BytesOpt, shape: Shape }
+        ",
+    )
+    .unwrap();
+    let bytes = [
+        0x22, 0, 0, 0, 0x10, 0, 0, 0, 0x17, 0, 0, 0, 0x17, 0, 0, 0, // header
+        1, 0, 0, 0, 1, 2, 3, // points
+        5, 0, 0, 0, 3, 0, 0, 0, 7, 8, 9, // shape
+    ];
+    let node = inspect_with_schema(&bytes, &schema, "Canvas").unwrap();
+    assert_eq!(
+        node.to_string(),
+        "\
+0           34  Canvas table, header [34, 16, 23, 23]
+16           7    points: PointVec fixvec, count 1
+20           3      [0]: Point struct
+20           2        x: Uint16 array = 0x0102
+22           1        y: byte = 0x03
+23           0    label: BytesOpt option, none
+23          11    shape: Shape union, id 5
+27           7      Bytes fixvec, count 3 = 0x070809
+"
+    );
+    assert_eq!(
+        node.hexdump(&bytes),
+        "\
+00000000  22 00 00 00 10 00 00 00 17 00 00 00 17 00 00 00  Canvas table, header [34, 16, 23, 23]
+00000010  01 00 00 00                                        points: PointVec fixvec, count 1
+00000014                                                       [0]: Point struct
+00000014  01 02                                                  x: Uint16 array = 0x0102
+00000016  03                                                     y: byte = 0x03
+00000017                                                     label: BytesOpt option, none
+00000017  05 00 00 00                                        shape: Shape union, id 5
+0000001b  03 00 00 00 07 08 09                                 Bytes fixvec, count 3 = 0x070809
+"
+    );
+
+    let mut bytes = bytes;
+    bytes[16] = 2;
+    let node = inspect_with_schema(&bytes, &schema, "Canvas").unwrap();
+    let error = node.first_error().unwrap();
+    assert_eq!(error.label, "points");
+    assert_eq!(error.error.as_deref(), Some("InvalidFixvec"));
+
+    // empty fixvec with trailing bytes
+    let bytes = [0, 0, 0, 0, 0xff, 0xff];
+    let node = inspect_with_schema(&bytes, &schema, "Bytes").unwrap();
+    assert_eq!(
+        node.first_error().unwrap().error.as_deref(),
+        Some("InvalidFixvec")
+    );
+    let node = inspect_with_schema(&bytes, &schema, "PointVec").unwrap();
+    assert_eq!(
+        node.first_error().unwrap().error.as_deref(),
+        Some("InvalidFixvec")
+    );
+    let node = inspect::<Vec<u16>>(&bytes, false).unwrap();
+    assert_eq!(
+        node.first_error().unwrap().error.as_deref(),
+        Some("InvalidFixvec")
+    );
+    let node = inspect::<Vec<u16>>(&bytes[..4], false).unwrap();
+    assert!(node.first_error().is_none());
+    let error = inspect_with_schema(&bytes, &schema, "Unknown").unwrap_err();
+    assert_eq!(error.to_string(), "undefined type `Unknown`");
+}
@@ -0,0 +1,269 @@
+use crate::ckb_types::{Block, Transaction};
+use serde::{Deserialize, Serialize};
+use serde_molecule::mol::to_mol;
+use serde_molecule::{dynvec_serde, molecule, struct_serde};
+use std::collections::BTreeMap;
+
+#[test]
+fn test_mol_ckb_types() {
+    let mol = to_mol::<Transaction>(false).unwrap();
+    assert_eq!(
+        mol,
+        "\
+array U32 [byte; 4];
+array Byte32 [byte; 32];
+
+struct OutPoint {
+    tx_hash: Byte32,
+    index: U32,
+}
+
+struct CellDep {
+    out_point: OutPoint,
+    dep_type: byte,
+}
+
+vector CellDepVec <CellDep>;
+vector Byte32Vec <Byte32>;
+array U64 [byte; 8];
+
+struct CellInput {
+    since: U64,
+    previous_output: OutPoint,
+}
+
+vector CellInputVec <CellInput>;
+vector Bytes <byte>;
+
+table Script {
+    code_hash: Byte32,
+    hash_type: byte,
+    args: Bytes,
+}
+
+option ScriptOpt (Script);
+
+table CellOutput {
+    capacity: U64,
+    lock: Script,
+    type_: ScriptOpt,
+}
+
+vector CellOutputVec <CellOutput>;
+vector BytesVec <Bytes>;
+
+table RawTransaction {
+    version: U32,
+    cell_deps: CellDepVec,
+    header_deps: Byte32Vec,
+    inputs: CellInputVec,
+    outputs: CellOutputVec,
+    outputs_data: BytesVec,
+}
+
+table Transaction {
+    raw: RawTransaction,
+    witnesses: BytesVec,
+}
+"
+    );
+}
+
+#[test]
+fn test_mol_struct_and_table() {
+    // `Header` is a molecule struct in `Block` and a table in `UncleBlock`.
+    let mol = to_mol::<Block>(false).unwrap();
+    assert!(mol.contains("struct HeaderStruct {\n    raw: RawHeader,\n    nonce: U128,\n}"));
+    assert!(mol.contains("table Header {\n    raw: RawHeader,\n    nonce: U128,\n}"));
+    assert!(mol.contains("table Block {\n    header: HeaderStruct,\n"));
+
+    let mol = to_mol::<crate::ckb_types::OutPoint>(true).unwrap();
+    assert!(mol.ends_with("struct OutPoint {\n    tx_hash: Byte32,\n    index: U32,\n}\n"));
+}
+
+#[derive(Serialize, Deserialize)]
+struct Struct1 {
+    f1: u8,
+    f2: u16,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Table1 {
+    f1: u8,
+    f2: u16,
+    #[serde(with = "dynvec_serde")]
+    dynvec: Vec<Vec<u8>>,
+    #[serde(with = "struct_serde")]
+    struct1: Struct1,
+    option: Option<u128>,
+    array3: [u8; 3],
+    string: String,
+    map: BTreeMap<u128, Vec<u8>>,
+    tuple: Tuple1,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Tuple1([u16; 2], Vec<Struct1>);
+
+#[test]
+fn test_mol_types() {
+    let mol = to_mol::<Table1>(false).unwrap();
+    assert_eq!(
+        mol,
+        "\
+array U16 [byte; 2];
+vector Bytes <byte>;
+vector BytesVec <Bytes>;
+
+struct Struct1 {
+    f1: byte,
+    f2: U16,
+}
+
+array U128 [byte; 16];
+option U128Opt (U128);
+array Byte3 [byte; 3];
+vector String <byte>;
+
+table U128BytesMapEntry {
+    key: U128,
+    value: Bytes,
+}
+
+vector U128BytesMap <U128BytesMapEntry>;
+array U16Array2 [U16; 2];
+vector Struct1Vec <Struct1>;
+
+table Tuple1 {
+    f0: U16Array2,
+    f1: Struct1Vec,
+}
+
+table Table1 {
+    f1: byte,
+    f2: U16,
+    dynvec: BytesVec,
+    struct1: Struct1,
+    option: U128Opt,
+    array3: Byte3,
+    string: String,
+    map: U128BytesMap,
+    tuple: Tuple1,
+}
+"
+    );
+}
+
+#[derive(Serialize, Deserialize)]
+enum Enum1 {
+    U16(u16),
+    Pair(u8, String),
+    Named { f1: Vec<u8> },
+}
+
+#[molecule(union)]
+#[derive(Serialize, Deserialize)]
+enum Union1 {
+    #[molecule(union_id = 4278190081)]
+    Table1(Table1),
+    U32(u32),
+    #[molecule(fallback)]
+    Unknown {
+        id: u32,
+        body: Vec<u8>,
+    },
+}
+
+#[test]
+fn test_mol_union() {
+    let mol = to_mol::<Enum1>(false).unwrap();
+    assert_eq!(
+        mol,
+        "\
+array U16 [byte; 2];
+vector String <byte>;
+
+table Enum1Pair {
+    f0: byte,
+    f1: String,
+}
+
+vector Bytes <byte>;
+
+table Enum1Named {
+    f1: Bytes,
+}
+
+union Enum1 {
+    U16,
+    Enum1Pair,
+    Enum1Named,
+}
+"
+    );
+
+    // the fallback variant isn't a union item
+    let mol = to_mol::<Union1>(false).unwrap();
+    assert!(mol.ends_with(
+        "\
+array U32 [byte; 4];
+
+union Union1 {
+    Table1: 4278190081,
+    U32: 4278190082,
+}
+"
+    ));
+}
+
+#[derive(Deserialize)]
+struct DynvecOfFixed {
+    #[serde(with = "dynvec_serde")]
+    f1: Vec<u32>,
+}
+
+#[derive(Deserialize)]
+enum WithUnit {
+    A,
+    B(u8),
+}
+
+#[derive(Deserialize)]
+enum SameItem {
+    A(u8),
+    B(u8),
+}
+
+#[derive(Deserialize)]
+struct ArrayOfTables {
+    f1: [Struct1; 2],
+}
+
+#[test]
+fn test_mol_errors() {
+    let error = to_mol::<DynvecOfFixed>(false).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "dynvec of fixed size `U32` has no molecule schema"
+    );
+    let error = to_mol::<WithUnit>(false).unwrap_err();
+    assert_eq!(error.to_string(), "`WithUnit::A` has no molecule schema");
+    let error = to_mol::<SameItem>(false).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "`byte` is used by more than one variant of union `SameItem`"
+    );
+    let error = to_mol::<ArrayOfTables>(false).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "array of `Struct1` has no molecule schema, the items must be fixed size"
+    );
+    let error = to_mol::<(u8, u32)>(false).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "tuple of different types has no molecule schema, use a molecule struct"
+    );
+    // in molecule struct, it's a struct
+    let mol = to_mol::<(u8, u32)>(true).unwrap();
+    assert!(mol.ends_with("struct ByteU32Tuple {\n    f0: byte,\n    f1: U32,\n}\n"));
+}
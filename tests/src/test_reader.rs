@@ -0,0 +1,271 @@
+use serde::{Deserialize, Serialize};
+use serde_molecule::reader::MoleculeReader as _;
+use serde_molecule::{dynvec_serde, from_slice, molecule, to_vec, Error, MoleculeReader};
+
+#[molecule(struct)]
+#[derive(Serialize, Deserialize, MoleculeReader, Clone, Default, PartialEq, Debug)]
+pub struct OutPoint {
+    pub tx_hash: [u8; 32],
+    pub index: u32,
+}
+
+#[molecule(struct)]
+#[derive(Serialize, Deserialize, MoleculeReader, Clone, Default, PartialEq, Debug)]
+pub struct CellInput {
+    pub since: u64,
+    pub previous_output: OutPoint,
+}
+
+#[derive(Serialize, Deserialize, MoleculeReader, Clone, Default, PartialEq, Debug)]
+pub struct Script {
+    pub code_hash: [u8; 32],
+    pub hash_type: u8,
+    pub args: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, MoleculeReader, Clone, Default, PartialEq, Debug)]
+pub struct CellOutput {
+    pub capacity: u64,
+    pub lock: Script,
+    pub type_: Option<Script>,
+}
+
+#[derive(Serialize, Deserialize, MoleculeReader, Clone, Default, PartialEq, Debug)]
+pub struct RawTransaction {
+    pub version: u32,
+    pub inputs: Vec<CellInput>,
+    #[serde(with = "dynvec_serde")]
+    pub outputs: Vec<CellOutput>,
+    #[serde(with = "dynvec_serde")]
+    pub outputs_data: Vec<Vec<u8>>,
+}
+
+#[derive(Serialize, Deserialize, MoleculeReader, Clone, Default, PartialEq, Debug)]
+pub struct Transaction {
+    pub raw: RawTransaction,
+    #[serde(with = "dynvec_serde")]
+    pub witnesses: Vec<Vec<u8>>,
+}
+
+fn transaction() -> Transaction {
+    let mut tx = Transaction::default();
+    tx.raw.version = 1;
+    tx.raw.inputs = vec![CellInput {
+        since: 2,
+        previous_output: OutPoint {
+            tx_hash: [3; 32],
+            index: 4,
+        },
+    }];
+    for i in 0..4u8 {
+        tx.raw.outputs.push(CellOutput {
+            capacity: 100 + i as u64,
+            lock: Script {
+                code_hash: [i; 32],
+                hash_type: 1,
+                args: vec![i; i as usize],
+            },
+            type_: None,
+        });
+        tx.raw.outputs_data.push(vec![]);
+    }
+    tx.raw.outputs[3].type_ = Some(Script::default());
+    tx.witnesses = vec![vec![5, 6]];
+    tx
+}
+
+#[test]
+fn test_reader_transaction() {
+    let tx = transaction();
+    let bytes = to_vec(&tx, false).unwrap();
+    let reader = TransactionReader::new(&bytes).unwrap();
+    assert_eq!(reader.as_slice(), &bytes[..]);
+    assert!(!reader.has_extra_fields());
+
+    let raw = reader.raw().unwrap();
+    assert_eq!(raw.version().unwrap(), 1);
+    let input = raw.inputs().unwrap().get(0).unwrap();
+    assert_eq!(input.since().unwrap(), 2);
+    let out_point = input.previous_output().unwrap();
+    assert_eq!(out_point.tx_hash().unwrap().as_array(), &[3; 32]);
+    assert_eq!(out_point.index().unwrap(), 4);
+    let value: OutPoint = from_slice(out_point.as_slice(), true).unwrap();
+    assert_eq!(value, tx.raw.inputs[0].previous_output);
+
+    let outputs = raw.outputs().unwrap();
+    assert_eq!(outputs.len(), 4);
+    let lock = outputs.get(3).unwrap().lock().unwrap();
+    assert_eq!(lock.args().unwrap().body(), &[3, 3, 3]);
+    assert_eq!(lock.code_hash().unwrap().get(0).unwrap(), 3);
+    let type_ = outputs.get(3).unwrap().type_().unwrap().unwrap();
+    assert!(type_.args().unwrap().is_empty());
+    for (i, output) in outputs.iter().enumerate() {
+        let output = output.unwrap();
+        assert_eq!(output.capacity().unwrap(), 100 + i as u64);
+        let value: CellOutput = from_slice(output.as_slice(), false).unwrap();
+        assert_eq!(value, tx.raw.outputs[i]);
+    }
+    assert!(raw.outputs_data().unwrap().get(0).unwrap().is_empty());
+    let witness = reader.witnesses().unwrap().get(0).unwrap();
+    assert_eq!(
+        witness.iter().collect::<Result<Vec<_>, _>>().unwrap(),
+        [5, 6]
+    );
+
+    // through the trait
+    let reader = Transaction::read(&bytes).unwrap();
+    assert_eq!(reader.raw().unwrap().version().unwrap(), 1);
+}
+
+#[molecule(struct)]
+#[derive(Serialize, Deserialize, MoleculeReader)]
+pub struct Hash([u8; 32]);
+
+#[molecule(union)]
+#[derive(Serialize, Deserialize, MoleculeReader)]
+pub enum Lock {
+    #[molecule(union_id = 10)]
+    Secp(Hash),
+    Multisig(Vec<Hash>),
+    Always,
+    #[molecule(fallback)]
+    Unknown {
+        id: u32,
+        body: Vec<u8>,
+    },
+}
+
+#[derive(Serialize, Deserialize, MoleculeReader)]
+pub enum Kind {
+    Empty,
+    Name(String),
+}
+
+#[derive(Serialize, Deserialize, MoleculeReader)]
+pub struct Account {
+    pub name: String,
+    pub active: bool,
+    pub lock: Lock,
+    pub kind: Option<Kind>,
+    #[serde(skip)]
+    pub cache: u64,
+    pub balance: i128,
+}
+
+#[test]
+fn test_reader_union() {
+    let account = Account {
+        name: "alice".into(),
+        active: true,
+        lock: Lock::Multisig(vec![Hash([1; 32]), Hash([2; 32])]),
+        kind: Some(Kind::Name("bob".into())),
+        cache: 0,
+        balance: -1,
+    };
+    let bytes = to_vec(&account, false).unwrap();
+    let reader = AccountReader::new(&bytes).unwrap();
+    assert_eq!(reader.name().unwrap(), "alice");
+    assert!(reader.active().unwrap());
+    assert_eq!(reader.balance().unwrap(), -1);
+    match reader.lock().unwrap() {
+        LockReader::Multisig(hashes) => {
+            let hash: HashReader = hashes.get(1).unwrap();
+            assert_eq!(hash.as_array(), &[2; 32]);
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    match reader.kind().unwrap() {
+        Some(KindReader::Name(name)) => assert_eq!(name, "bob"),
+        other => panic!("unexpected {:?}", other),
+    }
+
+    let read = |lock: &Lock| {
+        let bytes = to_vec(lock, false).unwrap();
+        format!("{:?}", LockReader::new(&bytes).unwrap())
+    };
+    assert!(read(&Lock::Secp(Hash([7; 32]))).starts_with("Secp(ArrayReader([7, 7"));
+    assert_eq!(read(&Lock::Always), "Always");
+    let unknown = Lock::Unknown {
+        id: 3,
+        body: vec![8, 9],
+    };
+    assert_eq!(read(&unknown), "Unknown(3, [8, 9])");
+    let bytes = to_vec(&Kind::Empty, false).unwrap();
+    assert!(matches!(
+        KindReader::new(&bytes).unwrap(),
+        KindReader::Empty
+    ));
+    let error = KindReader::new(&[2, 0, 0, 0]).unwrap_err();
+    assert!(matches!(error, Error::UnknownUnionId { id: 2 }));
+}
+
+// A newer `Script` with an extra field
+#[derive(Serialize)]
+pub struct ScriptV2 {
+    pub code_hash: [u8; 32],
+    pub hash_type: u8,
+    pub args: Vec<u8>,
+    pub extra: u32,
+}
+
+#[test]
+fn test_reader_errors() {
+    let script = ScriptV2 {
+        code_hash: [0; 32],
+        hash_type: 2,
+        args: vec![1],
+        extra: 3,
+    };
+    let bytes = to_vec(&script, false).unwrap();
+    let reader = ScriptReader::new(&bytes).unwrap();
+    assert!(reader.has_extra_fields());
+    assert_eq!(reader.hash_type().unwrap(), 2);
+
+    let bytes = to_vec(&transaction(), false).unwrap();
+    let error = TransactionReader::new(&bytes[..bytes.len() - 1]).unwrap_err();
+    assert!(matches!(error, Error::InvalidTableLength { .. }));
+    let reader = TransactionReader::new(&bytes).unwrap();
+    let error = reader.raw().unwrap().outputs().unwrap().get(4).unwrap_err();
+    assert!(matches!(
+        error,
+        Error::IndexOutOfBounds { index: 4, len: 4 }
+    ));
+
+    // the header of `raw` is checked when it's read
+    let mut bytes = bytes;
+    bytes[12] = 0xff;
+    let reader = TransactionReader::new(&bytes).unwrap();
+    let error = reader.raw().unwrap_err();
+    assert_eq!(error.path(), Some("raw"));
+    assert_eq!(error.offset(), Some(12));
+    assert!(matches!(error.inner(), Error::InvalidTableLength { .. }));
+
+    let error = OutPointReader::new(&[0; 35]).unwrap_err();
+    assert!(matches!(
+        error,
+        Error::MismatchedLength {
+            expected: 36,
+            actual: 35
+        }
+    ));
+    let bytes = to_vec(
+        &Account {
+            name: "a".into(),
+            active: true,
+            lock: Lock::Always,
+            kind: None,
+            cache: 0,
+            balance: 0,
+        },
+        false,
+    )
+    .unwrap();
+    let mut bytes = bytes;
+    // the content of `name`
+    let offset = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+    bytes[offset + 4] = 0xff;
+    let error = AccountReader::new(&bytes).unwrap().name().unwrap_err();
+    assert_eq!(error.path(), Some("name"));
+    assert_eq!(error.offset(), Some(offset));
+    assert!(matches!(error.inner(), Error::InvalidUtf8 { .. }));
+}
@@ -0,0 +1,210 @@
+use serde_molecule::schema::{parse, Ast, Decl, DeclKind, Field, Import, Schema, UnionItem};
+use serde_molecule::Error;
+use std::collections::BTreeMap;
+
+const SCHEMAS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/schemas/");
+
+#[test]
+fn test_parse() {
+    let ast = parse(
+        "import ../common/types;
+/* block
+   comment */
+array Array3 [byte; 3]; // line comment
+struct Struct1 { f1: byte, f2: Array3 }
+union Enum1 {
+    Array3: 5,
+    Struct1,
+}",
+    )
+    .unwrap();
+    assert_eq!(
+        ast,
+        Ast {
+            imports: vec![Import {
+                path: "../common/types".into(),
+                line: 1,
+            }],
+            decls: vec![
+                Decl {
+                    name: "Array3".into(),
+                    kind: DeclKind::Array {
+                        item: "byte".into(),
+                        count: 3,
+                    },
+                    line: 4,
+                },
+                Decl {
+                    name: "Struct1".into(),
+                    kind: DeclKind::Struct(vec![
+                        Field {
+                            name: "f1".into(),
+                            ty: "byte".into(),
+                        },
+                        Field {
+                            name: "f2".into(),
+                            ty: "Array3".into(),
+                        },
+                    ]),
+                    line: 5,
+                },
+                Decl {
+                    name: "Enum1".into(),
+                    kind: DeclKind::Union(vec![
+                        UnionItem {
+                            ty: "Array3".into(),
+                            id: 5,
+                        },
+                        UnionItem {
+                            ty: "Struct1".into(),
+                            id: 6,
+                        },
+                    ]),
+                    line: 6,
+                },
+            ],
+        }
+    );
+}
+
+#[test]
+fn test_schema_test1() {
+    let schema = Schema::load(format!("{}test1.mol", SCHEMAS)).unwrap();
+    assert_eq!(schema.decls().len(), 15);
+    assert_eq!(schema.fixed_size("byte"), Some(1));
+    assert_eq!(schema.fixed_size("Struct1"), Some(3));
+    assert_eq!(schema.fixed_size("Array3"), Some(3));
+    assert_eq!(schema.fixed_size("Table1"), None);
+    assert_eq!(schema.fixed_size("Unknown"), None);
+    assert_eq!(
+        schema.get("BytesVector").unwrap().kind,
+        DeclKind::Vector("Bytes".into())
+    );
+    match &schema.get("Table1").unwrap().kind {
+        DeclKind::Table(fields) => assert_eq!(fields.len(), 13),
+        kind => panic!("{:?}", kind),
+    }
+}
+
+#[test]
+fn test_schema_ckb() {
+    // protocols.mol imports blockchain.mol and extensions.mol, which imports
+    // blockchain.mol again.
+    let schema = Schema::load(format!("{}ckb/protocols.mol", SCHEMAS)).unwrap();
+    assert_eq!(schema.decls()[0].name, "Uint32");
+    let names: Vec<_> = schema.decls().iter().map(|d| d.name.as_str()).collect();
+    assert_eq!(names.iter().filter(|n| **n == "Uint32").count(), 1);
+    assert_eq!(schema.fixed_size("RawHeader"), Some(192));
+    assert_eq!(schema.fixed_size("Header"), Some(208));
+    match &schema.get("SyncMessage").unwrap().kind {
+        DeclKind::Union(items) => {
+            let ids: Vec<_> = items.iter().map(|i| i.id).collect();
+            assert_eq!(ids, [0, 1, 2, 3, 8]);
+        }
+        kind => panic!("{:?}", kind),
+    }
+}
+
+#[test]
+fn test_schema_imports() {
+    let files: BTreeMap<&str, &str> = [
+        ("types/basic.mol", "array Uint32 [byte; 4];"),
+        (
+            "types/block.mol",
+            "import ./basic;\nstruct Header { number: Uint32 }",
+        ),
+        ("loop/a.mol", "import ../loop/b;"),
+        ("loop/b.mol", "\nimport a;"),
+    ]
+    .into();
+    let mut loaded = vec![];
+    let schema = Schema::parse_with(
+        "import types/basic;\nimport types/block;\ntable Block { header: Header }",
+        |path| {
+            loaded.push(path.to_string());
+            Ok(files[path].to_string())
+        },
+    )
+    .unwrap();
+    assert_eq!(loaded, ["types/basic.mol", "types/block.mol"]);
+    let names: Vec<_> = schema.decls().iter().map(|d| d.name.as_str()).collect();
+    assert_eq!(names, ["Uint32", "Header", "Block"]);
+
+    let error = Schema::parse_with("import loop/a;", |path| Ok(files[path].to_string()));
+    assert_eq!(
+        error.unwrap_err().to_string(),
+        "circular import `loop/a.mol` in `loop/b.mol` at line 2"
+    );
+    let error = Schema::parse("import types/basic;").unwrap_err();
+    assert!(matches!(error, Error::Message(_)));
+}
+
+#[test]
+fn test_schema_errors() {
+    let cases = [
+        ("array A [byte 3];", "expected `;`, found `3` at line 1"),
+        (
+            "table A {\n  f1 byte }",
+            "expected `:`, found `b` at line 2",
+        ),
+        (
+            "vector A <byte>",
+            "expected `;`, found end of file at line 1",
+        ),
+        (
+            "enum A { B }",
+            "expected declaration, found `enum` at line 1",
+        ),
+        ("/* comment", "unterminated comment at line 1"),
+        (
+            "union A { B: 4294967296 }",
+            "union id 4294967296 overflows at line 1",
+        ),
+        ("table A { f1: B }", "undefined type `B` at line 1"),
+        (
+            "vector A <byte>;\noption A (byte);",
+            "type `A` is defined more than once at line 2",
+        ),
+        (
+            "array byte [byte; 1];",
+            "type `byte` is defined more than once at line 1",
+        ),
+        (
+            "table A { f1: byte, f1: byte }",
+            "field `f1` of `A` is defined more than once at line 1",
+        ),
+        (
+            "array A [byte; 0];",
+            "array `A` must have at least one item at line 1",
+        ),
+        (
+            "struct A {}",
+            "struct `A` must have at least one field at line 1",
+        ),
+        (
+            "vector B <byte>;\nunion A { B, B }",
+            "`B` is more than once in union `A` at line 2",
+        ),
+        (
+            "vector B <byte>;\nvector C <byte>;\nunion A { B: 1, C: 1 }",
+            "union id 1 is used more than once in union `A` at line 3",
+        ),
+        (
+            "table A { b: BOpt }\noption BOpt (B);\ntable B { a: A }",
+            "recursive type `A` at line 1",
+        ),
+        (
+            "vector Bytes <byte>;\nstruct A { f1: byte, f2: Bytes }",
+            "struct `A` can't have dynamic size field `f2: Bytes` at line 2",
+        ),
+        (
+            "table T {}\narray A [T; 2];",
+            "array `A` can't have dynamic size item `T` at line 2",
+        ),
+    ];
+    for (text, message) in cases {
+        let error = Schema::parse(text).unwrap_err();
+        assert!(matches!(error, Error::InvalidSchema { .. }));
+        assert_eq!(error.to_string(), message, "{}", text);
+    }
+}
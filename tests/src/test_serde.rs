@@ -1,6 +1,8 @@
 use crate::test_once;
 use serde::{Deserialize, Serialize};
-use serde_molecule::{dynvec_serde, from_slice, struct_serde, to_vec};
+use serde_molecule::{
+    dynvec_serde, from_slice, from_slice_with, struct_serde, to_vec, DeserializerOptions,
+};
 use std::collections::{BTreeMap, LinkedList};
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, Default, Debug)]
@@ -201,6 +203,52 @@ fn test_nested_struct() {
     assert_eq!(value2.f1, 0x12345678);
 }
 
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+struct StructOpt {
+    // molecule option of struct: empty for `None`
+    #[serde(with = "struct_serde")]
+    pub s0: Option<StructInner>,
+    pub f1: u8,
+}
+
+#[test]
+fn test_struct_option() {
+    for s0 in [Some(StructInner { f0: 0x87654321 }), None] {
+        let value = StructOpt { s0, f1: 1 };
+        test_once(&value);
+        let bytes = to_vec(&value, false).unwrap();
+        let value2: StructOpt = from_slice(&bytes, false).unwrap();
+        assert_eq!(value2, value);
+    }
+    // an `Option` field in molecule struct isn't allowed
+    #[derive(Serialize, Deserialize, Debug)]
+    struct Inner {
+        f0: Option<u32>,
+    }
+    #[derive(Serialize, Deserialize, Debug)]
+    struct Outer {
+        #[serde(with = "struct_serde")]
+        s0: Inner,
+    }
+    #[derive(Serialize)]
+    struct Encoded {
+        #[serde(with = "struct_serde")]
+        s0: StructInner,
+    }
+    let bytes = to_vec(
+        &Encoded {
+            s0: StructInner { f0: 1 },
+        },
+        false,
+    )
+    .unwrap();
+    let error = from_slice::<Outer>(&bytes, false).unwrap_err();
+    assert!(matches!(
+        error.inner(),
+        serde_molecule::Error::InvalidStructField
+    ));
+}
+
 #[derive(Serialize, Deserialize)]
 enum UnionWithDynvec {
     #[serde(with = "dynvec_serde")]
@@ -334,3 +382,112 @@ fn test_char() {
     let c = 'a';
     test_once(&c);
 }
+
+// serialized through `serialize_map(None)`, the number of entries is unknown up front
+struct FilteredMap(BTreeMap<u32, String>);
+
+impl Serialize for FilteredMap {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().filter(|(k, _)| **k != 0))
+    }
+}
+
+#[test]
+fn test_map_unknown_length() {
+    let mut map = BTreeMap::new();
+    map.insert(0, "skipped".to_string());
+    map.insert(1, "hi".to_string());
+    map.insert(100, "hi100".to_string());
+    let bytes = to_vec(&FilteredMap(map.clone()), false).unwrap();
+    map.remove(&0);
+    assert_eq!(bytes, to_vec(&map, false).unwrap());
+    let mut streamed = vec![];
+    serde_molecule::to_writer(&mut streamed, &FilteredMap(map.clone()), false).unwrap();
+    assert_eq!(bytes, streamed);
+    let size = serde_molecule::serialized_size(&FilteredMap(map.clone()), false).unwrap();
+    assert_eq!(bytes.len(), size);
+
+    let empty = FilteredMap(BTreeMap::new());
+    assert_eq!(to_vec(&empty, false).unwrap(), vec![4, 0, 0, 0]);
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct TableWithString {
+    f1: u8,
+    name: String,
+}
+
+#[test]
+fn test_invalid_utf8() {
+    let value = TableWithString {
+        f1: 1,
+        name: "hello".into(),
+    };
+    let mut bytes = to_vec(&value, false).unwrap();
+    // header(12) + f1(1) + string length(4)
+    assert_eq!(&bytes[17..], b"hello");
+    bytes[19] = 0xff;
+
+    let result: Result<TableWithString, _> = from_slice(&bytes, false);
+    assert!(matches!(
+        result.unwrap_err().inner(),
+        serde_molecule::Error::InvalidUtf8 { offset: 19 }
+    ));
+
+    let options = DeserializerOptions::new().lossy_utf8(true);
+    let value2: TableWithString = from_slice_with(&bytes, &options).unwrap();
+    assert_eq!(value2.name, "he\u{fffd}lo");
+
+    // the decoded string is charged once, 2 bytes more than "hello"
+    let min_alloc = |bytes: &[u8]| {
+        (0..1000)
+            .find(|&max| {
+                let options = options.max_alloc(max);
+                from_slice_with::<TableWithString>(bytes, &options).is_ok()
+            })
+            .unwrap()
+    };
+    let valid = to_vec(&value, false).unwrap();
+    assert_eq!(min_alloc(&bytes), min_alloc(&valid) + 2);
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct TableV1 {
+    f1: u8,
+    f2: String,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct TableV2 {
+    f1: u8,
+    f2: String,
+    f3: Vec<u8>,
+}
+
+#[test]
+fn test_compatible_table() {
+    let v2 = TableV2 {
+        f1: 1,
+        f2: "hello".into(),
+        f3: vec![1, 2, 3],
+    };
+    let bytes = to_vec(&v2, false).unwrap();
+
+    // extra fields are ignored by default
+    let v1: TableV1 = from_slice(&bytes, false).unwrap();
+    assert_eq!(v1.f2, "hello");
+
+    let strict = DeserializerOptions::new().compatible(false);
+    let result: Result<TableV1, _> = from_slice_with(&bytes, &strict);
+    assert!(matches!(
+        result.unwrap_err().inner(),
+        serde_molecule::Error::MismatchedTableFieldCount
+    ));
+    let v2_again: TableV2 = from_slice_with(&bytes, &strict).unwrap();
+    assert_eq!(v2, v2_again);
+
+    // missing fields are never accepted
+    let bytes = to_vec(&v1, false).unwrap();
+    let result: Result<TableV2, _> = from_slice(&bytes, false);
+    assert!(result.is_err());
+}
@@ -0,0 +1,171 @@
+use ckb_gen_types::packed;
+use ckb_gen_types::prelude::*;
+use molecule::prelude::*;
+use serde_json::json;
+use serde_molecule::schema::Schema;
+use serde_molecule::transcode::{from_json, to_json};
+
+const SCHEMAS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/schemas/");
+
+#[test]
+fn test_transcode_ckb() {
+    let schema = Schema::load(format!("{}ckb/protocols.mol", SCHEMAS)).unwrap();
+    let output = packed::CellOutput::new_builder()
+        .capacity(0x0102u64.pack())
+        .lock(
+            packed::Script::new_builder()
+                .code_hash([0xab; 32].pack())
+                .hash_type(1.into())
+                .args(vec![1, 2, 3].pack())
+                .build(),
+        )
+        .build();
+    let tx = packed::Transaction::default();
+    let raw = tx
+        .raw()
+        .as_builder()
+        .outputs(vec![output].pack())
+        .outputs_data(vec![vec![7u8].pack()].pack())
+        .build();
+    let tx = tx
+        .as_builder()
+        .raw(raw)
+        .witnesses(vec![Vec::<u8>::new().pack()].pack())
+        .build();
+    let json = to_json(tx.as_slice(), &schema, "Transaction").unwrap();
+    assert_eq!(
+        json,
+        json!({
+            "raw": {
+                "version": "0x00000000",
+                "cell_deps": [],
+                "header_deps": [],
+                "inputs": [],
+                "outputs": [{
+                    "capacity": "0x0201000000000000",
+                    "lock": {
+                        "code_hash": format!("0x{}", "ab".repeat(32)),
+                        "hash_type": 1,
+                        "args": "0x010203",
+                    },
+                    "type_": null,
+                }],
+                "outputs_data": ["0x07"],
+            },
+            "witnesses": ["0x"],
+        })
+    );
+    assert_eq!(
+        from_json(&json, &schema, "Transaction").unwrap(),
+        tx.as_slice()
+    );
+
+    let message = packed::SyncMessage::new_builder()
+        .set(packed::InIBD::default())
+        .build();
+    let json = to_json(message.as_slice(), &schema, "SyncMessage").unwrap();
+    assert_eq!(json, json!({ "type": "InIBD", "value": {} }));
+    assert_eq!(
+        from_json(&json, &schema, "SyncMessage").unwrap(),
+        message.as_slice()
+    );
+
+    // upper case hex digits are accepted, the output is lowercase
+    let bytes = from_json(&json!("0xABCD"), &schema, "Bytes").unwrap();
+    assert_eq!(bytes, [2, 0, 0, 0, 0xab, 0xcd]);
+    assert_eq!(to_json(&bytes, &schema, "Bytes").unwrap(), json!("0xabcd"));
+}
+
+#[test]
+fn test_transcode_errors() {
+    let schema = Schema::load(format!("{}ckb/protocols.mol", SCHEMAS)).unwrap();
+    let script = json!({
+        "code_hash": format!("0x{}", "00".repeat(32)),
+        "hash_type": 0,
+        "args": "0x",
+    });
+    assert!(from_json(&script, &schema, "Script").is_ok());
+    let cell_output = |lock: serde_json::Value| {
+        json!({
+            "capacity": "0x0000000000000000",
+            "lock": lock,
+            "type_": null,
+        })
+    };
+    let cases = [
+        (
+            json!({ "hash_type": 256 }),
+            "Script",
+            "expected byte, found 256 at `hash_type`",
+        ),
+        (
+            json!({ "hash_type": "0x01" }),
+            "Script",
+            "expected byte, found string at `hash_type`",
+        ),
+        (
+            json!({ "args": "0x1" }),
+            "Script",
+            "invalid hex string for `Bytes` at `args`",
+        ),
+        (
+            json!({ "args": "0102" }),
+            "Script",
+            "invalid hex string for `Bytes` at `args`",
+        ),
+        (
+            json!({ "args": [1, 2] }),
+            "Script",
+            "expected hex string for `Bytes`, found array at `args`",
+        ),
+        (
+            json!({ "code_hash": "0x00" }),
+            "Script",
+            "array `Byte32` has 32 items, found 1 at `code_hash`",
+        ),
+        (
+            json!({ "extra": 1 }),
+            "Script",
+            "unknown field `extra` of `Script`",
+        ),
+        (
+            json!(null),
+            "Script",
+            "expected object for `Script`, found null",
+        ),
+        (
+            json!({ "type": "Script", "value": {} }),
+            "SyncMessage",
+            "`Script` isn't an item of union `SyncMessage`",
+        ),
+        (
+            json!({ "type": "InIBD" }),
+            "SyncMessage",
+            "expected object with `type` and `value` for union `SyncMessage`, found object",
+        ),
+        (
+            json!([{}]),
+            "CellOutputVec",
+            "missing field `capacity` of `CellOutput` at `[0]`",
+        ),
+        (
+            json!([cell_output(json!({ "hash_type": 1 }))]),
+            "CellOutputVec",
+            "missing field `code_hash` of `Script` at `[0].lock`",
+        ),
+    ];
+    for (patch, name, message) in cases {
+        let json = match (&script, &patch) {
+            (serde_json::Value::Object(base), serde_json::Value::Object(patch))
+                if name == "Script" =>
+            {
+                let mut json = base.clone();
+                json.extend(patch.clone());
+                serde_json::Value::Object(json)
+            }
+            _ => patch,
+        };
+        let error = from_json(&json, &schema, name).unwrap_err();
+        assert_eq!(error.to_string(), message, "{}", json);
+    }
+}
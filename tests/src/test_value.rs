@@ -0,0 +1,241 @@
+use crate::old::test1::{Struct1Opt, Table1};
+use crate::old::test1_default as old_default;
+use ckb_gen_types::packed;
+use ckb_gen_types::prelude::*;
+use molecule::prelude::*;
+use serde_molecule::schema::Schema;
+use serde_molecule::{
+    from_compatible_slice_with_schema, from_slice_with_schema, to_vec_with_schema, Error, Value,
+};
+
+const SCHEMAS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/schemas/");
+
+fn bytes(v: &[u8]) -> Vec<Value> {
+    v.iter().map(|b| Value::Byte(*b)).collect()
+}
+
+fn field<'a>(value: &'a Value, name: &str) -> &'a Value {
+    match value {
+        Value::Struct(fields) | Value::Table(fields) => {
+            &fields.iter().find(|(n, _)| n == name).unwrap().1
+        }
+        _ => panic!("{:?}", value),
+    }
+}
+
+#[test]
+fn test_value_test1() {
+    let schema = Schema::load(format!("{}test1.mol", SCHEMAS)).unwrap();
+    let old_value = Table1::new_builder()
+        .f2(old_default::DEFAULT_U16.clone())
+        .fixvec(old_default::DEFAULT_FIXVEC.clone())
+        .dynvec(old_default::DEFAULT_DYNVEC.clone())
+        .struct1(old_default::DEFAULT_STRUCT1.clone())
+        .struct1_opt(Struct1Opt::from_slice(old_default::DEFAULT_STRUCT1.as_slice()).unwrap())
+        .map(old_default::DEFAULT_MAP.clone())
+        .build();
+    let value = from_slice_with_schema(old_value.as_slice(), &schema, "Table1").unwrap();
+    assert_eq!(field(&value, "f1"), &Value::Byte(0));
+    assert_eq!(field(&value, "f2"), &Value::Array(bytes(&[0xcd, 0])));
+    assert_eq!(field(&value, "fixvec"), &Value::FixVec(bytes(&[0xcd; 3])));
+    assert_eq!(
+        field(&value, "dynvec"),
+        &Value::DynVec(vec![Value::FixVec(bytes(&[0xcd; 3])); 3])
+    );
+    let struct1 = Value::Struct(vec![
+        ("f1".into(), Value::Byte(0xcd)),
+        ("f2".into(), Value::Array(bytes(&[0xcd, 0]))),
+    ]);
+    assert_eq!(field(&value, "struct1"), &struct1);
+    assert_eq!(field(&value, "option"), &Value::Option(None));
+    assert_eq!(
+        field(&value, "struct1_opt"),
+        &Value::Option(Some(Box::new(struct1)))
+    );
+    match field(&value, "map") {
+        Value::DynVec(entries) => assert_eq!(entries.len(), 1),
+        value => panic!("{:?}", value),
+    }
+    assert_eq!(
+        to_vec_with_schema(&value, &schema, "Table1").unwrap(),
+        old_value.as_slice()
+    );
+
+    let old_enum = crate::old::test1::Enum1::new_builder()
+        .set(crate::old::test1::U32::from_slice(&[1, 2, 3, 4]).unwrap())
+        .build();
+    let value = from_slice_with_schema(old_enum.as_slice(), &schema, "Enum1").unwrap();
+    assert_eq!(
+        value,
+        Value::Union {
+            id: 1,
+            value: Box::new(Value::Array(bytes(&[1, 2, 3, 4])))
+        }
+    );
+    assert_eq!(
+        to_vec_with_schema(&value, &schema, "Enum1").unwrap(),
+        old_enum.as_slice()
+    );
+}
+
+#[test]
+fn test_value_ckb() {
+    let schema = Schema::load(format!("{}ckb/protocols.mol", SCHEMAS)).unwrap();
+    let output = packed::CellOutput::new_builder()
+        .capacity(42u64.pack())
+        .type_(
+            Some(
+                packed::Script::new_builder()
+                    .code_hash([2; 32].pack())
+                    .args(vec![1, 2, 3].pack())
+                    .build(),
+            )
+            .pack(),
+        )
+        .build();
+    let tx = packed::Transaction::default();
+    let raw = tx
+        .raw()
+        .as_builder()
+        .outputs(vec![output].pack())
+        .outputs_data(vec![vec![7u8].pack()].pack())
+        .build();
+    let tx = tx.as_builder().raw(raw).build();
+    let value = from_slice_with_schema(tx.as_slice(), &schema, "Transaction").unwrap();
+    let raw = field(&value, "raw");
+    let output = match field(raw, "outputs") {
+        Value::DynVec(outputs) => &outputs[0],
+        value => panic!("{:?}", value),
+    };
+    assert_eq!(
+        field(output, "capacity"),
+        &Value::Array(bytes(&42u64.to_le_bytes()))
+    );
+    match field(output, "type_") {
+        Value::Option(Some(script)) => {
+            assert_eq!(field(script, "args"), &Value::FixVec(bytes(&[1, 2, 3])))
+        }
+        value => panic!("{:?}", value),
+    }
+    assert_eq!(
+        field(raw, "outputs_data"),
+        &Value::DynVec(vec![Value::FixVec(bytes(&[7]))])
+    );
+    assert_eq!(
+        to_vec_with_schema(&value, &schema, "Transaction").unwrap(),
+        tx.as_slice()
+    );
+
+    // union with customized ids
+    let message = packed::SyncMessage::new_builder()
+        .set(packed::InIBD::default())
+        .build();
+    let value = from_slice_with_schema(message.as_slice(), &schema, "SyncMessage").unwrap();
+    assert_eq!(
+        value,
+        Value::Union {
+            id: 8,
+            value: Box::new(Value::Table(vec![]))
+        }
+    );
+    assert_eq!(
+        to_vec_with_schema(&value, &schema, "SyncMessage").unwrap(),
+        message.as_slice()
+    );
+}
+
+#[test]
+fn test_value_compatible() {
+    let old = Schema::parse("array Uint32 [byte; 4];\ntable T { a: Uint32 }").unwrap();
+    let new = Schema::parse("array Uint32 [byte; 4];\ntable T { a: Uint32, b: Uint32 }").unwrap();
+    let value = Value::Table(vec![
+        ("a".into(), Value::Array(bytes(&[1; 4]))),
+        ("b".into(), Value::Array(bytes(&[2; 4]))),
+    ]);
+    let data = to_vec_with_schema(&value, &new, "T").unwrap();
+    let error = from_slice_with_schema(&data, &old, "T").unwrap_err();
+    assert!(matches!(error, Error::MismatchedTableFieldCount));
+    assert_eq!(
+        from_compatible_slice_with_schema(&data, &old, "T").unwrap(),
+        Value::Table(vec![("a".into(), Value::Array(bytes(&[1; 4])))])
+    );
+    let data = to_vec_with_schema(
+        &Value::Table(vec![]),
+        &Schema::parse("table T {}").unwrap(),
+        "T",
+    );
+    let error = from_compatible_slice_with_schema(&data.unwrap(), &old, "T").unwrap_err();
+    assert!(matches!(error, Error::MismatchedTableFieldCount));
+}
+
+#[test]
+fn test_value_errors() {
+    let schema = Schema::load(format!("{}ckb/protocols.mol", SCHEMAS)).unwrap();
+    let output = packed::CellOutput::default();
+    let tx = packed::Transaction::default();
+    let raw = tx.raw().as_builder().outputs(vec![output].pack()).build();
+    let tx = tx.as_builder().raw(raw).build();
+    let mut data = tx.as_slice().to_vec();
+    // corrupt the full size in the header of the lock script
+    let script = packed::Script::default();
+    let pos = data
+        .windows(script.as_slice().len())
+        .position(|w| w == script.as_slice())
+        .unwrap();
+    data[pos] += 1;
+    let error = from_slice_with_schema(&data, &schema, "Transaction").unwrap_err();
+    assert_eq!(error.path(), Some("raw.outputs[0].lock"));
+    assert_eq!(error.offset(), Some(pos));
+    assert!(matches!(error.inner(), Error::InvalidTableLength { .. }));
+
+    let error = from_slice_with_schema(&[9, 0, 0, 0], &schema, "SyncMessage").unwrap_err();
+    assert!(matches!(error, Error::UnknownUnionId { id: 9 }));
+    let error = from_slice_with_schema(&[0; 4], &schema, "Uint64").unwrap_err();
+    assert!(matches!(
+        error,
+        Error::MismatchedLength {
+            expected: 8,
+            actual: 4
+        }
+    ));
+    let error = from_slice_with_schema(&[], &schema, "Unknown").unwrap_err();
+    assert_eq!(error.to_string(), "undefined type `Unknown`");
+
+    let cases = [
+        (
+            "Bytes",
+            Value::DynVec(vec![]),
+            "expected fixvec `Bytes`, found dynvec",
+        ),
+        (
+            "Uint32",
+            Value::Array(bytes(&[1])),
+            "array `Uint32` has 4 items, found 1",
+        ),
+        (
+            "OutPoint",
+            Value::Struct(vec![
+                ("index".into(), Value::Array(bytes(&[0; 4]))),
+                ("tx_hash".into(), Value::Array(bytes(&[0; 32]))),
+            ]),
+            "expected field `tx_hash` of `OutPoint`, found `index`",
+        ),
+        (
+            "Script",
+            Value::Table(vec![]),
+            "`Script` has 3 fields, found 0",
+        ),
+        (
+            "SyncMessage",
+            Value::Union {
+                id: 4,
+                value: Box::new(Value::Table(vec![])),
+            },
+            "UnknownUnionId { id: 4 }",
+        ),
+    ];
+    for (name, value, message) in cases {
+        let error = to_vec_with_schema(&value, &schema, name).unwrap_err();
+        assert_eq!(error.to_string(), message, "{}", name);
+    }
+}
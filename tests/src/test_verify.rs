@@ -0,0 +1,219 @@
+use crate::ckb_types::{CellInput, CellOutput, OutPoint, Script, Transaction};
+use serde::{Deserialize, Serialize};
+use serde_molecule::trace::{ContainerFormat, Format, Layout, VariantFormat};
+use serde_molecule::{
+    dynvec_serde, from_slice_with, to_vec, verify, DeserializerOptions, Error, Result,
+};
+use std::collections::BTreeMap;
+
+fn sample_tx() -> Transaction {
+    let script = Script {
+        code_hash: [5u8; 32],
+        hash_type: 1,
+        args: vec![1, 2, 3],
+    };
+    let mut tx = Transaction::default();
+    tx.raw.inputs = vec![CellInput {
+        since: 42,
+        previous_output: OutPoint {
+            tx_hash: [1u8; 32],
+            index: 1,
+        },
+    }];
+    tx.raw.outputs = vec![
+        CellOutput {
+            capacity: 42,
+            lock: script.clone(),
+            type_: Some(script.clone()),
+        },
+        CellOutput {
+            capacity: 100,
+            lock: script,
+            type_: None,
+        },
+    ];
+    tx.raw.outputs_data = vec![vec![], vec![1, 2]];
+    tx.witnesses = vec![vec![1, 2, 3]];
+    tx
+}
+
+#[test]
+fn test_trace_ckb_types() {
+    let layout = Layout::of::<Transaction>().unwrap();
+    assert_eq!(layout.root, Format::TypeName("Transaction"));
+    let names: Vec<_> = layout.registry.keys().copied().collect();
+    assert_eq!(
+        names,
+        [
+            "CellDep",
+            "CellInput",
+            "CellOutput",
+            "OutPoint",
+            "RawTransaction",
+            "Script",
+            "Transaction"
+        ]
+    );
+    assert_eq!(
+        layout.registry["CellInput"],
+        ContainerFormat::Struct(vec![
+            ("since", Format::U64),
+            (
+                "previous_output",
+                Format::Struct(Box::new(Format::TypeName("OutPoint")))
+            ),
+        ])
+    );
+    assert_eq!(
+        layout.registry["Transaction"],
+        ContainerFormat::Struct(vec![
+            ("raw", Format::TypeName("RawTransaction")),
+            (
+                "witnesses",
+                Format::Dynvec(Box::new(Format::Fixvec(Box::new(Format::U8))))
+            ),
+        ])
+    );
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+enum Inner {
+    X(u8),
+    Y(String),
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+enum Outer {
+    A,
+    B(Inner),
+    C { f1: BTreeMap<u8, Inner> },
+}
+
+#[test]
+fn test_trace_enum() {
+    let layout = Layout::of::<Outer>().unwrap();
+    assert_eq!(
+        layout.registry["Inner"],
+        ContainerFormat::Enum(vec![
+            ("X", VariantFormat::Newtype(Box::new(Format::U8))),
+            ("Y", VariantFormat::Newtype(Box::new(Format::Str))),
+        ])
+    );
+    let inner = Format::TypeName("Inner");
+    assert_eq!(
+        layout.registry["Outer"],
+        ContainerFormat::Enum(vec![
+            ("A", VariantFormat::Unit),
+            ("B", VariantFormat::Newtype(Box::new(inner.clone()))),
+            (
+                "C",
+                VariantFormat::Struct(vec![(
+                    "f1",
+                    Format::Map(Box::new(Format::U8), Box::new(inner))
+                )])
+            ),
+        ])
+    );
+
+    let options = DeserializerOptions::new();
+    for value in [
+        Outer::A,
+        Outer::B(Inner::Y("hello".into())),
+        Outer::C {
+            f1: [(1, Inner::X(1)), (2, Inner::Y("a".into()))].into(),
+        },
+    ] {
+        let bytes = to_vec(&value, false).unwrap();
+        verify::<Outer>(&bytes, &options).unwrap();
+    }
+}
+
+#[derive(Deserialize)]
+#[allow(dead_code)]
+enum Recursive {
+    Leaf(u8),
+    Node(Box<Recursive>),
+}
+
+#[test]
+fn test_trace_recursive() {
+    let error = Layout::of::<Recursive>().unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "recursive type `Recursive` isn't supported"
+    );
+}
+
+#[test]
+fn test_verify() {
+    let bytes = to_vec(&sample_tx(), false).unwrap();
+    let options = DeserializerOptions::new();
+    verify::<Transaction>(&bytes, &options).unwrap();
+
+    let error = verify::<Transaction>(&bytes[..bytes.len() - 1], &options).unwrap_err();
+    assert!(matches!(error.inner(), Error::InvalidTableLength { .. }));
+
+    let out_point = to_vec(&sample_tx().raw.inputs[0].previous_output, true).unwrap();
+    verify::<OutPoint>(&out_point, &options.is_struct(true)).unwrap();
+    let error = verify::<OutPoint>(&out_point[1..], &options.is_struct(true)).unwrap_err();
+    assert_eq!(error.path(), Some("index"));
+}
+
+// Every single byte change must be rejected by `verify` if and only if it's
+// rejected by `from_slice_with`, with the same error.
+fn check_mutations<T>(bytes: &[u8], options: &DeserializerOptions)
+where
+    T: for<'a> Deserialize<'a>,
+{
+    let layout = Layout::of::<T>().unwrap();
+    for i in 0..bytes.len() {
+        for value in [0u8, 1, 2, 0x7f, 0xff] {
+            let mut bytes = bytes.to_vec();
+            bytes[i] = value;
+            let expected: Result<T> = from_slice_with(&bytes, options);
+            let result = layout.verify(&bytes, options);
+            match (expected, result) {
+                (Ok(_), Ok(_)) => {}
+                (Err(e1), Err(e2)) => assert_eq!(e1.to_string(), e2.to_string()),
+                (e1, e2) => panic!("byte {} = {}: {:?} vs {:?}", i, value, e1.map(drop), e2),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_verify_mutations() {
+    let bytes = to_vec(&sample_tx(), false).unwrap();
+    check_mutations::<Transaction>(&bytes, &DeserializerOptions::new());
+    check_mutations::<Transaction>(&bytes, &DeserializerOptions::new().canonical(true));
+
+    let value = Outer::C {
+        f1: [(1, Inner::X(1)), (2, Inner::Y("a".into()))].into(),
+    };
+    let bytes = to_vec(&value, false).unwrap();
+    check_mutations::<Outer>(&bytes, &DeserializerOptions::new());
+    check_mutations::<Outer>(&bytes, &DeserializerOptions::new().canonical(true));
+}
+
+#[derive(Serialize, Deserialize)]
+struct Limited {
+    #[serde(with = "dynvec_serde")]
+    f1: Vec<Vec<u8>>,
+}
+
+#[test]
+fn test_verify_limits() {
+    let bytes = to_vec(
+        &Limited {
+            f1: vec![vec![1], vec![2, 3]],
+        },
+        false,
+    )
+    .unwrap();
+    let options = DeserializerOptions::new().max_items(1);
+    let error = verify::<Limited>(&bytes, &options).unwrap_err();
+    assert!(matches!(error.inner(), Error::ItemLimitExceeded));
+    let options = DeserializerOptions::new().max_depth(2);
+    let error = verify::<Limited>(&bytes, &options).unwrap_err();
+    assert!(matches!(error.inner(), Error::DepthLimitExceeded));
+}
@@ -0,0 +1,142 @@
+use crate::ckb_types::{CellInput, CellOutput, OutPoint, Script, Transaction, WitnessArgs};
+use serde_molecule::view::{DynvecView, FixvecView, TableView};
+use serde_molecule::{from_slice_with, to_vec, DeserializerOptions, Error};
+
+fn transaction() -> Transaction {
+    let script = Script {
+        code_hash: [5; 32],
+        hash_type: 1,
+        args: vec![1, 2, 3],
+    };
+    let mut tx = Transaction::default();
+    tx.raw.version = 7;
+    tx.raw.inputs = vec![CellInput {
+        since: 9,
+        previous_output: OutPoint {
+            tx_hash: [1; 32],
+            index: 2,
+        },
+    }];
+    tx.raw.outputs = vec![
+        CellOutput {
+            capacity: 42,
+            lock: script.clone(),
+            type_: Some(script.clone()),
+        },
+        CellOutput {
+            capacity: 100,
+            lock: script,
+            type_: None,
+        },
+    ];
+    tx.raw.outputs_data = vec![vec![], vec![4, 5]];
+    tx.witnesses = vec![vec![6; 3]];
+    tx
+}
+
+#[test]
+fn test_view_transaction() {
+    let tx = transaction();
+    let bytes = to_vec(&tx, false).unwrap();
+    let view = TableView::with_count(&bytes, 2).unwrap();
+    assert_eq!(view.len(), 2);
+    assert_eq!(view.as_slice(), &bytes[..]);
+
+    let raw = view.table(0).unwrap();
+    assert_eq!(raw.get::<u32>(0, false).unwrap(), 7);
+    let inputs = raw.fixvec(3, 44).unwrap();
+    assert_eq!(inputs.len(), 1);
+    let input: CellInput = from_struct(inputs.item(0).unwrap());
+    assert_eq!(input, tx.raw.inputs[0]);
+    let out_point: OutPoint = inputs
+        .get::<(u64, OutPoint)>(0)
+        .map(|(_, out_point)| out_point)
+        .unwrap();
+    assert_eq!(out_point, tx.raw.inputs[0].previous_output);
+
+    let outputs = raw.dynvec(4).unwrap();
+    assert_eq!(outputs.len(), 2);
+    for (i, output) in outputs.iter().enumerate() {
+        let value: CellOutput = serde_molecule::from_slice(output, false).unwrap();
+        assert_eq!(value, tx.raw.outputs[i]);
+    }
+    let output: CellOutput = outputs.get(1, false).unwrap();
+    assert_eq!(output, tx.raw.outputs[1]);
+    let lock = outputs.table(0).unwrap().table(1).unwrap();
+    assert_eq!(lock.item(0).unwrap(), &[5; 32]);
+    assert_eq!(lock.get::<u8>(1, false).unwrap(), 1);
+    let args = lock.fixvec(2, 1).unwrap();
+    assert_eq!(args.body(), &[1, 2, 3]);
+    assert_eq!(args.get::<u8>(2).unwrap(), 3);
+    assert_eq!(args.iter().collect::<Vec<_>>(), [&[1], &[2], &[3]]);
+    // the type script is none
+    assert!(outputs.table(1).unwrap().item(2).unwrap().is_empty());
+
+    let outputs_data = raw.dynvec(5).unwrap();
+    assert!(outputs_data.fixvec(0, 1).unwrap().is_empty());
+    assert_eq!(outputs_data.fixvec(1, 1).unwrap().body(), &[4, 5]);
+    let witnesses = view.dynvec(1).unwrap();
+    assert_eq!(witnesses.get::<Vec<u8>>(0, false).unwrap(), vec![6; 3]);
+}
+
+fn from_struct<'a, T: serde::Deserialize<'a>>(bytes: &'a [u8]) -> T {
+    serde_molecule::from_slice(bytes, true).unwrap()
+}
+
+#[test]
+fn test_view_compatible() {
+    // a newer `WitnessArgs` with an extra field
+    let bytes = serde_molecule::molecule::assemble_table(&[
+        to_vec(&Some(vec![1u8]), false).unwrap(),
+        vec![],
+        vec![],
+        vec![0xff],
+    ]);
+    let view = TableView::with_count(&bytes, 3).unwrap();
+    assert_eq!(view.len(), 4);
+    assert_eq!(
+        view.get::<Option<Vec<u8>>>(0, false).unwrap(),
+        Some(vec![1])
+    );
+    assert_eq!(view.item(3).unwrap(), &[0xff]);
+    let error = TableView::with_count(&bytes, 5).unwrap_err();
+    assert!(matches!(error, Error::MismatchedTableFieldCount));
+    let options = DeserializerOptions::new().compatible(false);
+    assert!(from_slice_with::<WitnessArgs>(&bytes, &options).is_err());
+}
+
+#[test]
+fn test_view_errors() {
+    let bytes = to_vec(&transaction(), false).unwrap();
+    let error = TableView::new(&bytes[..bytes.len() - 1]).unwrap_err();
+    assert!(matches!(error, Error::InvalidTableLength { .. }));
+
+    let view = TableView::new(&bytes).unwrap();
+    let error = view.item(2).unwrap_err();
+    assert!(matches!(
+        error,
+        Error::IndexOutOfBounds { index: 2, len: 2 }
+    ));
+    // the items are checked when they are viewed, the offset is in `raw`
+    let error = view.table(0).unwrap().fixvec(3, 40).unwrap_err();
+    assert_eq!(error.to_string(), "InvalidFixvec at offset 40, path `[3]`");
+    let error = view.get::<u32>(1, false).unwrap_err();
+    assert_eq!(error.path(), Some("[1]"));
+
+    let error = FixvecView::new(&[1, 0, 0, 0, 1], 2).unwrap_err();
+    assert!(matches!(error, Error::InvalidFixvec));
+    let error = FixvecView::new(&[0xff; 4], usize::MAX).unwrap_err();
+    assert!(matches!(error, Error::Overflow));
+    let view = FixvecView::new(&[2, 0, 0, 0, 1, 2], 1).unwrap();
+    let error = view.get::<u8>(2).unwrap_err();
+    assert!(matches!(
+        error,
+        Error::IndexOutOfBounds { index: 2, len: 2 }
+    ));
+    let error = view.get::<u16>(0).unwrap_err();
+    assert_eq!(error.offset(), Some(4));
+
+    let view = DynvecView::new(&[4, 0, 0, 0]).unwrap();
+    assert!(view.is_empty());
+    assert_eq!(view.iter().count(), 0);
+}
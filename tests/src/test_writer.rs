@@ -0,0 +1,108 @@
+use crate::ckb_types::{CellDep, CellInput, CellOutput, OutPoint, Script, Transaction};
+use serde::{Deserialize, Serialize};
+use serde_molecule::{from_slice, serialized_size, to_vec, to_writer, Error};
+use std::collections::BTreeMap;
+use std::io::{self, BufWriter, Cursor};
+
+fn sample_tx() -> Transaction {
+    let script = Script {
+        code_hash: [5u8; 32],
+        hash_type: 1,
+        args: vec![1, 2, 3],
+    };
+    let out_point = OutPoint {
+        tx_hash: [1u8; 32],
+        index: 42,
+    };
+    let mut tx = Transaction::default();
+    tx.raw.cell_deps = vec![CellDep {
+        out_point: out_point.clone(),
+        dep_type: 1,
+    }];
+    tx.raw.inputs = vec![CellInput {
+        since: 42,
+        previous_output: out_point,
+    }];
+    tx.raw.outputs = vec![
+        CellOutput {
+            capacity: 42,
+            lock: script.clone(),
+            type_: Some(script.clone()),
+        },
+        CellOutput {
+            capacity: 100,
+            lock: script,
+            type_: None,
+        },
+    ];
+    tx.raw.outputs_data = vec![vec![], vec![1, 2]];
+    tx.witnesses = vec![vec![], vec![1, 2, 3], vec![4, 5, 6, 7]];
+    tx
+}
+
+#[test]
+fn test_to_writer_io() {
+    let tx = sample_tx();
+    let bytes = to_vec(&tx, false).unwrap();
+
+    let mut cursor = Cursor::new(vec![]);
+    to_writer(&mut cursor, &tx, false).unwrap();
+    assert_eq!(cursor.into_inner(), bytes);
+
+    let mut writer = BufWriter::new(vec![]);
+    to_writer(&mut writer, &tx, false).unwrap();
+    assert_eq!(writer.into_inner().unwrap(), bytes);
+
+    let tx2: Transaction = from_slice(&bytes, false).unwrap();
+    assert_eq!(tx, tx2);
+}
+
+#[test]
+fn test_to_writer_struct() {
+    let out_point = OutPoint {
+        tx_hash: [7u8; 32],
+        index: 3,
+    };
+    let mut data = vec![];
+    to_writer(&mut data, &out_point, true).unwrap();
+    assert_eq!(data.len(), 36);
+    assert_eq!(data, to_vec(&out_point, true).unwrap());
+    assert_eq!(serialized_size(&out_point, true).unwrap(), 36);
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct TableWithMap {
+    f1: u8,
+    map: BTreeMap<u32, String>,
+}
+
+#[test]
+fn test_to_writer_map() {
+    let mut value = TableWithMap {
+        f1: 1,
+        map: BTreeMap::new(),
+    };
+    value.map.insert(1, "hello".into());
+    value.map.insert(2, "world".into());
+    let mut data = vec![];
+    to_writer(&mut data, &value, false).unwrap();
+    let value2: TableWithMap = from_slice(&data, false).unwrap();
+    assert_eq!(value, value2);
+}
+
+struct FailingWriter;
+
+impl io::Write for FailingWriter {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(io::ErrorKind::Other, "disk full"))
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_to_writer_error() {
+    let result = to_writer(&mut FailingWriter, &sample_tx(), false);
+    assert!(matches!(result.unwrap_err().inner(), Error::Io(_)));
+}